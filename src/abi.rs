@@ -0,0 +1,88 @@
+use serde_json::Value;
+use starknet::core::types::Felt;
+use starknet::core::utils::get_selector_from_name;
+use std::collections::HashMap;
+
+use crate::TestError;
+
+// Number of felts a function's inputs serialize to, according to a contract ABI
+// Both Cairo 1 ABIs (functions nested in interfaces) and Cairo 0 ABIs are understood
+// Returns None when an input has no fixed size, e.g. arrays
+pub fn calldata_len(abi: &Value, function: &str) -> Result<Option<usize>, TestError> {
+    let entries = abi.as_array().ok_or("ABI is not a JSON array")?;
+    let mut structs = HashMap::new();
+    let mut functions = Vec::new();
+    collect(entries, &mut structs, &mut functions);
+
+    let inputs = functions
+        .into_iter()
+        .find(|f| f["name"] == function)
+        .map(|f| &f["inputs"])
+        .ok_or_else(|| format!("function `{}` not found in ABI", function))?;
+    let mut total = 0;
+    for input in inputs.as_array().into_iter().flatten() {
+        let ty = input["type"].as_str().unwrap_or_default();
+        match type_len(ty, &structs) {
+            Some(len) => total += len,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(total))
+}
+
+// Name of the function with this selector, None when the ABI has no such function
+pub fn function_name(abi: &Value, selector: Felt) -> Option<String> {
+    let mut structs = HashMap::new();
+    let mut functions = Vec::new();
+    collect(abi.as_array()?, &mut structs, &mut functions);
+    functions
+        .into_iter()
+        .filter_map(|f| f["name"].as_str())
+        .find(|name| get_selector_from_name(name).is_ok_and(|s| s == selector))
+        .map(str::to_string)
+}
+
+fn collect<'a>(
+    entries: &'a [Value],
+    structs: &mut HashMap<&'a str, &'a Value>,
+    functions: &mut Vec<&'a Value>,
+) {
+    for entry in entries {
+        match entry["type"].as_str() {
+            Some("struct") => {
+                if let Some(name) = entry["name"].as_str() {
+                    structs.insert(name, &entry["members"]);
+                }
+            }
+            Some("function") => functions.push(entry),
+            Some("interface") => {
+                if let Some(items) = entry["items"].as_array() {
+                    collect(items, structs, functions);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn type_len(ty: &str, structs: &HashMap<&str, &Value>) -> Option<usize> {
+    match ty {
+        "core::integer::u256" | "Uint256" => return Some(2),
+        "core::bool" => return Some(1),
+        _ => {}
+    }
+    if let Some(members) = structs.get(ty) {
+        return members
+            .as_array()?
+            .iter()
+            .map(|m| type_len(m["type"].as_str().unwrap_or_default(), structs))
+            .sum();
+    }
+    // Arrays, spans, options and enums have a variable size
+    let variable = ["Array<", "Span<", "Option<", "*", "ByteArray"];
+    if variable.iter().any(|v| ty.contains(v)) {
+        return None;
+    }
+    // felt252, addresses, class hashes and the smaller integer types take one felt
+    Some(1)
+}
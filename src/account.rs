@@ -0,0 +1,173 @@
+use clap::Args;
+use serde::Deserialize;
+use starknet::core::types::{BlockId, BlockTag, Felt, FunctionCall};
+use starknet::core::utils::{get_contract_address, get_selector_from_name};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider};
+use starknet::signers::SigningKey;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::signer::{LocalSigner, MultiSigner, RemoteSigner, Signer, SignerKind};
+use crate::TestError;
+
+// Test account used when neither an address nor a class hash is given
+const DEFAULT_ACCOUNT: &str = "0x059e0eaf58972c3b7de923ad6a280476430295f7ea967b768bd381bf5d90d50b";
+
+// Getters exposing the signer of common account implementations
+const KEY_GETTERS: [&str; 3] = ["get_public_key", "getPublicKey", "get_owner"];
+
+#[derive(Args, Clone)]
+pub struct AccountArgs {
+    /// Address of the account controlled by PRIVATE_KEY
+    #[arg(long, conflicts_with = "account_class_hash")]
+    pub account_address: Option<String>,
+
+    /// Derive the address from the key instead, for accounts deployed from this class
+    /// with the public key as only constructor argument
+    #[arg(long)]
+    pub account_class_hash: Option<String>,
+
+    /// Deployment salt used for the derivation, defaults to the public key
+    #[arg(long, requires = "account_class_hash")]
+    pub account_salt: Option<String>,
+
+    /// How the account's transactions are signed. Accounts of an --accounts file always
+    /// sign with their own key
+    #[arg(long, value_enum, default_value = "local")]
+    pub signer: SignerKind,
+
+    /// Signing service of --signer remote, PRIVATE_KEY isn't needed then
+    #[arg(long, required_if_eq("signer", "remote"))]
+    pub remote_signer_url: Option<String>,
+
+    /// Env var holding one more key of a --signer multisig account, can be repeated
+    #[arg(long)]
+    pub cosigner_key_env: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct Account {
+    pub address: Felt,
+    pub signer: Arc<dyn Signer>,
+}
+
+// Entry of an accounts file: `[{"address": "0x...", "private_key": "0x..."}]`
+#[derive(Deserialize)]
+struct AccountEntry {
+    address: String,
+    private_key: String,
+}
+
+impl Account {
+    pub fn new(address: &str, private_key: &str) -> Result<Account, TestError> {
+        Ok(Account {
+            address: Felt::from_hex(address)?,
+            signer: Arc::new(LocalSigner(signing_key(private_key)?)),
+        })
+    }
+
+    pub fn public_key(&self) -> Option<Felt> {
+        self.signer.public_key()
+    }
+}
+
+fn signing_key(private_key: &str) -> Result<SigningKey, TestError> {
+    if private_key.is_empty() {
+        return Err("PRIVATE_KEY is not set".into());
+    }
+    Ok(SigningKey::from_secret_scalar(Felt::from_hex(private_key)?))
+}
+
+impl AccountArgs {
+    pub fn resolve(&self, private_key: &str) -> Result<Account, TestError> {
+        let signer: Arc<dyn Signer> = match self.signer {
+            SignerKind::Local => Arc::new(LocalSigner(signing_key(private_key)?)),
+            SignerKind::Multisig => {
+                let mut keys = vec![signing_key(private_key)?];
+                for var in &self.cosigner_key_env {
+                    let key = std::env::var(var).map_err(|_| format!("{} is not set", var))?;
+                    keys.push(signing_key(&key)?);
+                }
+                Arc::new(MultiSigner(keys))
+            }
+            SignerKind::Remote => Arc::new(RemoteSigner::new(
+                self.remote_signer_url.as_deref().unwrap_or_default(),
+            )?),
+        };
+        let Some(class_hash) = &self.account_class_hash else {
+            let address = self.account_address.as_deref().unwrap_or(DEFAULT_ACCOUNT);
+            return Ok(Account {
+                address: Felt::from_hex(address)?,
+                signer,
+            });
+        };
+
+        let public_key = signer
+            .public_key()
+            .ok_or("--account-class-hash needs the public key, give --account-address instead")?;
+        let salt = match &self.account_salt {
+            Some(salt) => Felt::from_hex(salt)?,
+            None => public_key,
+        };
+        let address =
+            get_contract_address(salt, Felt::from_hex(class_hash)?, &[public_key], Felt::ZERO);
+        Ok(Account { address, signer })
+    }
+}
+
+pub fn load_accounts(path: &Path) -> Result<Vec<Account>, TestError> {
+    let entries: Vec<AccountEntry> = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if entries.is_empty() {
+        return Err(format!("no accounts in {}", path.display()).into());
+    }
+    entries
+        .iter()
+        .map(|entry| Account::new(&entry.address, &entry.private_key))
+        .collect()
+}
+
+// Value parser of address and token arguments
+pub fn parse_felt(raw: &str) -> Result<Felt, String> {
+    Felt::from_hex(raw).map_err(|_| format!("`{}` is not a hex felt", raw))
+}
+
+// Checks on-chain that the account's signer is our key
+// Accounts exposing none of the known getters can't be checked and only produce a warning
+pub async fn verify_account(
+    provider: &JsonRpcClient<HttpTransport>,
+    account: &Account,
+) -> Result<(), TestError> {
+    let Some(public_key) = account.public_key() else {
+        eprintln!(
+            "Warning: the signer of {:#x} is remote, its key can't be verified",
+            account.address
+        );
+        return Ok(());
+    };
+    for getter in KEY_GETTERS {
+        let call = FunctionCall {
+            contract_address: account.address,
+            entry_point_selector: get_selector_from_name(getter)?,
+            calldata: vec![],
+        };
+        let Ok(result) = provider.call(call, BlockId::Tag(BlockTag::Pending)).await else {
+            continue;
+        };
+        return match result.first() {
+            Some(key) if *key == public_key => Ok(()),
+            Some(key) => Err(format!(
+                "account {:#x} is controlled by key {:#x}, not by the configured private key",
+                account.address, key
+            )
+            .into()),
+            None => Err(format!("{} of {:#x} returned nothing", getter, account.address).into()),
+        };
+    }
+    eprintln!(
+        "Warning: could not verify the signer of {:#x}, is it deployed?",
+        account.address
+    );
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use clap::{Args, Parser};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::init::expand_config;
+use crate::ramp::{parse_duration, parse_ratio, LiveStats};
+use crate::run::{run_with_sinks, LinearArgs};
+use crate::sink::quiet_sinks;
+use crate::TestError;
+
+#[derive(Args)]
+pub struct AssertArgs {
+    /// Rate the paymaster has to sustain
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub tps: u32,
+
+    /// How long it has to sustain it, e.g. `10m`
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Duration,
+
+    /// Lowest share of successful transactions that passes, the successful ones also have
+    /// to reach this share of --tps
+    #[arg(long, default_value = "0.99", value_parser = parse_ratio)]
+    pub min_success: f64,
+
+    /// Options of `linear` for everything else, after `--`, e.g. `-- --config campaign.json`
+    /// or `-- --endpoint https://paymaster.example --accounts pool.json`. The ramp is
+    /// replaced by a single step at --tps, a campaign with one of its own can't be used
+    #[arg(last = true)]
+    pub linear: Vec<String>,
+}
+
+#[derive(Parser)]
+#[command(name = "linear")]
+struct Run {
+    #[command(flatten)]
+    args: LinearArgs,
+}
+
+// One step at a constant rate and a one line verdict, for release pipelines that only
+// need to know whether the paymaster still handles the contracted load. The results go
+// nowhere unless the `linear` options ask for them, e.g. with --output
+pub async fn assert_load(args: AssertArgs) -> Result<bool, TestError> {
+    let mut argv = vec!["paymaster-stress".to_string(), "linear".to_string()];
+    argv.extend(args.linear);
+    argv.extend([
+        "--step-tps".to_string(),
+        args.tps.to_string(),
+        "--step-durations".to_string(),
+        format!("{}ms", args.duration.as_millis()),
+    ]);
+    let run = Run::try_parse_from(&expand_config(argv)?[1..]).map_err(|e| e.to_string())?;
+    let sinks = quiet_sinks(&run.args);
+    let results = run_with_sinks(run.args, Arc::new(LiveStats::default()), sinks).await?;
+
+    let Some(step) = results.results.first() else {
+        println!(
+            "FAIL: the run stopped before sending, {:?}",
+            results.stop_reason
+        );
+        return Ok(false);
+    };
+    let metrics = &step.metrics;
+    let passed = metrics.success_rate >= args.min_success
+        && metrics.effective_tps >= args.tps as f64 * args.min_success;
+    println!(
+        "{}: {} TPS for {:.0}s, {:.2}% success, {:.1} successful TPS, p95 {:.0} ms",
+        if passed { "PASS" } else { "FAIL" },
+        args.tps,
+        args.duration.as_secs_f64(),
+        metrics.success_rate * 100.0,
+        metrics.effective_tps,
+        metrics.p95_latency_ms
+    );
+    Ok(passed)
+}
@@ -0,0 +1,204 @@
+use clap::Args;
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::core::params::{BatchRequestBuilder, ObjectParams};
+use paymaster_rpc::{ExecuteRequest, ExecuteResponse, PaymasterAPIClient};
+use starknet::core::types::{Call, Felt};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+use crate::account::{load_accounts, AccountArgs};
+use crate::client::{build_client, ClientArgs, PaymasterClient};
+use crate::network::NetworkArgs;
+use crate::scenario::{RECIPIENT, TRANSFER};
+use crate::sketch::LatencySketch;
+use crate::transaction::{prepare_execution, Fee};
+use crate::types::*;
+use crate::TestError;
+
+const EXECUTE_METHOD: &str = "paymaster_executeTransaction";
+
+#[derive(Args)]
+pub struct BatchingArgs {
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// JSON file of `{address, private_key}` entries, a batch takes one transaction from
+    /// each of as many accounts, so it needs at least the largest batch size
+    #[arg(long)]
+    pub accounts: Option<PathBuf>,
+
+    /// Transactions executed per request, 1 sends plain executeTransaction calls as the
+    /// baseline the batches are compared to
+    #[arg(long, value_delimiter = ',', default_value = "1,5,10", value_parser = clap::value_parser!(u32).range(1..))]
+    pub batch_sizes: Vec<u32>,
+
+    /// Requests sent per batch size, one after the other
+    #[arg(long, default_value = "20", value_parser = clap::value_parser!(u32).range(1..))]
+    pub requests: u32,
+
+    /// Pause between two requests
+    #[arg(long, default_value = "100")]
+    pub interval_ms: u64,
+
+    /// Write the results to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Executes transfers K at a time in one JSON-RPC batch of executeTransaction calls, for
+// every batch size K, and compares latency and throughput of the execute requests against
+// single executions. Transactions are built and signed before the request is timed
+pub async fn probe_batching(mut args: BatchingArgs) -> Result<BatchingResults, TestError> {
+    let run_tag = args.client.tag_run();
+    let client = build_client(&args.client).await?;
+    let network = args.network.resolve()?;
+    let largest = args.batch_sizes.iter().copied().max().unwrap_or(1);
+    // A batch goes out in one request, as if its transactions were sent in the same second
+    let transactions = args
+        .batch_sizes
+        .iter()
+        .map(|&size| size as u64)
+        .sum::<u64>()
+        * args.requests as u64;
+    args.network
+        .guard_production_total(&args.client.endpoint, largest, transactions)?;
+    let accounts = match &args.accounts {
+        Some(path) => load_accounts(path)?,
+        None => {
+            let config = envy::from_env::<Config>()?;
+            vec![args.account.resolve(&config.private_key)?]
+        }
+    };
+    if largest as usize > accounts.len() {
+        return Err(format!(
+            "batches of {} transactions need as many accounts, {} given",
+            largest,
+            accounts.len()
+        )
+        .into());
+    }
+    let fee = Fee::GasToken(network.gas_token);
+    // Distinct amounts keep a build response cache out of the measurement
+    let mut amount = 0;
+    let mut transfer = || {
+        amount += 1;
+        vec![Call {
+            to: network.gas_token,
+            selector: TRANSFER,
+            calldata: vec![RECIPIENT, Felt::from(amount), Felt::ZERO],
+        }]
+    };
+
+    println!(
+        "Probing {} with batches of {:?} transactions",
+        args.client.endpoint, args.batch_sizes
+    );
+    let mut sizes = Vec::new();
+    for &batch_size in &args.batch_sizes {
+        let mut latencies = LatencySketch::new(0.01);
+        let mut transactions = 0;
+        let mut failed = 0;
+        let mut executing = Duration::ZERO;
+        for _ in 0..args.requests {
+            let mut requests = Vec::new();
+            for account in &accounts[..batch_size as usize] {
+                match prepare_execution(&client, account, transfer(), fee).await {
+                    Ok(request) => requests.push(request),
+                    Err(_) => failed += 1,
+                }
+            }
+            transactions += batch_size;
+            if requests.is_empty() {
+                continue;
+            }
+            let start = Instant::now();
+            failed += execute(&client, requests, batch_size == 1).await?;
+            let latency = start.elapsed();
+            executing += latency;
+            latencies.add(latency.as_secs_f64() * 1000.0);
+            sleep(Duration::from_millis(args.interval_ms)).await;
+        }
+        let executed = transactions - failed;
+        sizes.push(BatchSizeResult {
+            batch_size,
+            transactions,
+            failed,
+            avg_request_ms: latencies.mean(),
+            p50_request_ms: latencies.median(),
+            p95_request_ms: latencies.quantile(0.95),
+            throughput_tps: if executing > Duration::ZERO {
+                executed as f64 / executing.as_secs_f64()
+            } else {
+                0.0
+            },
+            throughput_gain: None,
+        });
+    }
+    let single = sizes
+        .iter()
+        .find(|s| s.batch_size == 1)
+        .map(|s| s.throughput_tps)
+        .filter(|tps| *tps > 0.0);
+    for size in &mut sizes {
+        size.throughput_gain = single.map(|single| size.throughput_tps / single);
+        println!(
+            "Batch of {}: request p50 {:.1} ms, {:.1} tx/s executed{}",
+            size.batch_size,
+            size.p50_request_ms,
+            size.throughput_tps,
+            size.throughput_gain
+                .map(|gain| format!(", {:.2}x single executes", gain))
+                .unwrap_or_default()
+        );
+    }
+
+    let results = BatchingResults {
+        endpoint: args.client.endpoint.clone(),
+        run_tag,
+        requests: args.requests,
+        sizes,
+    };
+    if let Some(path) = &args.output {
+        fs::write(path, serde_json::to_string_pretty(&results)?)?;
+        println!("Results saved to: {}", path.display());
+    }
+    Ok(results)
+}
+
+// Number of the transactions that failed. A paymaster rejecting batches altogether ends
+// the probe, there is nothing to compare
+async fn execute(
+    client: &PaymasterClient,
+    requests: Vec<ExecuteRequest>,
+    single: bool,
+) -> Result<u32, TestError> {
+    if single {
+        let mut failed = 0;
+        for request in requests {
+            if client.execute_transaction(request).await.is_err() {
+                failed += 1;
+            }
+        }
+        return Ok(failed);
+    }
+    let mut batch = BatchRequestBuilder::new();
+    for request in &requests {
+        let mut params = ObjectParams::new();
+        params.insert("transaction", &request.transaction)?;
+        params.insert("parameters", &request.parameters)?;
+        batch.insert(EXECUTE_METHOD, params)?;
+    }
+    let response = client
+        .batch_request::<ExecuteResponse>(batch)
+        .await
+        .map_err(|e| format!("batch request failed: {}", e))?;
+    Ok(response.num_failed_calls() as u32)
+}
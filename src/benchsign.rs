@@ -0,0 +1,161 @@
+use clap::Args;
+use starknet::core::types::{Felt, TypedData};
+use starknet::macros::felt;
+use starknet::signers::SigningKey;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+
+use crate::signer::{LocalSigner, MultiSigner, Signer};
+use crate::types::SigningBenchmark;
+use crate::TestError;
+
+// Account the typed data is hashed for, any address costs the same
+const ACCOUNT: Felt = felt!("0x0591d2a7bd2ac6b9e4a2b4e5b8e7e0c1a3f5d6e7f8091a2b3c4d5e6f7a8b9c0d");
+
+// Shape of the outside execution a paymaster hands back for a transfer, SNIP-12 revision 1
+const TYPED_DATA: &str = r#"{
+  "types": {
+    "StarknetDomain": [
+      { "name": "name", "type": "shortstring" },
+      { "name": "version", "type": "shortstring" },
+      { "name": "chainId", "type": "shortstring" },
+      { "name": "revision", "type": "shortstring" }
+    ],
+    "OutsideExecution": [
+      { "name": "Caller", "type": "ContractAddress" },
+      { "name": "Nonce", "type": "felt" },
+      { "name": "Execute After", "type": "u128" },
+      { "name": "Execute Before", "type": "u128" },
+      { "name": "Calls", "type": "Call*" }
+    ],
+    "Call": [
+      { "name": "To", "type": "ContractAddress" },
+      { "name": "Selector", "type": "selector" },
+      { "name": "Calldata", "type": "felt*" }
+    ]
+  },
+  "primaryType": "OutsideExecution",
+  "domain": {
+    "name": "Account.execute_from_outside",
+    "version": "2",
+    "chainId": "SN_SEPOLIA",
+    "revision": "1"
+  },
+  "message": {
+    "Caller": "0x414e595f43414c4c4552",
+    "Nonce": "0x5f1c3a",
+    "Execute After": "0x1",
+    "Execute Before": "0x67d2b1c0",
+    "Calls": [
+      {
+        "To": "0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d",
+        "Selector": "transfer",
+        "Calldata": ["0x2a", "0x1", "0x0"]
+      }
+    ]
+  }
+}"#;
+
+#[derive(Args)]
+pub struct BenchSigningArgs {
+    /// How long each thread hashes and signs
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u64).range(1..))]
+    pub duration_secs: u64,
+
+    /// Threads signing at the same time, defaults to the number of CPUs like the runtime's
+    /// worker threads
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+
+    /// Keys signing every transaction, more than one measures a --signer multisig account
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub keys: u32,
+}
+
+// Hashes the typed data of a transaction and signs it as fast as the machine allows, the
+// client-side work of every transaction of a run. A --max-tps above what comes out here
+// measures the generator rather than the paymaster
+pub async fn bench_signing(args: &BenchSigningArgs) -> Result<SigningBenchmark, TestError> {
+    let typed_data: TypedData = serde_json::from_str(TYPED_DATA)?;
+    let keys: Vec<SigningKey> = (0..args.keys).map(|_| SigningKey::from_random()).collect();
+    let signer: Arc<dyn Signer> = match keys.len() {
+        1 => Arc::new(LocalSigner(keys[0].clone())),
+        _ => Arc::new(MultiSigner(keys)),
+    };
+    let threads = match args.threads {
+        Some(threads) => threads,
+        None => thread::available_parallelism()?.get() as u32,
+    };
+    let duration = Duration::from_secs(args.duration_secs);
+    println!(
+        "Hashing and signing with {} key(s) on {} thread(s) for {}s",
+        args.keys, threads, args.duration_secs
+    );
+
+    let mut workers = Vec::new();
+    for _ in 0..threads {
+        let typed_data = typed_data.clone();
+        let signer = Arc::clone(&signer);
+        let handle = Handle::current();
+        workers.push(tokio::task::spawn_blocking(move || {
+            sign_for(&typed_data, signer.as_ref(), &handle, duration)
+        }));
+    }
+    let mut signed = 0;
+    let mut hash_time = Duration::ZERO;
+    let mut sign_time = Duration::ZERO;
+    for worker in workers {
+        let (count, hashing, signing) = worker.await?.map_err(|e| e.to_string())?;
+        signed += count;
+        hash_time += hashing;
+        sign_time += signing;
+    }
+
+    let signed_per_sec = signed as f64 / duration.as_secs_f64();
+    let report = SigningBenchmark {
+        threads,
+        keys: args.keys,
+        duration_secs: args.duration_secs,
+        signed,
+        signed_per_sec,
+        per_thread_per_sec: signed_per_sec / threads as f64,
+        avg_hash_us: hash_time.as_secs_f64() * 1e6 / signed.max(1) as f64,
+        avg_sign_us: sign_time.as_secs_f64() * 1e6 / signed.max(1) as f64,
+    };
+    println!(
+        "{:.0} transactions/s ({:.0} per thread), {:.0} us hashing and {:.0} us signing each",
+        report.signed_per_sec, report.per_thread_per_sec, report.avg_hash_us, report.avg_sign_us
+    );
+    println!(
+        "Runs above ~{:.0} TPS are limited by this machine, not the paymaster",
+        report.signed_per_sec
+    );
+    Ok(report)
+}
+
+// Transactions hashed and signed before `duration` ran out, with the time spent on each
+fn sign_for(
+    typed_data: &TypedData,
+    signer: &dyn Signer,
+    handle: &Handle,
+    duration: Duration,
+) -> Result<(u64, Duration, Duration), String> {
+    let start = Instant::now();
+    let mut signed = 0;
+    let mut hashing = Duration::ZERO;
+    let mut signing = Duration::ZERO;
+    while start.elapsed() < duration {
+        let hash_start = Instant::now();
+        let message_hash = typed_data
+            .message_hash(ACCOUNT)
+            .map_err(|e| e.to_string())?;
+        let sign_start = Instant::now();
+        handle.block_on(signer.sign(ACCOUNT, message_hash))?;
+        signing += sign_start.elapsed();
+        hashing += sign_start - hash_start;
+        signed += 1;
+    }
+    Ok((signed, hashing, signing))
+}
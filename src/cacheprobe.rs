@@ -0,0 +1,178 @@
+use clap::Args;
+use starknet::core::types::{Call, Felt};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::account::AccountArgs;
+use crate::client::{build_client, ClientArgs};
+use crate::network::NetworkArgs;
+use crate::scenario::{Leg, RECIPIENT, TRANSFER};
+use crate::sketch::LatencySketch;
+use crate::transaction::{run_legs, Fee, Pipeline};
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Args)]
+pub struct CacheProbeArgs {
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// Build requests sent of each kind
+    #[arg(long, default_value = "100", value_parser = clap::value_parser!(u32).range(1..))]
+    pub requests: u32,
+
+    /// Pause between two pairs of requests, the probe measures latency and not load
+    #[arg(long, default_value = "100")]
+    pub interval_ms: u64,
+
+    /// Identical requests must have a median this much lower than the variants, as a
+    /// share of the variants' median, for caching to be reported
+    #[arg(long, default_value = "0.2")]
+    pub min_speedup: f64,
+
+    /// Write the results to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Sends the same build request over and over, interleaved with variants that only differ
+// in the transferred amount. A paymaster caching build responses answers the repeats
+// faster than the variants, and the built-in scenarios, which repeat the same calldata,
+// then measure the cache rather than the paymaster
+pub async fn probe_cache(mut args: CacheProbeArgs) -> Result<CacheProbeResults, TestError> {
+    let run_tag = args.client.tag_run();
+    let client = build_client(&args.client).await?;
+    let network = args.network.resolve()?;
+    // Only builds, nothing is executed
+    args.network
+        .guard_production_total(&args.client.endpoint, 1, 0)?;
+    let config = envy::from_env::<Config>()?;
+    let account = args.account.resolve(&config.private_key)?;
+    let transfer = |amount: u64| Leg {
+        account: 0,
+        calls: vec![Call {
+            to: network.gas_token,
+            selector: TRANSFER,
+            calldata: vec![RECIPIENT, Felt::from(amount), Felt::ZERO],
+        }],
+    };
+    let fee = Fee::GasToken(network.gas_token);
+    let pipeline = Pipeline::default();
+    let accounts = [account];
+    let build = |leg: Leg| {
+        let client = &client;
+        let pipeline = &pipeline;
+        let accounts = &accounts;
+        async move {
+            run_legs(
+                client,
+                pipeline,
+                accounts,
+                vec![leg],
+                fee,
+                false,
+                true,
+                false,
+            )
+            .await
+            .result
+        }
+    };
+
+    println!(
+        "Probing {} for cached build responses",
+        args.client.endpoint
+    );
+    // The first identical request fills the cache if there is one
+    build(transfer(1))
+        .await
+        .map_err(|e| format!("build request failed: {}", e.name()))?;
+    let mut identical = ProbeSample::new();
+    let mut variant = ProbeSample::new();
+    for i in 0..args.requests {
+        // Alternating which one goes first keeps drifts of the paymaster out of the comparison
+        let variant_leg = transfer(2 + i as u64);
+        if i % 2 == 0 {
+            identical.add(build(transfer(1)).await);
+            variant.add(build(variant_leg).await);
+        } else {
+            variant.add(build(variant_leg).await);
+            identical.add(build(transfer(1)).await);
+        }
+        sleep(Duration::from_millis(args.interval_ms)).await;
+    }
+
+    let identical = identical.finish();
+    let variant = variant.finish();
+    let p50_speedup = if variant.p50_latency_ms > 0.0 {
+        1.0 - identical.p50_latency_ms / variant.p50_latency_ms
+    } else {
+        0.0
+    };
+    let results = CacheProbeResults {
+        endpoint: args.client.endpoint.clone(),
+        run_tag,
+        caching_suspected: p50_speedup >= args.min_speedup,
+        p50_speedup,
+        identical,
+        variant,
+    };
+    println!(
+        "Identical requests p50 {:.1} ms, variants p50 {:.1} ms: {}",
+        results.identical.p50_latency_ms,
+        results.variant.p50_latency_ms,
+        if results.caching_suspected {
+            "build responses look cached, results of repeated calldata are flattered"
+        } else {
+            "no sign of caching"
+        }
+    );
+    if let Some(path) = &args.output {
+        fs::write(path, serde_json::to_string_pretty(&results)?)?;
+        println!("Results saved to: {}", path.display());
+    }
+    Ok(results)
+}
+
+// Latencies of one kind of request of a probe
+pub struct ProbeSample {
+    latencies: LatencySketch,
+    sent: u32,
+    failed: u32,
+}
+
+impl ProbeSample {
+    pub fn new() -> ProbeSample {
+        ProbeSample {
+            latencies: LatencySketch::new(0.01),
+            sent: 0,
+            failed: 0,
+        }
+    }
+
+    pub fn add(&mut self, result: Result<f64, TransactionError>) {
+        self.sent += 1;
+        match result {
+            Ok(latency) => self.latencies.add(latency),
+            Err(_) => self.failed += 1,
+        }
+    }
+
+    pub fn finish(self) -> CacheProbeSample {
+        CacheProbeSample {
+            sent: self.sent,
+            failed: self.failed,
+            avg_latency_ms: self.latencies.mean(),
+            p50_latency_ms: self.latencies.median(),
+            p95_latency_ms: self.latencies.quantile(0.95),
+        }
+    }
+}
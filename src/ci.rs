@@ -0,0 +1,77 @@
+use std::env;
+
+use crate::types::CiMetadata;
+
+// CI systems by the variable they always set, followed by the ones holding the commit,
+// the branch and the job URL
+const PROVIDERS: [(&str, &str, &str, &str, &str); 4] = [
+    (
+        "GITLAB_CI",
+        "gitlab",
+        "CI_COMMIT_SHA",
+        "CI_COMMIT_REF_NAME",
+        "CI_JOB_URL",
+    ),
+    (
+        "CIRCLECI",
+        "circleci",
+        "CIRCLE_SHA1",
+        "CIRCLE_BRANCH",
+        "CIRCLE_BUILD_URL",
+    ),
+    (
+        "BUILDKITE",
+        "buildkite",
+        "BUILDKITE_COMMIT",
+        "BUILDKITE_BRANCH",
+        "BUILDKITE_BUILD_URL",
+    ),
+    (
+        "JENKINS_URL",
+        "jenkins",
+        "GIT_COMMIT",
+        "GIT_BRANCH",
+        "BUILD_URL",
+    ),
+];
+
+// Pipeline that started the run, read from the variables common CI systems set, so a
+// result file leads back to the build of the paymaster under test. None outside CI
+pub fn detect() -> Option<CiMetadata> {
+    let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+    // GitHub only has the parts of the job URL
+    if var("GITHUB_ACTIONS").is_some() {
+        let job_url = match (
+            var("GITHUB_SERVER_URL"),
+            var("GITHUB_REPOSITORY"),
+            var("GITHUB_RUN_ID"),
+        ) {
+            (Some(server), Some(repository), Some(run)) => {
+                Some(format!("{}/{}/actions/runs/{}", server, repository, run))
+            }
+            _ => None,
+        };
+        return Some(CiMetadata {
+            provider: "github".to_string(),
+            commit: var("GITHUB_SHA"),
+            branch: var("GITHUB_HEAD_REF").or_else(|| var("GITHUB_REF_NAME")),
+            job_url,
+        });
+    }
+    for (marker, provider, commit, branch, job_url) in PROVIDERS {
+        if var(marker).is_some() {
+            return Some(CiMetadata {
+                provider: provider.to_string(),
+                commit: var(commit),
+                branch: var(branch),
+                job_url: var(job_url),
+            });
+        }
+    }
+    var("CI").map(|_| CiMetadata {
+        provider: "unknown".to_string(),
+        commit: None,
+        branch: None,
+        job_url: None,
+    })
+}
@@ -0,0 +1,586 @@
+use async_trait::async_trait;
+use clap::{Args, ValueEnum};
+use http::header::{HeaderName, AUTHORIZATION, HOST};
+use jsonrpsee::core::client::{BatchResponse, ClientT};
+use jsonrpsee::core::params::BatchRequestBuilder;
+use jsonrpsee::core::traits::ToRpcParams;
+use jsonrpsee::core::ClientError;
+use jsonrpsee::http_client::transport::HttpBackend;
+use jsonrpsee::http_client::{
+    CustomCertStore, HeaderMap, HeaderValue, HttpClient, HttpClientBuilder,
+};
+use jsonrpsee::ws_client::{WsClient, WsClientBuilder};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::RootCertStore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::cell::Cell;
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tokio::net::{lookup_host, TcpStream};
+use tokio::sync::RwLock;
+use tokio::time::timeout;
+use tower::Service;
+use url::Url;
+
+use crate::types::{AddressTiming, ConnectionReport, WebSocketReport};
+use crate::TestError;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Requests a WebSocket connection keeps waiting for their response before new ones queue
+// in the client, far above jsonrpsee's default so the socket isn't the bottleneck
+const WS_MAX_PENDING: usize = 65_536;
+
+#[derive(Args, Clone)]
+pub struct ClientArgs {
+    /// JSON-RPC endpoint of the paymaster, http(s) or ws(s)
+    #[arg(long, default_value = "http://localhost:12777")]
+    pub endpoint: String,
+
+    /// Client certificate and key (PEM) presented when the endpoint requires mTLS
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// Private key (PEM) of --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// CA bundle (PEM) used instead of the system roots to verify the endpoint
+    #[arg(long)]
+    pub tls_ca: Option<PathBuf>,
+
+    /// Extra request header in `Name: value` form, can be repeated
+    #[arg(long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+
+    /// Name of the env var holding a token sent as `Authorization: Bearer <token>`
+    #[arg(long)]
+    pub bearer_token_env: Option<String>,
+
+    /// Time a request may take before it fails as a timeout
+    #[arg(long, default_value = "60")]
+    pub request_timeout_secs: u64,
+
+    /// Header carrying the run tag on every request, so operators can filter the
+    /// paymaster's logs and metrics down to the traffic of one run
+    #[arg(long, default_value = "X-Stress-Run")]
+    pub tag_header: String,
+
+    /// Tag sent in --tag-header and stored with the results, random per run when not given
+    #[arg(long)]
+    pub run_tag: Option<String>,
+
+    /// Address family the endpoint is reached over. With v4 or v6 its host is resolved
+    /// once at startup and every request goes to that address, only for http endpoints
+    /// since the address would not match the certificate of an https one
+    #[arg(long, value_enum, default_value = "any")]
+    pub ip_family: IpFamily,
+
+    /// JSON-RPC transport, run once with each to compare them. Over ws every request
+    /// shares one connection, opened again when it drops, and an http(s) endpoint is
+    /// reached at the same address as ws(s)
+    #[arg(long, value_enum, default_value = "http")]
+    pub transport: Transport,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Http,
+    Ws,
+}
+
+#[derive(ValueEnum, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum IpFamily {
+    /// Whatever the system resolver returns first, resolved again on every new connection
+    Any,
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn matches(&self, address: &SocketAddr) -> bool {
+        match self {
+            IpFamily::Any => true,
+            IpFamily::V4 => address.is_ipv4(),
+            IpFamily::V6 => address.is_ipv6(),
+        }
+    }
+}
+
+impl ClientArgs {
+    // Picks the run tag unless one was given, call before building the clients of a run
+    pub fn tag_run(&mut self) -> String {
+        self.run_tag
+            .get_or_insert_with(|| {
+                rand::random::<[u8; 8]>()
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect()
+            })
+            .clone()
+    }
+}
+
+// The paymaster behind either transport, the rest of the tool only uses it as a JSON-RPC
+// client and the paymaster API on top
+pub enum PaymasterClient {
+    Http(HttpClient<ServerTiming<HttpBackend>>),
+    Ws(WsConnection),
+}
+
+impl PaymasterClient {
+    // Connection metrics, only over ws
+    pub fn websocket(&self) -> Option<WebSocketReport> {
+        match self {
+            PaymasterClient::Http(_) => None,
+            PaymasterClient::Ws(ws) => Some(WebSocketReport {
+                reconnects: ws.reconnects.load(Ordering::Relaxed),
+                max_pending_requests: ws.max_pending.load(Ordering::Relaxed),
+            }),
+        }
+    }
+}
+
+pub struct WsConnection {
+    endpoint: String,
+    builder: WsClientBuilder,
+    client: RwLock<Arc<WsClient>>,
+    reconnects: AtomicU32,
+    // Requests sent on the socket and still waiting for their response
+    pending: AtomicU32,
+    max_pending: AtomicU32,
+}
+
+impl WsConnection {
+    // The current connection, opened again first when it dropped. Requests in flight on
+    // the dropped one fail
+    async fn client(&self) -> Result<Arc<WsClient>, ClientError> {
+        let client = Arc::clone(&*self.client.read().await);
+        if client.is_connected() {
+            return Ok(client);
+        }
+        let mut current = self.client.write().await;
+        // Another request may have reconnected in the meantime
+        if !current.is_connected() {
+            *current = Arc::new(self.builder.clone().build(&self.endpoint).await?);
+            self.reconnects.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(Arc::clone(&*current))
+    }
+
+    // Counts a request as pending until the guard is dropped, also when the request is
+    // cancelled while waiting for its response
+    fn enter(&self) -> PendingGuard<'_> {
+        let pending = self.pending.fetch_add(1, Ordering::Relaxed) + 1;
+        self.max_pending.fetch_max(pending, Ordering::Relaxed);
+        PendingGuard(&self.pending)
+    }
+}
+
+struct PendingGuard<'a>(&'a AtomicU32);
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl ClientT for PaymasterClient {
+    async fn notification<Params>(&self, method: &str, params: Params) -> Result<(), ClientError>
+    where
+        Params: ToRpcParams + Send,
+    {
+        match self {
+            PaymasterClient::Http(client) => client.notification(method, params).await,
+            PaymasterClient::Ws(ws) => ws.client().await?.notification(method, params).await,
+        }
+    }
+
+    async fn request<R, Params>(&self, method: &str, params: Params) -> Result<R, ClientError>
+    where
+        R: DeserializeOwned,
+        Params: ToRpcParams + Send,
+    {
+        match self {
+            PaymasterClient::Http(client) => client.request(method, params).await,
+            PaymasterClient::Ws(ws) => {
+                let client = ws.client().await?;
+                let _pending = ws.enter();
+                client.request(method, params).await
+            }
+        }
+    }
+
+    async fn batch_request<'a, R>(
+        &self,
+        batch: BatchRequestBuilder<'a>,
+    ) -> Result<BatchResponse<'a, R>, ClientError>
+    where
+        R: DeserializeOwned + fmt::Debug + 'a,
+    {
+        match self {
+            PaymasterClient::Http(client) => client.batch_request(batch).await,
+            PaymasterClient::Ws(ws) => {
+                let client = ws.client().await?;
+                let _pending = ws.enter();
+                client.batch_request(batch).await
+            }
+        }
+    }
+}
+
+tokio::task_local! {
+    // Server time reported for the responses of the requests sent within `server_timed`
+    static SERVER_TIME: Cell<Option<f64>>;
+}
+
+// Runs a request and returns the time the paymaster reported spending on it in its
+// `Server-Timing` response header, None without the header and always over ws
+pub async fn server_timed<F: Future>(request: F) -> (F::Output, Option<f64>) {
+    SERVER_TIME
+        .scope(Cell::new(None), async {
+            let output = request.await;
+            (output, SERVER_TIME.with(Cell::get))
+        })
+        .await
+}
+
+// Milliseconds of a `Server-Timing` header. A `total` metric is taken as is, otherwise
+// the durations of all metrics are added up, e.g. `db;dur=53, app;dur=47.2` is 100.2
+fn server_time(header: &str) -> Option<f64> {
+    let mut sum = None;
+    for metric in header.split(',') {
+        let mut params = metric.split(';').map(str::trim);
+        let name = params.next().unwrap_or_default();
+        let dur = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(key, _)| key.trim().eq_ignore_ascii_case("dur"))
+            .and_then(|(_, value)| value.trim().trim_matches('"').parse::<f64>().ok());
+        let Some(dur) = dur else {
+            continue;
+        };
+        if name.eq_ignore_ascii_case("total") {
+            return Some(dur);
+        }
+        sum = Some(sum.unwrap_or(0.0) + dur);
+    }
+    sum
+}
+
+// HTTP middleware handing the `Server-Timing` of every response to `server_timed`. The
+// response future is polled by the task that sent the request, where its scope is set
+#[derive(Clone)]
+pub struct ServerTiming<S>(S);
+
+impl<S, Request, B> Service<Request> for ServerTiming<S>
+where
+    S: Service<Request, Response = http::Response<B>>,
+    S::Future: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let response = self.0.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            let time = response
+                .headers()
+                .get_all("server-timing")
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .filter_map(server_time)
+                .reduce(|a, b| a + b);
+            if let Some(time) = time {
+                // Requests outside of `server_timed` have nowhere to report to
+                let _ = SERVER_TIME
+                    .try_with(|total| total.set(Some(total.get().unwrap_or(0.0) + time)));
+            }
+            Ok(response)
+        })
+    }
+}
+
+// Builds the JSON-RPC client used to talk to the paymaster
+// The default rustls setup is only replaced when one of the TLS options is set
+pub async fn build_client(args: &ClientArgs) -> Result<PaymasterClient, TestError> {
+    if args.transport == Transport::Ws {
+        return Ok(PaymasterClient::Ws(connect_ws(args).await?));
+    }
+    let mut headers = request_headers(args)?;
+    let mut endpoint = args.endpoint.clone();
+    if args.ip_family != IpFamily::Any {
+        let mut url = Url::parse(&args.endpoint)?;
+        if url.scheme() != "http" {
+            return Err("--ip-family needs an http endpoint".into());
+        }
+        let address = resolve(&url)
+            .await?
+            .addresses
+            .into_iter()
+            .find(|address| args.ip_family.matches(address))
+            .ok_or_else(|| format!("{} has no address of that family", args.endpoint))?;
+        // The paymaster still sees the name it is served under
+        headers.insert(HOST, HeaderValue::from_str(&authority(&url))?);
+        url.set_ip_host(address.ip())
+            .map_err(|_| format!("cannot send to {}", address))?;
+        endpoint = url.to_string();
+    }
+    let mut builder = HttpClientBuilder::default()
+        .set_headers(headers)
+        .request_timeout(Duration::from_secs(args.request_timeout_secs))
+        .set_http_middleware(tower::ServiceBuilder::new().layer_fn(ServerTiming));
+    if args.tls_cert.is_some() || args.tls_ca.is_some() {
+        builder = builder.with_custom_cert_store(tls_config(args)?);
+    }
+    Ok(PaymasterClient::Http(builder.build(&endpoint)?))
+}
+
+// Client with every option at its default, e.g. for a self-test that never sends
+pub fn default_http_client(endpoint: &str) -> Result<PaymasterClient, TestError> {
+    Ok(PaymasterClient::Http(
+        HttpClientBuilder::default()
+            .set_http_middleware(tower::ServiceBuilder::new().layer_fn(ServerTiming))
+            .build(endpoint)?,
+    ))
+}
+
+async fn connect_ws(args: &ClientArgs) -> Result<WsConnection, TestError> {
+    if args.ip_family != IpFamily::Any {
+        return Err("--ip-family needs the http transport".into());
+    }
+    let mut url = Url::parse(&args.endpoint)?;
+    let scheme = match url.scheme() {
+        "http" => Some("ws"),
+        "https" => Some("wss"),
+        _ => None,
+    };
+    if let Some(scheme) = scheme {
+        url.set_scheme(scheme)
+            .map_err(|_| format!("cannot reach {} over ws", args.endpoint))?;
+    }
+    let mut builder = WsClientBuilder::default()
+        .set_headers(request_headers(args)?)
+        .request_timeout(Duration::from_secs(args.request_timeout_secs))
+        .max_concurrent_requests(WS_MAX_PENDING);
+    if args.tls_cert.is_some() || args.tls_ca.is_some() {
+        builder = builder.with_custom_cert_store(tls_config(args)?);
+    }
+    let endpoint = url.to_string();
+    let client = builder.clone().build(&endpoint).await?;
+    Ok(WsConnection {
+        endpoint,
+        builder,
+        client: RwLock::new(Arc::new(client)),
+        reconnects: AtomicU32::new(0),
+        pending: AtomicU32::new(0),
+        max_pending: AtomicU32::new(0),
+    })
+}
+
+struct Resolved {
+    // None when the endpoint is an address already
+    dns: Option<Duration>,
+    addresses: Vec<SocketAddr>,
+}
+
+async fn resolve(url: &Url) -> Result<Resolved, TestError> {
+    let host = url.host_str().ok_or("the endpoint has no host")?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let literal = url
+        .host()
+        .is_some_and(|h| !matches!(h, url::Host::Domain(_)));
+    let start = Instant::now();
+    let addresses: Vec<SocketAddr> = lookup_host((host.trim_matches(['[', ']']), port))
+        .await
+        .map_err(|e| format!("cannot resolve {}: {}", host, e))?
+        .collect();
+    Ok(Resolved {
+        dns: (!literal).then(|| start.elapsed()),
+        addresses,
+    })
+}
+
+fn authority(url: &Url) -> String {
+    match url.port() {
+        Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+        None => url.host_str().unwrap_or_default().to_string(),
+    }
+}
+
+// Times the lookup of the endpoint's host and a TCP connect to each of its addresses,
+// IPv4 and IPv6 alike, so a slow path of one family shows up next to the other
+pub async fn probe_connection(args: &ClientArgs) -> Result<ConnectionReport, TestError> {
+    let url = Url::parse(&args.endpoint)?;
+    let resolved = resolve(&url).await?;
+    let mut addresses = Vec::new();
+    for address in resolved.addresses {
+        let start = Instant::now();
+        let (connect_ms, error) = match timeout(CONNECT_TIMEOUT, TcpStream::connect(address)).await
+        {
+            Ok(Ok(_)) => (Some(start.elapsed().as_secs_f64() * 1000.0), None),
+            Ok(Err(e)) => (None, Some(e.to_string())),
+            Err(_) => (None, Some("timed out".to_string())),
+        };
+        addresses.push(AddressTiming {
+            address,
+            ipv6: address.is_ipv6(),
+            used: args.ip_family.matches(&address),
+            connect_ms,
+            error,
+        });
+    }
+    Ok(ConnectionReport {
+        host: url.host_str().unwrap_or_default().to_string(),
+        ip_family: args.ip_family,
+        dns_ms: resolved.dns.map(|dns| dns.as_secs_f64() * 1000.0),
+        addresses,
+    })
+}
+
+fn request_headers(args: &ClientArgs) -> Result<HeaderMap, TestError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &args.headers {
+        headers.append(
+            HeaderName::from_bytes(name.as_bytes())?,
+            HeaderValue::from_str(value)?,
+        );
+    }
+    if let Some(var) = &args.bearer_token_env {
+        let token = std::env::var(var).map_err(|_| format!("{} is not set", var))?;
+        let mut value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+        value.set_sensitive(true);
+        headers.insert(AUTHORIZATION, value);
+    }
+    if let Some(tag) = &args.run_tag {
+        headers.insert(
+            HeaderName::from_bytes(args.tag_header.as_bytes())?,
+            HeaderValue::from_str(tag)?,
+        );
+    }
+    Ok(headers)
+}
+
+pub fn parse_header(raw: &str) -> Result<(String, String), String> {
+    let (name, value) = raw
+        .split_once(':')
+        .ok_or_else(|| format!("expected `Name: value`, got `{}`", raw))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+fn tls_config(args: &ClientArgs) -> Result<CustomCertStore, TestError> {
+    let mut roots = RootCertStore::empty();
+    match &args.tls_ca {
+        Some(ca) => {
+            for cert in load_certs(ca)? {
+                roots.add(cert)?;
+            }
+        }
+        None => {
+            let native = rustls_native_certs::load_native_certs();
+            roots.add_parsable_certificates(native.certs);
+        }
+    }
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let builder = CustomCertStore::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots);
+
+    let config = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => {
+            let key = PrivateKeyDer::from_pem_file(key)
+                .map_err(|e| format!("invalid TLS key {}: {}", key.display(), e))?;
+            builder.with_client_auth_cert(load_certs(cert)?, key)?
+        }
+        _ => builder.with_no_client_auth(),
+    };
+    Ok(config)
+}
+
+fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, TestError> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("invalid certificate file {}: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("no certificates found in {}", path.display()).into());
+    }
+    Ok(certs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use jsonrpsee::rpc_params;
+    use jsonrpsee::server::{RpcModule, Server};
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        client: ClientArgs,
+    }
+
+    #[tokio::test]
+    async fn cancelled_requests_stop_being_pending() {
+        // A paymaster that never answers
+        let server = Server::builder().build("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("ws://{}", server.local_addr().unwrap());
+        let mut module = RpcModule::new(());
+        module
+            .register_async_method("paymaster_health", |_, _, _| std::future::pending::<bool>())
+            .unwrap();
+        let _server = server.start(module);
+
+        let args = Cli::parse_from(["test", "--endpoint", &endpoint, "--transport", "ws"]).client;
+        let client = build_client(&args).await.unwrap();
+        let request = client.request::<bool, _>("paymaster_health", rpc_params![]);
+        assert!(timeout(Duration::from_millis(200), request).await.is_err());
+
+        let PaymasterClient::Ws(ws) = &client else {
+            panic!("not a ws client");
+        };
+        assert_eq!(ws.max_pending.load(Ordering::Relaxed), 1);
+        assert_eq!(ws.pending.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn server_time_adds_up_the_metrics() {
+        assert_eq!(server_time("db;dur=53, app;dur=47.2"), Some(100.2));
+        assert_eq!(server_time("cache;desc=\"hit\";dur=\"1.5\""), Some(1.5));
+        assert_eq!(server_time("db;DUR=2,miss,app;dur=3"), Some(5.0));
+    }
+
+    #[test]
+    fn server_time_prefers_a_total_metric() {
+        assert_eq!(
+            server_time("db;dur=53, total;dur=120, app;dur=47"),
+            Some(120.0)
+        );
+    }
+
+    #[test]
+    fn server_time_needs_a_duration() {
+        assert_eq!(server_time(""), None);
+        assert_eq!(server_time("miss, cache;desc=\"hit\""), None);
+        assert_eq!(server_time("db;dur=fast"), None);
+    }
+}
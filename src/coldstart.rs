@@ -0,0 +1,134 @@
+use clap::Args;
+use starknet::core::types::{Call, Felt};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::account::AccountArgs;
+use crate::cacheprobe::ProbeSample;
+use crate::client::{build_client, ClientArgs};
+use crate::network::NetworkArgs;
+use crate::scenario::{Leg, RECIPIENT, TRANSFER};
+use crate::transaction::{run_legs, Fee, Pipeline};
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Args)]
+pub struct ColdStartArgs {
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// Idle time before a burst in seconds, can be repeated, each gap is measured on its own
+    #[arg(long = "idle-secs", default_values_t = [60, 300, 900], value_parser = clap::value_parser!(u64).range(1..))]
+    pub idle_secs: Vec<u64>,
+
+    /// Transactions sent back to back after each idle gap, the first one meets the cold
+    /// paymaster and the others the warmed up one
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u32).range(2..))]
+    pub burst: u32,
+
+    /// Bursts per idle gap
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+    pub rounds: u32,
+
+    /// Write the results to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Leaves the paymaster idle, then sends a small burst of transfers one after the other.
+// Compared to the rest of its burst, the first transaction shows what scaling from zero,
+// expired connections and cold caches add to a request
+pub async fn probe_cold_start(mut args: ColdStartArgs) -> Result<ColdStartResults, TestError> {
+    let run_tag = args.client.tag_run();
+    let client = build_client(&args.client).await?;
+    let network = args.network.resolve()?;
+    let transactions = args.idle_secs.len() as u64 * args.rounds as u64 * args.burst as u64;
+    args.network
+        .guard_production_total(&args.client.endpoint, 1, transactions)?;
+    let config = envy::from_env::<Config>()?;
+    let account = args.account.resolve(&config.private_key)?;
+    let fee = Fee::GasToken(network.gas_token);
+    let pipeline = Pipeline::default();
+    let accounts = [account];
+    // Distinct amounts keep a build response cache out of the measurement
+    let mut amount = 0;
+    let mut transfer = || {
+        amount += 1;
+        Leg {
+            account: 0,
+            calls: vec![Call {
+                to: network.gas_token,
+                selector: TRANSFER,
+                calldata: vec![RECIPIENT, Felt::from(amount), Felt::ZERO],
+            }],
+        }
+    };
+
+    let total: u64 = args.idle_secs.iter().sum::<u64>() * args.rounds as u64;
+    println!(
+        "Probing {} for cold starts, about {} minute(s) of idle time",
+        args.client.endpoint,
+        total.div_ceil(60)
+    );
+    let mut gaps = Vec::new();
+    for &idle_secs in &args.idle_secs {
+        let mut first = ProbeSample::new();
+        let mut warm = ProbeSample::new();
+        for _ in 0..args.rounds {
+            sleep(Duration::from_secs(idle_secs)).await;
+            for i in 0..args.burst {
+                let leg = transfer();
+                let sent = run_legs(
+                    &client,
+                    &pipeline,
+                    &accounts,
+                    vec![leg],
+                    fee,
+                    false,
+                    false,
+                    false,
+                )
+                .await;
+                if i == 0 {
+                    first.add(sent.result);
+                } else {
+                    warm.add(sent.result);
+                }
+            }
+        }
+        let first = first.finish();
+        let warm = warm.finish();
+        let gap = ColdStartGap {
+            idle_secs,
+            p50_penalty_ms: first.p50_latency_ms - warm.p50_latency_ms,
+            first,
+            warm,
+        };
+        println!(
+            "After {} s idle: first request p50 {:.1} ms, warm p50 {:.1} ms ({:+.1} ms)",
+            idle_secs, gap.first.p50_latency_ms, gap.warm.p50_latency_ms, gap.p50_penalty_ms
+        );
+        gaps.push(gap);
+    }
+
+    let results = ColdStartResults {
+        endpoint: args.client.endpoint.clone(),
+        run_tag,
+        burst: args.burst,
+        rounds: args.rounds,
+        gaps,
+    };
+    if let Some(path) = &args.output {
+        fs::write(path, serde_json::to_string_pretty(&results)?)?;
+        println!("Results saved to: {}", path.display());
+    }
+    Ok(results)
+}
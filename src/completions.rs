@@ -0,0 +1,44 @@
+use clap::Command;
+use clap_complete::Shell;
+
+// Completion script of subcommands, their options and the values of enum options, to be
+// sourced by the shell, e.g. `source <(paymaster-stress completions bash)`
+pub fn generate(shell: Shell, cli: &mut Command) -> Result<String, std::string::FromUtf8Error> {
+    let name = cli.get_name().to_string();
+    let mut out = Vec::new();
+    clap_complete::generate(shell, cli, name, &mut out);
+    String::from_utf8(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Cli;
+    use clap::CommandFactory;
+
+    #[test]
+    fn bash_completes_the_options_of_a_subcommand() {
+        let script = generate(Shell::Bash, &mut Cli::command()).unwrap();
+        assert!(script.contains("paymaster__stress,linear)"));
+        let linear = script.find("stress__subcmd__linear)\n").unwrap();
+        let options = &script[linear..linear + script[linear..].find(";;").unwrap()];
+        assert!(options.contains("--max-tps"));
+        assert!(options.contains("--transport"));
+        assert!(script.contains("complete -F _paymaster__stress"));
+    }
+
+    #[test]
+    fn fish_descriptions_stay_on_one_line() {
+        let script = generate(Shell::Fish, &mut Cli::command()).unwrap();
+        let line = script
+            .lines()
+            .find(|line| line.contains("using_subcommand linear") && line.contains("-l max-tps"))
+            .unwrap();
+        assert!(line.contains("-d 'TPS of the last step"));
+        // A description running over several lines leaves a quote open
+        for line in script.lines().filter(|line| line.starts_with("complete")) {
+            let quotes = line.replace("\\'", "").matches('\'').count();
+            assert_eq!(quotes % 2, 0, "{}", line);
+        }
+    }
+}
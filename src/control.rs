@@ -0,0 +1,207 @@
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use clap::Parser;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use crate::ramp::LiveStats;
+use crate::run::{run_linear, LinearArgs};
+use crate::types::LiveSnapshot;
+use crate::TestError;
+
+// Run options arrive as a JSON object keyed by the CLI flag names, e.g.
+// `{"max_tps": 50, "steps": 5, "header": ["X-Api-Key: ..."]}`, and go through the
+// same parser as the `linear` subcommand so both interfaces always accept the same options
+#[derive(Parser)]
+#[command(name = "linear")]
+struct StartRequest {
+    #[command(flatten)]
+    args: LinearArgs,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum RunState {
+    Running,
+    Finished,
+    Failed,
+}
+
+struct RunEntry {
+    state: RunState,
+    live: Arc<LiveStats>,
+    results: Option<Value>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct RunStatus {
+    id: u64,
+    state: RunState,
+    live: LiveSnapshot,
+    error: Option<String>,
+}
+
+#[derive(Default)]
+struct Control {
+    runs: Mutex<BTreeMap<u64, RunEntry>>,
+}
+
+type ApiError = (StatusCode, String);
+
+pub async fn serve_control(listen: &str) -> Result<(), TestError> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!("Control API listening on http://{}", listen);
+
+    let app = Router::new()
+        .route("/runs", post(start_run).get(list_runs))
+        .route("/runs/:id", get(run_status))
+        .route("/runs/:id/stop", post(stop_run))
+        .route("/runs/:id/results", get(run_results))
+        .with_state(Arc::new(Control::default()));
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn start_run(
+    State(control): State<Arc<Control>>,
+    Json(params): Json<Map<String, Value>>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let mut argv = vec!["linear".to_string()];
+    argv.extend(to_cli_args(&params).map_err(|e| (StatusCode::BAD_REQUEST, e))?);
+    let request =
+        StartRequest::try_parse_from(argv).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let mut runs = control.runs.lock().unwrap();
+    // Only one run at a time, concurrent runs would skew each other's numbers
+    if runs.values().any(|run| run.state == RunState::Running) {
+        return Err((StatusCode::CONFLICT, "a run is already in progress".into()));
+    }
+    let id = runs.keys().next_back().map_or(1, |last| last + 1);
+    let live = Arc::new(LiveStats::default());
+    runs.insert(
+        id,
+        RunEntry {
+            state: RunState::Running,
+            live: Arc::clone(&live),
+            results: None,
+            error: None,
+        },
+    );
+
+    // The load generator gets its own runtime so the API stays responsive under load
+    let control = Arc::clone(&control);
+    std::thread::spawn(move || {
+        let outcome = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime.block_on(async {
+                    let results = run_linear(request.args, live).await;
+                    results
+                        .and_then(|r| Ok(serde_json::to_value(r)?))
+                        .map_err(|e| e.to_string())
+                })
+            });
+
+        let mut runs = control.runs.lock().unwrap();
+        if let Some(run) = runs.get_mut(&id) {
+            match outcome {
+                Ok(results) => {
+                    run.state = RunState::Finished;
+                    run.results = Some(results);
+                }
+                Err(error) => {
+                    run.state = RunState::Failed;
+                    run.error = Some(error);
+                }
+            }
+        }
+    });
+
+    Ok((StatusCode::CREATED, Json(json!({ "id": id }))))
+}
+
+async fn list_runs(State(control): State<Arc<Control>>) -> Json<Vec<RunStatus>> {
+    let runs = control.runs.lock().unwrap();
+    Json(runs.iter().map(|(id, run)| status(*id, run)).collect())
+}
+
+async fn run_status(
+    State(control): State<Arc<Control>>,
+    Path(id): Path<u64>,
+) -> Result<Json<RunStatus>, ApiError> {
+    let runs = control.runs.lock().unwrap();
+    let run = runs.get(&id).ok_or_else(not_found)?;
+    Ok(Json(status(id, run)))
+}
+
+async fn stop_run(
+    State(control): State<Arc<Control>>,
+    Path(id): Path<u64>,
+) -> Result<StatusCode, ApiError> {
+    let runs = control.runs.lock().unwrap();
+    let run = runs.get(&id).ok_or_else(not_found)?;
+    run.live.request_stop();
+    Ok(StatusCode::ACCEPTED)
+}
+
+async fn run_results(
+    State(control): State<Arc<Control>>,
+    Path(id): Path<u64>,
+) -> Result<Json<Value>, ApiError> {
+    let runs = control.runs.lock().unwrap();
+    let run = runs.get(&id).ok_or_else(not_found)?;
+    match (&run.results, &run.error) {
+        (Some(results), _) => Ok(Json(results.clone())),
+        (None, Some(error)) => Err((StatusCode::INTERNAL_SERVER_ERROR, error.clone())),
+        (None, None) => Err((StatusCode::CONFLICT, "run still in progress".into())),
+    }
+}
+
+fn status(id: u64, run: &RunEntry) -> RunStatus {
+    RunStatus {
+        id,
+        state: run.state,
+        live: run.live.snapshot(),
+        error: run.error.clone(),
+    }
+}
+
+fn not_found() -> ApiError {
+    (StatusCode::NOT_FOUND, "unknown run".into())
+}
+
+// `{"max_tps": 50, "header": ["a: b", "c: d"], "quiet": true}` becomes
+// `--max-tps=50 --header=a: b --header=c: d --quiet`
+pub fn to_cli_args(params: &Map<String, Value>) -> Result<Vec<String>, String> {
+    let mut argv = Vec::new();
+    for (key, value) in params {
+        let flag = format!("--{}", key.replace('_', "-"));
+        match value {
+            Value::Bool(true) => argv.push(flag),
+            Value::Bool(false) | Value::Null => {}
+            Value::Array(items) => {
+                for item in items {
+                    argv.push(format!("{}={}", flag, scalar(key, item)?));
+                }
+            }
+            other => argv.push(format!("{}={}", flag, scalar(key, other)?)),
+        }
+    }
+    Ok(argv)
+}
+
+fn scalar(key: &str, value: &Value) -> Result<String, String> {
+    match value {
+        Value::String(s) => Ok(s.clone()),
+        Value::Number(n) => Ok(n.to_string()),
+        Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(format!("unsupported value for `{}`", key)),
+    }
+}
@@ -0,0 +1,143 @@
+use serde_json::Value;
+use std::backtrace::Backtrace;
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once};
+
+use crate::encrypt::EncryptArgs;
+use crate::redact::redact;
+use crate::types::{CrashReport, TestResult};
+use crate::TestError;
+
+// Run the crash report is written for, the latest one when several run side by side
+struct Target {
+    run_tag: String,
+    path: PathBuf,
+    // Serialized as they complete, the hook can't reach the ramp's own copies
+    steps: Vec<Value>,
+    // The report is redacted and encrypted like the results
+    redact: bool,
+    encrypt: EncryptArgs,
+}
+
+static TARGET: Mutex<Option<Target>> = Mutex::new(None);
+static INSTALL: Once = Once::new();
+// Only the first panic is reported, a panicking transaction task takes the step down
+// with it and more panics may follow
+static REPORTED: AtomicBool = AtomicBool::new(false);
+
+// Makes a panic during the run write the steps completed so far and where it panicked
+// next to the first --output, or to `paymaster-stress-crash-<run tag>.json` without one,
+// redacted with --redact and encrypted with --encrypt-to like the results
+pub fn install(run_tag: &str, output: Option<&Path>, redact: bool, encrypt: &EncryptArgs) {
+    let path = match output {
+        Some(output) => output.with_extension("crash.json"),
+        None => PathBuf::from(format!("paymaster-stress-crash-{}.json", run_tag)),
+    };
+    *TARGET.lock().unwrap_or_else(|e| e.into_inner()) = Some(Target {
+        run_tag: run_tag.to_string(),
+        path: encrypt.path(&path),
+        steps: Vec::new(),
+        redact,
+        encrypt: encrypt.clone(),
+    });
+    REPORTED.store(false, Ordering::SeqCst);
+    INSTALL.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if REPORTED.swap(true, Ordering::SeqCst) {
+                return;
+            }
+            let target = TARGET.lock().unwrap_or_else(|e| e.into_inner());
+            let Some(target) = target.as_ref() else {
+                return;
+            };
+            let message = match info.payload().downcast_ref::<&str>() {
+                Some(message) => message.to_string(),
+                None => info
+                    .payload()
+                    .downcast_ref::<String>()
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            let report = CrashReport {
+                version: env!("CARGO_PKG_VERSION"),
+                run_tag: target.run_tag.clone(),
+                message,
+                location: info.location().map(|location| location.to_string()),
+                backtrace: Backtrace::force_capture().to_string(),
+                completed_steps: target.steps.clone(),
+            };
+            let written = write_report(target, &report).map_err(|e| e.to_string());
+            match written {
+                Ok(()) => eprintln!(
+                    "Crash report with {} completed step(s) saved to: {}",
+                    target.steps.len(),
+                    target.path.display()
+                ),
+                Err(e) => eprintln!("Failed to write the crash report: {}", e),
+            }
+        }));
+    });
+}
+
+fn write_report(target: &Target, report: &CrashReport) -> Result<(), TestError> {
+    let mut json = serde_json::to_value(report)?;
+    if target.redact {
+        redact(&mut json);
+    }
+    let content = target.encrypt.encrypt(serde_json::to_vec_pretty(&json)?)?;
+    fs::write(&target.path, content)?;
+    Ok(())
+}
+
+// Keeps the steps of `results` not kept yet for the crash report
+pub fn keep_steps(results: &[TestResult]) {
+    let mut target = TARGET.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(target) = target.as_mut() else {
+        return;
+    };
+    for result in results.iter().skip(target.steps.len()) {
+        target
+            .steps
+            .push(serde_json::to_value(result).unwrap_or(Value::Null));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn reports_are_redacted_like_the_results() {
+        let path = std::env::temp_dir().join(format!("crash-test-{}.json", std::process::id()));
+        let target = Target {
+            run_tag: "tag".to_string(),
+            path: path.clone(),
+            steps: vec![json!({"account": "0xabc", "calldata": ["0x1", "0x2"]})],
+            redact: true,
+            encrypt: EncryptArgs { encrypt_to: None },
+        };
+        let report = CrashReport {
+            version: "0",
+            run_tag: target.run_tag.clone(),
+            message: "boom".to_string(),
+            location: None,
+            backtrace: String::new(),
+            completed_steps: target.steps.clone(),
+        };
+        write_report(&target, &report).unwrap();
+        let written: Value = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(written["completed_steps"][0]["account"], "account-1");
+        assert_eq!(
+            written["completed_steps"][0]["calldata"],
+            "2 felt(s) redacted"
+        );
+        assert_eq!(written["message"], "boom");
+    }
+}
@@ -0,0 +1,143 @@
+use clap::{Args, Parser};
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+use crate::control::to_cli_args;
+use crate::ramp::LiveStats;
+use crate::run::{run_linear, LinearArgs};
+use crate::trend::{describe, regressions, MAX_DROP, WINDOW};
+use crate::types::*;
+use crate::TestError;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+#[derive(Args)]
+pub struct DaemonArgs {
+    /// Campaign to run, a `linear --config` file as written by `init`. It is read again
+    /// before every run, edits apply from the next one
+    #[arg(long)]
+    pub config: PathBuf,
+
+    /// Time of day to run at, `HH:MM` in UTC, can be repeated
+    #[arg(long, value_parser = parse_time_of_day, required_unless_present = "every_mins")]
+    pub at: Vec<u64>,
+
+    /// Run every this many minutes instead, starting right away
+    #[arg(long, conflicts_with = "at", value_parser = clap::value_parser!(u64).range(1..))]
+    pub every_mins: Option<u64>,
+
+    /// Every run is stored here as `<unix time>.json`, next to the rolling trend.json
+    #[arg(long)]
+    pub results_dir: PathBuf,
+
+    /// Runs kept in trend.json
+    #[arg(long, default_value = "30")]
+    pub keep: usize,
+}
+
+#[derive(Parser)]
+#[command(name = "linear")]
+struct Campaign {
+    #[command(flatten)]
+    args: LinearArgs,
+}
+
+// Seconds into the day
+fn parse_time_of_day(raw: &str) -> Result<u64, String> {
+    let invalid = || format!("expected `HH:MM`, got `{}`", raw);
+    let (hours, minutes) = raw.split_once(':').ok_or_else(invalid)?;
+    let hours: u64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok(hours * 3600 + minutes * 60)
+}
+
+// Runs the campaign on schedule until killed. A failed run is logged and the schedule
+// goes on, the next night may well work
+pub async fn daemon(args: DaemonArgs) -> Result<(), TestError> {
+    fs::create_dir_all(&args.results_dir)?;
+    // Fail on a broken config at startup rather than at the first run
+    campaign(&args.config)?;
+    let trend_path = args.results_dir.join("trend.json");
+    let mut first = true;
+    loop {
+        let wait = match args.every_mins {
+            Some(_) if first => Duration::ZERO,
+            Some(minutes) => Duration::from_secs(minutes * 60),
+            None => until_next(&args.at)?,
+        };
+        first = false;
+        println!("Next run in {:?}", wait);
+        sleep(wait).await;
+
+        let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let run = async {
+            let mut linear = campaign(&args.config)?;
+            let output = args.results_dir.join(format!("{}.json", started));
+            // Where it ends up, with --encrypt-to in the campaign
+            let written = linear.encrypt.path(&output);
+            linear.output = vec![output];
+            let results = run_linear(linear, Arc::new(LiveStats::default())).await?;
+            Ok::<_, TestError>((results, written))
+        };
+        match run.await {
+            Ok((results, written)) => {
+                let mut trend = read_trend(&trend_path)?;
+                let mut entry = TrendEntry::new(&results, &written);
+                entry.regressions = regressions(
+                    &trend[trend.len().saturating_sub(WINDOW)..],
+                    &entry,
+                    MAX_DROP,
+                );
+                println!(
+                    "Run done, max sustainable TPS {}",
+                    results.summary.max_sustainable_tps
+                );
+                if !entry.regressions.is_empty() {
+                    println!(
+                        "Regression against the previous runs: {}",
+                        describe(&entry.regressions)
+                    );
+                }
+                trend.push(entry);
+                let skip = trend.len().saturating_sub(args.keep);
+                trend.drain(..skip);
+                fs::write(&trend_path, serde_json::to_string_pretty(&trend)?)?;
+            }
+            Err(e) => println!("Run failed: {}", e),
+        }
+    }
+}
+
+// Options of a run from a `linear --config` file
+pub fn campaign(config: &Path) -> Result<LinearArgs, TestError> {
+    let params: Map<String, Value> = serde_json::from_str(&fs::read_to_string(config)?)
+        .map_err(|e| format!("invalid config {}: {}", config.display(), e))?;
+    let mut argv = vec!["linear".to_string()];
+    argv.extend(to_cli_args(&params)?);
+    let campaign = Campaign::try_parse_from(argv).map_err(|e| e.to_string())?;
+    Ok(campaign.args)
+}
+
+fn until_next(times: &[u64]) -> Result<Duration, TestError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() % DAY_SECS;
+    let wait = times
+        .iter()
+        .map(|at| (at + DAY_SECS - now - 1) % DAY_SECS + 1)
+        .min()
+        .unwrap_or(DAY_SECS);
+    Ok(Duration::from_secs(wait))
+}
+
+fn read_trend(path: &Path) -> Result<Vec<TrendEntry>, TestError> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
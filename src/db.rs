@@ -0,0 +1,121 @@
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::types::{StressTestResults, TestResult, TxRecord};
+use crate::TestError;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    label TEXT,
+    endpoint TEXT NOT NULL,
+    started_at INTEGER NOT NULL,
+    duration_secs INTEGER,
+    max_sustainable_tps INTEGER,
+    total_transactions INTEGER,
+    overall_success_rate REAL,
+    results_json TEXT
+);
+CREATE TABLE IF NOT EXISTS steps (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    step INTEGER NOT NULL,
+    target_tps INTEGER NOT NULL,
+    successful_txs INTEGER NOT NULL,
+    failed_txs INTEGER NOT NULL,
+    success_rate REAL NOT NULL,
+    avg_latency_ms REAL NOT NULL,
+    PRIMARY KEY (run_id, step)
+);
+CREATE TABLE IF NOT EXISTS transactions (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    step INTEGER NOT NULL,
+    sent_at_ms INTEGER NOT NULL,
+    in_flight INTEGER NOT NULL,
+    latency_ms REAL,
+    error TEXT
+);
+CREATE INDEX IF NOT EXISTS runs_label ON runs(label);
+CREATE INDEX IF NOT EXISTS runs_started_at ON runs(started_at);
+CREATE INDEX IF NOT EXISTS transactions_run_step ON transactions(run_id, step);
+CREATE INDEX IF NOT EXISTS transactions_error ON transactions(error);
+";
+
+// SQLite store accumulating runs across invocations, one row per run, step and transaction
+pub struct ResultsDb {
+    conn: Connection,
+    run_id: i64,
+}
+
+impl ResultsDb {
+    // Opens (creating if needed) the database and registers a new run
+    pub fn open(path: &Path, label: Option<&str>, endpoint: &str) -> Result<ResultsDb, TestError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(SCHEMA)?;
+        let started_at = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        conn.execute(
+            "INSERT INTO runs (label, endpoint, started_at) VALUES (?1, ?2, ?3)",
+            params![label, endpoint, started_at],
+        )?;
+        let run_id = conn.last_insert_rowid();
+        Ok(ResultsDb { conn, run_id })
+    }
+
+    // Writes a step and its transactions in a single SQLite transaction
+    pub fn record_step(
+        &mut self,
+        step: u32,
+        target_tps: u32,
+        result: &TestResult,
+        txs: &[TxRecord],
+    ) -> Result<(), TestError> {
+        let tx = self.conn.transaction()?;
+        tx.execute(
+            "INSERT INTO steps (run_id, step, target_tps, successful_txs, failed_txs, \
+             success_rate, avg_latency_ms) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                self.run_id,
+                step,
+                target_tps,
+                result.metrics.successful_txs,
+                result.metrics.failed_txs,
+                result.metrics.success_rate,
+                result.metrics.avg_latency_ms,
+            ],
+        )?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO transactions (run_id, step, sent_at_ms, in_flight, latency_ms, error) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+            for record in txs {
+                insert.execute(params![
+                    self.run_id,
+                    record.step,
+                    record.sent_at_ms as i64,
+                    record.in_flight,
+                    record.latency_ms,
+                    record.error.map(|e| e.name()),
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn finish_run(&mut self, results: &StressTestResults) -> Result<(), TestError> {
+        self.conn.execute(
+            "UPDATE runs SET duration_secs = ?1, max_sustainable_tps = ?2, \
+             total_transactions = ?3, overall_success_rate = ?4, results_json = ?5 WHERE id = ?6",
+            params![
+                results.total_duration_secs as i64,
+                results.summary.max_sustainable_tps,
+                results.summary.total_transactions,
+                results.summary.overall_success_rate,
+                serde_json::to_string(results)?,
+                self.run_id,
+            ],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use jsonrpsee::core::client::ClientT;
+use jsonrpsee::rpc_params;
+use paymaster_rpc::PaymasterAPIClient;
+use serde_json::Value;
+
+use crate::client::PaymasterClient;
+use crate::types::PaymasterInfo;
+use crate::TestError;
+
+// Methods every scenario goes through
+const REQUIRED_METHODS: [&str; 2] = ["paymaster_buildTransaction", "paymaster_executeTransaction"];
+
+// Asks the paymaster what it is and what it supports. The version and method list come from
+// the OpenRPC `rpc.discover` document when the server publishes one, the supported tokens
+// from the paymaster API itself
+pub async fn discover(client: &PaymasterClient) -> Result<PaymasterInfo, TestError> {
+    let document: Option<Value> = client.request("rpc.discover", rpc_params![]).await.ok();
+    let version = document
+        .as_ref()
+        .and_then(|doc| doc["info"]["version"].as_str())
+        .map(String::from);
+    let methods = document.as_ref().and_then(|doc| {
+        doc["methods"].as_array().map(|methods| {
+            methods
+                .iter()
+                .filter_map(|m| m["name"].as_str().map(String::from))
+                .collect::<Vec<_>>()
+        })
+    });
+    let supported_tokens = client
+        .get_supported_tokens()
+        .await?
+        .into_iter()
+        .map(|token| token.token_address)
+        .collect();
+    Ok(PaymasterInfo {
+        version,
+        methods,
+        supported_tokens,
+    })
+}
+
+impl PaymasterInfo {
+    // Unknown when the server doesn't publish its methods, in which case everything is tried
+    pub fn supports(&self, method: &str) -> bool {
+        self.methods
+            .as_ref()
+            .is_none_or(|methods| methods.iter().any(|m| m == method))
+    }
+
+    pub fn check_required(&self) -> Result<(), TestError> {
+        let missing: Vec<&str> = REQUIRED_METHODS
+            .into_iter()
+            .filter(|method| !self.supports(method))
+            .collect();
+        if !missing.is_empty() {
+            return Err(format!(
+                "paymaster does not implement {}, nothing can be sent",
+                missing.join(", ")
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
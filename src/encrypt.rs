@@ -0,0 +1,97 @@
+use clap::Args;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct EncryptArgs {
+    /// Encrypt the results, the transaction log and uploads to this recipient: an age
+    /// public key (`age1...`) through the `age` binary, anything else is handed to `gpg`
+    ///
+    /// Needs --output, the results would otherwise be printed in the clear
+    #[arg(long, requires = "output")]
+    pub encrypt_to: Option<String>,
+}
+
+impl EncryptArgs {
+    // Suffix of encrypted files, appended to the original name
+    pub fn extension(&self) -> Option<&'static str> {
+        self.encrypt_to
+            .as_deref()
+            .map(|recipient| if is_age(recipient) { "age" } else { "gpg" })
+    }
+
+    // `path` as it is written, with the suffix when encrypting
+    pub fn path(&self, path: &Path) -> PathBuf {
+        match self.extension() {
+            Some(extension) => {
+                let mut name = path.as_os_str().to_owned();
+                name.push(".");
+                name.push(extension);
+                PathBuf::from(name)
+            }
+            None => path.to_path_buf(),
+        }
+    }
+
+    pub fn encrypt(&self, plaintext: Vec<u8>) -> Result<Vec<u8>, TestError> {
+        let Some(recipient) = &self.encrypt_to else {
+            return Ok(plaintext);
+        };
+        let mut process = if is_age(recipient) {
+            let mut process = Command::new("age");
+            process.arg("--encrypt").arg("--recipient").arg(recipient);
+            process
+        } else {
+            let mut process = Command::new("gpg");
+            process
+                .args(["--batch", "--yes", "--trust-model", "always", "--encrypt"])
+                .arg("--recipient")
+                .arg(recipient);
+            process
+        };
+        let mut child = process
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run the encryption tool for {}: {}", recipient, e))?;
+        // Fed from another thread, the tool starts writing before it read everything
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or("no stdin for the encryption tool")?;
+        let writer = thread::spawn(move || stdin.write_all(&plaintext));
+        let output = child.wait_with_output()?;
+        writer
+            .join()
+            .map_err(|_| "writing to the encryption tool panicked")??;
+        if !output.status.success() {
+            return Err(format!(
+                "encryption failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )
+            .into());
+        }
+        Ok(output.stdout)
+    }
+
+    // Replaces a file by its encrypted version, returns where that went
+    pub fn encrypt_file(&self, path: &Path) -> Result<PathBuf, TestError> {
+        if self.encrypt_to.is_none() {
+            return Ok(path.to_path_buf());
+        }
+        let encrypted = self.path(path);
+        fs::write(&encrypted, self.encrypt(fs::read(path)?)?)?;
+        fs::remove_file(path)?;
+        Ok(encrypted)
+    }
+}
+
+fn is_age(recipient: &str) -> bool {
+    recipient.starts_with("age1")
+}
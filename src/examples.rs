@@ -0,0 +1,65 @@
+// Command lines for the usual workflows, printed by `examples`. The private key is read
+// from PRIVATE_KEY in all of them
+pub const EXAMPLES: &str = "\
+# Linear ramp to 50 TPS in 5 steps over 10 minutes, results to a file along with an HTML
+# and a CSV report of them
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 50 --steps 5 \\
+    --duration 600 --output ramp.json --output ramp.html --output ramp.csv
+
+# Soak: hold 20 TPS for 8 hours, summarized hour by hour, lost transactions tracked on-chain
+paymaster-stress linear --endpoint https://paymaster.example --step-tps 20 --duration 28800 \\
+    --rpc-url https://starknet-sepolia.example --lost-after-secs 300 --output soak.json
+
+# Find the max sustainable TPS: stop at the first failing step and confirm the one below
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 200 --steps 20 \\
+    --duration 1200 --stop-after-failures 1 --verify-peak
+
+# Find the TPS at which p99 latency reaches 2 seconds
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 200 --p99-target-ms 2000
+
+# Compare two runs, e.g. before and after a paymaster release
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 50 --label v1 --output v1.json
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 50 --label v2 --output v2.json
+paymaster-stress report v2.json --format md
+paymaster-stress trend . --window 1
+
+# Compare throughput and latency over ws against http: the same ramp on each transport,
+# the ws report also shows reconnects and the most requests pending on the socket
+mkdir -p transports
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 50 --transport http \\
+    --label http --output transports/http.json
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 50 --transport ws \\
+    --label ws --output transports/ws.json
+paymaster-stress report transports/http.json --format md
+paymaster-stress report transports/ws.json --format md
+paymaster-stress trend transports --window 1
+
+# Traffic within the paymaster's fair-use policy: no account sends more than 2 TPS
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 100 --accounts accounts.json \\
+    --max-account-tps 2
+
+# Compare fee modes and bursty against evenly paced traffic at the same TPS
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 30 --compare-fee-modes
+paymaster-stress linear --endpoint https://paymaster.example --max-tps 30 --burst-size 10 --burst-compare
+
+# Check the pool's accounts are deployed and funded before a long run
+paymaster-stress accounts status accounts.json --rpc-url https://rpc.example
+
+# How many TPS this machine can sign before the generator becomes the bottleneck
+paymaster-stress bench-signing --duration-secs 10
+
+# Check every scenario works before a long run
+paymaster-stress smoke --endpoint https://paymaster.example
+
+# Release gate: does the paymaster still handle the contracted load
+paymaster-stress assert --tps 100 --duration 10m --min-success 0.99 -- --endpoint https://paymaster.example --accounts accounts.json
+
+# Send a failing transaction of a --trace-sample run again, with its requests printed
+paymaster-stress replay-tx 9f2c4e1a7b3d5c60-3-12 --results v2.json --accounts accounts.json
+
+# Check a campaign written by `init` before booking the test slot
+paymaster-stress validate-config paymaster-stress.json
+
+# Shell completions, e.g. for bash
+source <(paymaster-stress completions bash)
+";
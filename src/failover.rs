@@ -0,0 +1,149 @@
+use clap::Args;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::client::PaymasterClient;
+use crate::types::{FailoverReport, FailoverTrigger, WebSocketReport};
+
+#[derive(Args, Clone)]
+pub struct FailoverArgs {
+    /// Endpoint traffic moves to once the failover triggers
+    #[arg(long)]
+    pub fallback_endpoint: Option<String>,
+
+    /// Scripted switch to the fallback this many seconds into the run
+    #[arg(long, requires = "fallback_endpoint")]
+    pub failover_after_secs: Option<u64>,
+
+    /// Switch to the fallback after this many consecutive failed transactions
+    #[arg(long, requires = "fallback_endpoint")]
+    pub failover_after_failures: Option<u32>,
+}
+
+#[derive(Default)]
+struct State {
+    on_fallback: bool,
+    consecutive_failures: u32,
+    // First failure of the current run of consecutive failures
+    burst_start: Option<Instant>,
+    burst_size: u32,
+    // Set once the burst that led to (or followed) the switch ended with a success
+    burst_closed: bool,
+    report: Option<FailoverReport>,
+}
+
+// Hands out the client for the currently active endpoint and moves traffic to the
+// fallback either at a scripted time or once failures pile up, the way a wallet
+// client would fail over between paymaster regions
+pub struct Failover {
+    primary: Arc<PaymasterClient>,
+    fallback: Option<Arc<PaymasterClient>>,
+    switch_after: Option<Duration>,
+    max_failures: Option<u32>,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+impl Failover {
+    pub fn new(
+        primary: PaymasterClient,
+        fallback: Option<PaymasterClient>,
+        args: &FailoverArgs,
+    ) -> Failover {
+        Failover {
+            primary: Arc::new(primary),
+            fallback: fallback.map(Arc::new),
+            switch_after: args.failover_after_secs.map(Duration::from_secs),
+            max_failures: args.failover_after_failures,
+            start: Instant::now(),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    // Connection metrics of the primary endpoint, only over ws
+    pub fn websocket(&self) -> Option<WebSocketReport> {
+        self.primary.websocket()
+    }
+
+    pub fn client(&self) -> Arc<PaymasterClient> {
+        let Some(fallback) = &self.fallback else {
+            return Arc::clone(&self.primary);
+        };
+        let mut state = self.state.lock().unwrap();
+        let due = self
+            .switch_after
+            .is_some_and(|after| self.start.elapsed() >= after);
+        if !state.on_fallback && due {
+            self.switch(&mut state, FailoverTrigger::Scheduled);
+        }
+        if state.on_fallback {
+            Arc::clone(fallback)
+        } else {
+            Arc::clone(&self.primary)
+        }
+    }
+
+    pub fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+        if success {
+            state.consecutive_failures = 0;
+            state.burst_start = None;
+            if state.on_fallback && !state.burst_closed {
+                state.burst_closed = true;
+                let burst_size = state.burst_size;
+                if let Some(report) = &mut state.report {
+                    report.error_burst_size = burst_size;
+                }
+            }
+            if !state.on_fallback {
+                state.burst_size = 0;
+            }
+            return;
+        }
+
+        state.consecutive_failures += 1;
+        if state.burst_start.is_none() {
+            state.burst_start = Some(Instant::now());
+        }
+        if !state.burst_closed {
+            state.burst_size += 1;
+        }
+        let exceeded = self
+            .max_failures
+            .is_some_and(|max| state.consecutive_failures >= max);
+        if self.fallback.is_some() && !state.on_fallback && exceeded {
+            self.switch(&mut state, FailoverTrigger::ConsecutiveFailures);
+        }
+    }
+
+    pub fn report(&self) -> Option<FailoverReport> {
+        let mut state = self.state.lock().unwrap();
+        let burst_size = state.burst_size;
+        let closed = state.burst_closed;
+        state.report.as_mut().map(|report| {
+            if !closed {
+                report.error_burst_size = burst_size;
+            }
+            report.clone()
+        })
+    }
+
+    fn switch(&self, state: &mut State, trigger: FailoverTrigger) {
+        let now = Instant::now();
+        // Detection time only makes sense when the switch reacted to failures
+        let detection_time_ms = match trigger {
+            FailoverTrigger::ConsecutiveFailures => state
+                .burst_start
+                .map(|start| now.duration_since(start).as_secs_f64() * 1000.0),
+            FailoverTrigger::Scheduled => None,
+        };
+        state.on_fallback = true;
+        state.report = Some(FailoverReport {
+            trigger,
+            switched_at_secs: now.duration_since(self.start).as_secs_f64(),
+            detection_time_ms,
+            error_burst_size: state.burst_size,
+        });
+    }
+}
@@ -0,0 +1,108 @@
+use clap::Args;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::sleep;
+
+use crate::account::{load_accounts, AccountArgs};
+use crate::client::parse_header;
+use crate::network::{Network, NetworkArgs};
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Args)]
+pub struct FundArgs {
+    /// Faucet endpoint every account is requested funds from with a POST
+    #[arg(long)]
+    pub faucet: String,
+
+    /// JSON body of the request, `{address}` is replaced with the account address
+    #[arg(long, default_value = r#"{"address": "{address}"}"#)]
+    pub faucet_body: String,
+
+    /// Extra request header in `Name: value` form, e.g. the faucet's API key, can be repeated
+    #[arg(long = "faucet-header", value_parser = parse_header)]
+    pub faucet_headers: Vec<(String, String)>,
+
+    /// Pause between two requests, faucets limit how fast they hand out funds
+    #[arg(long, default_value = "1000")]
+    pub delay_ms: u64,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// JSON file of `{address, private_key}` entries, every one of them gets funded
+    #[arg(long)]
+    pub accounts: Option<PathBuf>,
+}
+
+// Asks the faucet for funds once per account, one after the other. A refused request is
+// reported and the next account is tried, the faucet may only be rate limiting
+pub async fn fund(args: FundArgs) -> Result<FundReport, TestError> {
+    if let Network::Mainnet = args.network.network {
+        return Err("there are no faucets on mainnet".into());
+    }
+    let accounts = match &args.accounts {
+        Some(path) => load_accounts(path)?,
+        None => {
+            let config = envy::from_env::<Config>()?;
+            vec![args.account.resolve(&config.private_key)?]
+        }
+    };
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(60))
+        .build()?;
+
+    println!("Funding {} account(s) from {}", accounts.len(), args.faucet);
+    let mut requests = Vec::new();
+    for (i, account) in accounts.iter().enumerate() {
+        if i > 0 {
+            sleep(Duration::from_millis(args.delay_ms)).await;
+        }
+        let body = args
+            .faucet_body
+            .replace("{address}", &format!("{:#064x}", account.address));
+        let mut request = client
+            .post(&args.faucet)
+            .header("Content-Type", "application/json")
+            .body(body);
+        for (name, value) in &args.faucet_headers {
+            request = request.header(name, value);
+        }
+        let (status, error) = match request.send().await {
+            Ok(response) => {
+                let status = response.status();
+                let error = if status.is_success() {
+                    None
+                } else {
+                    Some(response.text().await.unwrap_or_default())
+                };
+                (Some(status.as_u16()), error)
+            }
+            Err(e) => (None, Some(e.to_string())),
+        };
+        println!(
+            "  {:#064x}: {}",
+            account.address,
+            match (&error, status) {
+                (None, _) => "funded".to_string(),
+                (Some(_), Some(status)) => format!("refused ({})", status),
+                (Some(e), None) => format!("failed ({})", e),
+            }
+        );
+        requests.push(FaucetRequest {
+            account: account.address,
+            status,
+            funded: error.is_none(),
+            error,
+        });
+    }
+
+    Ok(FundReport {
+        faucet: args.faucet,
+        funded: requests.iter().filter(|r| r.funded).count() as u32,
+        requests,
+    })
+}
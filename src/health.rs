@@ -0,0 +1,254 @@
+use clap::Args;
+use paymaster_rpc::PaymasterAPIClient;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, timeout, Instant};
+
+use crate::failover::Failover;
+use crate::types::{AvailabilityChange, HealthReport, StatusPageReport, StatusSample};
+use crate::TestError;
+
+// Characters kept of a status page that isn't JSON
+const MAX_TEXT_BODY: usize = 2048;
+
+#[derive(Args, Clone)]
+pub struct HealthArgs {
+    /// Poll the paymaster's availability this often while the test runs, 0 disables polling
+    #[arg(long, default_value = "5")]
+    pub health_interval_secs: u64,
+
+    /// Public status page or health JSON of the paymaster, fetched every
+    /// --status-interval-secs during the run. Every change of its HTTP status, or of
+    /// --status-field, is kept in the results, to line up the errors seen here with
+    /// incidents the operator declared
+    #[arg(long)]
+    pub status_url: Option<String>,
+
+    /// GraphQL query POSTed to --status-url instead of a plain GET
+    #[arg(long, requires = "status_url")]
+    pub status_query: Option<String>,
+
+    /// JSON pointer to the field of the --status-url response compared between fetches
+    /// along with the HTTP status, e.g. `/status/indicator`. The rest of the body isn't
+    /// compared, timestamps and request ids would make every fetch a change
+    #[arg(long, requires = "status_url", value_parser = parse_pointer)]
+    pub status_field: Option<String>,
+
+    /// Seconds between two fetches of --status-url
+    #[arg(long, default_value = "30", value_parser = clap::value_parser!(u64).range(1..))]
+    pub status_interval_secs: u64,
+}
+
+fn parse_pointer(raw: &str) -> Result<String, String> {
+    if raw.starts_with('/') {
+        Ok(raw.to_string())
+    } else {
+        Err(format!(
+            "expected a JSON pointer like `/status`, got `{}`",
+            raw
+        ))
+    }
+}
+
+impl HealthArgs {
+    pub fn interval(&self) -> Option<Duration> {
+        (self.health_interval_secs > 0).then(|| Duration::from_secs(self.health_interval_secs))
+    }
+
+    pub fn status_page(&self) -> Option<StatusPage> {
+        Some(StatusPage {
+            url: self.status_url.clone()?,
+            query: self.status_query.clone(),
+            field: self.status_field.clone(),
+            interval: Duration::from_secs(self.status_interval_secs),
+        })
+    }
+}
+
+// Where the status page is scraped from and how often
+pub struct StatusPage {
+    pub url: String,
+    pub query: Option<String>,
+    // JSON pointer compared between fetches, only the HTTP status without it
+    pub field: Option<String>,
+    pub interval: Duration,
+}
+
+// Polls `is_available` on whichever endpoint is currently active and records every
+// change of availability, so an outage shows up on the timeline and not only as errors
+pub struct HealthMonitor {
+    report: Arc<Mutex<HealthReport>>,
+    task: JoinHandle<()>,
+}
+
+impl HealthMonitor {
+    pub fn start(failover: Arc<Failover>, period: Duration, start: Instant) -> HealthMonitor {
+        let report = Arc::new(Mutex::new(HealthReport::default()));
+        let task_report = Arc::clone(&report);
+        let task = tokio::spawn(async move {
+            // The service was available when the run started
+            let mut available = true;
+            let mut ticker = interval(period);
+            loop {
+                ticker.tick().await;
+                let client = failover.client();
+                // A poll hanging for a whole period counts as unavailable
+                let (now_available, reason) = match timeout(period, client.is_available()).await {
+                    Ok(Ok(true)) => (true, None),
+                    Ok(Ok(false)) => (false, Some("reported unavailable".to_string())),
+                    Ok(Err(e)) => (false, Some(e.to_string())),
+                    Err(_) => (false, Some("timed out".to_string())),
+                };
+
+                let mut report = task_report.lock().unwrap();
+                report.polls += 1;
+                if !now_available {
+                    report.unavailable_polls += 1;
+                }
+                if now_available != available {
+                    available = now_available;
+                    report.changes.push(AvailabilityChange {
+                        at_secs: start.elapsed().as_secs_f64(),
+                        available,
+                        reason,
+                    });
+                }
+            }
+        });
+        HealthMonitor { report, task }
+    }
+
+    pub fn finish(self) -> HealthReport {
+        self.task.abort();
+        self.report.lock().unwrap().clone()
+    }
+}
+
+// Fetches the status page every interval and keeps what it returned whenever its status
+// or watched field changed, unchanged fetches are only counted
+pub struct StatusMonitor {
+    report: Arc<Mutex<StatusPageReport>>,
+    task: JoinHandle<()>,
+}
+
+impl StatusMonitor {
+    pub fn start(page: StatusPage, start: Instant) -> Result<StatusMonitor, TestError> {
+        let client = reqwest::Client::builder().timeout(page.interval).build()?;
+        let report = Arc::new(Mutex::new(StatusPageReport {
+            url: page.url.clone(),
+            ..Default::default()
+        }));
+        let task_report = Arc::clone(&report);
+        let task = tokio::spawn(async move {
+            let mut ticker = interval(page.interval);
+            let mut last = None;
+            loop {
+                ticker.tick().await;
+                let at_secs = start.elapsed().as_secs_f64();
+                let request = match &page.query {
+                    Some(query) => client.post(&page.url).json(&json!({ "query": query })),
+                    None => client.get(&page.url),
+                };
+                let sample = match request.send().await {
+                    Ok(response) => {
+                        let status = response.status().as_u16();
+                        match response.text().await {
+                            Ok(body) => (Some(status), Some(parse_body(&body)), None),
+                            Err(e) => (Some(status), None, Some(e.to_string())),
+                        }
+                    }
+                    Err(e) => (None, None, Some(e.to_string())),
+                };
+
+                let mut report = task_report.lock().unwrap();
+                report.fetches += 1;
+                if sample.2.is_some() || sample.0.is_some_and(|status| status >= 400) {
+                    report.failed_fetches += 1;
+                }
+                let key = change_key(page.field.as_deref(), &sample);
+                if last.as_ref() != Some(&key) {
+                    let (status, body, error) = sample;
+                    report.samples.push(StatusSample {
+                        at_secs,
+                        status,
+                        body,
+                        error,
+                    });
+                    last = Some(key);
+                }
+            }
+        });
+        Ok(StatusMonitor { report, task })
+    }
+
+    pub fn finish(self) -> StatusPageReport {
+        self.task.abort();
+        self.report.lock().unwrap().clone()
+    }
+}
+
+// What two fetches are compared on: the HTTP status, the watched field and whether the
+// fetch failed. Error messages and the rest of the body vary from one fetch to the next
+fn change_key(
+    field: Option<&str>,
+    (status, body, error): &(Option<u16>, Option<Value>, Option<String>),
+) -> (Option<u16>, Option<Value>, bool) {
+    let watched = field.and_then(|field| body.as_ref()?.pointer(field).cloned());
+    (*status, watched, error.is_some())
+}
+
+// JSON as is, anything else, e.g. an HTML status page, as a string cut to a sane length
+fn parse_body(body: &str) -> Value {
+    serde_json::from_str(body)
+        .unwrap_or_else(|_| Value::String(body.chars().take(MAX_TEXT_BODY).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(status: u16, body: Value) -> (Option<u16>, Option<Value>, Option<String>) {
+        (Some(status), Some(body), None)
+    }
+
+    #[test]
+    fn volatile_bodies_are_not_a_change() {
+        let a = fetch(200, json!({"status": "ok", "time": 1, "request_id": "a"}));
+        let b = fetch(200, json!({"status": "ok", "time": 2, "request_id": "b"}));
+        assert_eq!(change_key(None, &a), change_key(None, &b));
+        assert_eq!(
+            change_key(Some("/status"), &a),
+            change_key(Some("/status"), &b)
+        );
+    }
+
+    #[test]
+    fn status_and_watched_field_are_a_change() {
+        let ok = fetch(200, json!({"status": {"indicator": "none"}}));
+        let degraded = fetch(200, json!({"status": {"indicator": "minor"}}));
+        let down = fetch(503, json!({"status": {"indicator": "none"}}));
+        let field = Some("/status/indicator");
+        assert_ne!(change_key(field, &ok), change_key(field, &degraded));
+        assert_ne!(change_key(field, &ok), change_key(field, &down));
+        assert_eq!(change_key(None, &ok), change_key(None, &degraded));
+    }
+
+    #[test]
+    fn failed_fetches_are_compared_by_failing() {
+        let a = (None, None, Some("connection refused".to_string()));
+        let b = (None, None, Some("timed out".to_string()));
+        assert_eq!(change_key(None, &a), change_key(None, &b));
+        assert_ne!(
+            change_key(None, &a),
+            change_key(None, &fetch(200, json!({})))
+        );
+    }
+
+    #[test]
+    fn status_fields_are_json_pointers() {
+        assert!(parse_pointer("/status/indicator").is_ok());
+        assert!(parse_pointer("status").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use clap::Args;
+use std::process::Command;
+use tokio::task::spawn_blocking;
+use tokio::time::Instant;
+
+use crate::types::{HookRun, HookStage};
+use crate::TestError;
+
+// Output kept per stream and hook run
+const MAX_OUTPUT_BYTES: usize = 4096;
+
+#[derive(Args, Clone)]
+pub struct HookArgs {
+    /// Shell command run before every step, e.g. to scale the paymaster, with STEP, RUN_TAG
+    /// and TARGET_TPS (USERS for closed-loop runs) set in its environment
+    #[arg(long)]
+    pub pre_step_hook: Option<String>,
+
+    /// Shell command run after every step, e.g. to capture a server-side profile, also given
+    /// SUCCESS_RATE, EFFECTIVE_TPS and P95_LATENCY_MS of the step
+    #[arg(long)]
+    pub post_step_hook: Option<String>,
+}
+
+// Runs the hook through `sh -c` and waits for it, the next step only starts afterwards
+// A hook exiting with an error is reported but doesn't stop the run
+pub async fn run_hook(
+    stage: HookStage,
+    command: &str,
+    step: u32,
+    env: &[(&str, String)],
+) -> Result<HookRun, TestError> {
+    let mut process = Command::new("sh");
+    process.arg("-c").arg(command);
+    for (name, value) in env {
+        process.env(name, value);
+    }
+    let start = Instant::now();
+    let output = spawn_blocking(move || process.output()).await??;
+    let duration_ms = start.elapsed().as_secs_f64() * 1000.0;
+    if !output.status.success() {
+        eprintln!("Step {} hook `{}` failed: {}", step, command, output.status);
+    }
+    Ok(HookRun {
+        stage,
+        step,
+        exit_code: output.status.code(),
+        duration_ms,
+        stdout: tail(&output.stdout),
+        stderr: tail(&output.stderr),
+    })
+}
+
+fn tail(output: &[u8]) -> String {
+    let start = output.len().saturating_sub(MAX_OUTPUT_BYTES);
+    String::from_utf8_lossy(&output[start..]).into_owned()
+}
@@ -0,0 +1,267 @@
+use clap::ValueEnum;
+use jsonrpsee::http_client::HttpClientBuilder;
+use paymaster_rpc::PaymasterAPIClient;
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use url::Url;
+
+use crate::account::{load_accounts, parse_felt};
+use crate::control::to_cli_args;
+use crate::network::{Network, NetworkArgs, PRODUCTION_MAX_TPS, PRODUCTION_MAX_TRANSACTIONS};
+use crate::scenario::{CustomScenario, Scenario};
+use crate::TestError;
+
+// Walks through the options of a first run and writes them to `out`, for
+// `linear --config <out>`. Every answer is checked before moving on
+pub async fn init(out: &Path) -> Result<(), TestError> {
+    println!("Setting up a paymaster stress test, press enter to take the default in brackets");
+    let mut config = Map::new();
+
+    loop {
+        let endpoint = ask(
+            "Paymaster endpoint",
+            Some("http://localhost:12777"),
+            |raw| {
+                Url::parse(raw).map_err(|e| e.to_string())?;
+                Ok(raw.to_string())
+            },
+        )?;
+        let available = match HttpClientBuilder::default()
+            .request_timeout(Duration::from_secs(5))
+            .build(&endpoint)
+        {
+            Ok(client) => client.is_available().await.unwrap_or(false),
+            Err(_) => false,
+        };
+        config.insert("endpoint".into(), endpoint.into());
+        if available || confirm("The paymaster is not reachable, keep it anyway?")? {
+            break;
+        }
+    }
+
+    let network = ask(
+        "Network (sepolia, mainnet, custom)",
+        Some("sepolia"),
+        |raw| Network::from_str(raw, true),
+    )?;
+    config.insert("network".into(), network_name(network).into());
+    let mut gas_token = None;
+    if let Network::Custom = network {
+        let token = ask("Gas token address", None, |raw| {
+            parse_felt(raw).map(|_| raw.to_string())
+        })?;
+        config.insert("gas_token".into(), token.clone().into());
+        gas_token = Some(token);
+        let chain_id = ask(
+            "Chain id, e.g. SN_MY_APPCHAIN (empty to skip the check)",
+            Some(""),
+            |raw| Ok(raw.to_string()),
+        )?;
+        if !chain_id.is_empty() {
+            config.insert("chain_id".into(), chain_id.into());
+        }
+    }
+
+    let accounts = ask(
+        "Accounts file (empty for the PRIVATE_KEY account)",
+        Some(""),
+        |raw| {
+            if raw.is_empty() {
+                return Ok(None);
+            }
+            let accounts = load_accounts(Path::new(raw)).map_err(|e| e.to_string())?;
+            println!("  {} account(s)", accounts.len());
+            Ok(Some(raw.to_string()))
+        },
+    )?;
+    match accounts {
+        Some(path) => {
+            config.insert("accounts".into(), path.into());
+        }
+        None => {
+            let address = ask("Account address (empty for the default)", Some(""), |raw| {
+                if raw.is_empty() {
+                    Ok(None)
+                } else {
+                    parse_felt(raw).map(|_| Some(raw.to_string()))
+                }
+            })?;
+            if let Some(address) = address {
+                config.insert("account_address".into(), address.into());
+            }
+        }
+    }
+
+    let scenario = ask(
+        "Scenario (transfer, approve-transfer-from, nonce-chain or a scenario file)",
+        Some("transfer"),
+        |raw| {
+            if let Ok(scenario) = Scenario::from_str(raw, true) {
+                return Ok(Ok(scenario));
+            }
+            CustomScenario::load(Path::new(raw))
+                .map_err(|e| format!("not a built-in scenario nor a scenario file: {}", e))?;
+            Ok(Err(raw.to_string()))
+        },
+    )?;
+    match scenario {
+        Ok(scenario) => {
+            config.insert("scenario".into(), scenario_name(scenario).into());
+            if let Scenario::NonceChain = scenario {
+                let length = ask("Transactions per chain", Some("10"), parse_positive)?;
+                config.insert("chain_length".into(), length.into());
+            }
+        }
+        Err(path) => {
+            config.insert("scenario_file".into(), path.into());
+        }
+    }
+
+    let network_args = NetworkArgs {
+        network,
+        gas_token,
+        chain_id: None,
+        no_safety_limits: false,
+        i_know_this_is_production: false,
+        production_endpoint: Vec::new(),
+        production_max_tps: PRODUCTION_MAX_TPS,
+        production_max_transactions: PRODUCTION_MAX_TRANSACTIONS,
+    };
+    let endpoint = config["endpoint"].as_str().unwrap_or_default();
+    let production = network_args.production_reason(&[endpoint]);
+    if let Some(reason) = &production {
+        if !confirm(&format!("{}, run against it anyway?", reason))? {
+            return Err("nothing written".into());
+        }
+        config.insert("i_know_this_is_production".into(), true.into());
+    }
+    let limits = network_args.resolve()?;
+    let max_tps = ask("Highest TPS of the ramp", Some("10"), |raw| {
+        let max_tps = parse_positive(raw)?;
+        limits.check_max_tps(max_tps).map_err(|e| e.to_string())?;
+        if production.is_some() && max_tps > PRODUCTION_MAX_TPS {
+            return Err(format!(
+                "production runs are capped at {} TPS",
+                PRODUCTION_MAX_TPS
+            ));
+        }
+        Ok(max_tps)
+    })?;
+    config.insert("max_tps".into(), max_tps.into());
+    let duration = ask(
+        "Duration of the whole ramp in seconds",
+        Some("60"),
+        parse_positive,
+    )?;
+    config.insert("duration".into(), duration.into());
+    let steps = ask("Number of steps", Some("5"), |raw| {
+        let steps = parse_positive(raw)?;
+        if steps > duration {
+            return Err("steps can't be shorter than a second".to_string());
+        }
+        Ok(steps)
+    })?;
+    config.insert("steps".into(), steps.into());
+    let output = ask("Results file", Some("results.json"), |raw| {
+        Ok(raw.to_string())
+    })?;
+    config.insert("output".into(), output.into());
+
+    if out.exists() && !confirm(&format!("{} exists, overwrite it?", out.display()))? {
+        return Err("nothing written".into());
+    }
+    fs::write(out, serde_json::to_string_pretty(&Value::Object(config))?)?;
+    println!("Saved to {}, run it with:", out.display());
+    println!("  paymaster-stress linear --config {}", out.display());
+    Ok(())
+}
+
+// Replaces `--config <file>` of the `linear` subcommand with the options stored in the
+// file, options given after it on the command line take precedence
+pub fn expand_config(argv: Vec<String>) -> Result<Vec<String>, TestError> {
+    if argv.get(1).map(String::as_str) != Some("linear") {
+        return Ok(argv);
+    }
+    let Some(position) = argv
+        .iter()
+        .position(|arg| arg == "--config" || arg.starts_with("--config="))
+    else {
+        return Ok(argv);
+    };
+    let path = match argv[position].strip_prefix("--config=") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from(argv.get(position + 1).ok_or("--config needs a file")?),
+    };
+    let config: Map<String, Value> = serde_json::from_str(&fs::read_to_string(&path)?)
+        .map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+    let mut expanded = argv[..position].to_vec();
+    expanded.extend(to_cli_args(&config)?);
+    expanded.extend(argv[position..].iter().cloned());
+    Ok(expanded)
+}
+
+// Asks until `parse` accepts the answer, an empty answer takes the default
+fn ask<T>(
+    question: &str,
+    default: Option<&str>,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T, TestError> {
+    loop {
+        match default {
+            Some(default) if !default.is_empty() => print!("{} [{}]: ", question, default),
+            _ => print!("{}: ", question),
+        }
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line)? == 0 {
+            return Err("input closed".into());
+        }
+        let answer = match (line.trim(), default) {
+            ("", Some(default)) => default,
+            ("", None) => {
+                println!("  an answer is required");
+                continue;
+            }
+            (answer, _) => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) => println!("  {}", e),
+        }
+    }
+}
+
+fn confirm(question: &str) -> Result<bool, TestError> {
+    ask(&format!("{} (y/n)", question), Some("n"), |raw| {
+        match raw.to_lowercase().as_str() {
+            "y" | "yes" => Ok(true),
+            "n" | "no" => Ok(false),
+            _ => Err("answer y or n".to_string()),
+        }
+    })
+}
+
+fn parse_positive(raw: &str) -> Result<u32, String> {
+    match raw.parse::<u32>() {
+        Ok(value) if value > 0 => Ok(value),
+        _ => Err(format!("expected a positive number, got `{}`", raw)),
+    }
+}
+
+// Names as the CLI spells them
+fn network_name(network: Network) -> String {
+    network
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
+
+fn scenario_name(scenario: Scenario) -> String {
+    scenario
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
@@ -1,21 +1,61 @@
-use clap::{command, Parser, Subcommand};
-use paymaster_rpc::client::Client;
-use starknet::core::types::{Call, Felt};
-use starknet::signers::SigningKey;
-use std::fs;
+use clap::{CommandFactory, Parser, Subcommand};
 use std::path::PathBuf;
-use std::process::exit;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::task::JoinSet;
-use tokio::time::{interval, Instant};
+mod abi;
+mod account;
+mod assert;
+mod batching;
+mod benchsign;
+mod cacheprobe;
+mod ci;
+mod client;
+mod coldstart;
+mod completions;
+mod control;
+mod crash;
+mod daemon;
+mod db;
+mod discover;
+mod encrypt;
+mod examples;
+mod failover;
+mod fund;
+mod health;
+mod hooks;
+mod init;
+mod manifest;
+mod merge;
+mod network;
+mod pool;
+mod poolstatus;
+mod postmortem;
+mod probe;
+mod ramp;
+mod receipts;
+mod redact;
+mod replay;
+mod report;
+mod resources;
+mod run;
+mod scenario;
+mod selftest;
+mod serve;
+mod signer;
+mod sink;
+mod sketch;
+mod smoke;
+mod stats;
+mod statsd;
+mod taxonomy;
+mod transaction;
+mod trend;
+mod txlog;
 mod types;
-use crate::types::*;
-use paymaster_rpc::{
-    BuildTransactionRequest, BuildTransactionResponse, ExecutableInvokeParameters,
-    ExecutableTransactionParameters, ExecuteRequest, ExecutionParameters, FeeMode,
-    InvokeParameters, TransactionParameters,
-};
+mod upload;
+mod validate;
+mod watchdog;
+use crate::ramp::LiveStats;
+use crate::run::{run_linear, LinearArgs};
 
 #[derive(Parser)]
 #[command(name = "paymaster-stress")]
@@ -25,287 +65,263 @@ struct Cli {
     command: Commands,
 }
 
+// Parsed once at startup, the size difference between variants doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand)]
 enum Commands {
-    // Test Sending Increasing TPS to Paymaster
-    // Only 1 command type supported for now
-    Linear {
-        #[arg(long, default_value = "http://localhost:12777")]
-        endpoint: String,
-
+    /// Test Sending Increasing TPS to Paymaster
+    ///
+    /// Options given after --config override the ones from the file
+    #[command(args_override_self = true)]
+    Linear(LinearArgs),
+    /// Browse stored result files in a local web UI
+    ServeResults {
+        /// Directory of result files
+        dir: PathBuf,
+
+        /// Address the web UI listens on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: String,
+    },
+    /// HTTP API to start, watch and stop runs from an orchestrator
+    Control {
+        /// Address the API listens on
+        #[arg(long, default_value = "127.0.0.1:9090")]
+        listen: String,
+    },
+    /// Discover the paymaster's rate limits by raising the request rate until it pushes back
+    ProbeLimits(probe::ProbeArgs),
+    /// Compare repeated identical build requests against near-identical ones to find out
+    /// whether the paymaster caches build responses
+    ProbeCache(cacheprobe::CacheProbeArgs),
+    /// Latency of the first request after the paymaster sat idle, against the requests
+    /// right behind it
+    ProbeColdStart(coldstart::ColdStartArgs),
+    /// Execute transactions several at a time in JSON-RPC batches and compare them against
+    /// single executes
+    ProbeBatching(batching::BatchingArgs),
+    /// Render a stored result file, to stdout unless --out is given
+    Report {
+        /// Result file written by `linear`
+        results: PathBuf,
+
+        /// Format of the report
+        #[arg(long, value_enum, default_value = "md")]
+        format: report::ReportFormat,
+
+        /// Write the report to this file
         #[arg(long)]
-        max_tps: u32,
-
-        #[arg(long, default_value = "5")]
-        duration: u32,
-
-        #[arg(long, default_value = "5")]
-        steps: u32,
+        out: Option<PathBuf>,
+    },
+    /// Run the ramp against a simulated paymaster with known error rate and latency,
+    /// and check the reported metrics match them
+    SelfTest(selftest::SelfTestArgs),
+    /// How many transactions per second this machine can hash and sign, the ceiling of any
+    /// run from it
+    BenchSigning(benchsign::BenchSigningArgs),
+    /// Send a few transactions per scenario, fee mode and account and check each succeeds
+    Smoke(smoke::SmokeArgs),
+    /// Hold one rate for a while and pass or fail on the success rate, e.g.
+    /// `assert --tps 100 --duration 10m --min-success 0.99 -- --config campaign.json`
+    Assert(assert::AssertArgs),
+    /// Send a failing transaction of a --trace-sample again, with every request and response
+    /// printed, to reproduce it without rerunning the campaign
+    ReplayTx(replay::ReplayArgs),
+    /// Request testnet funds from a faucet for every account of the pool
+    Fund(fund::FundArgs),
+    /// Inspect the accounts of a pool file on-chain
+    Accounts {
+        #[command(subcommand)]
+        command: poolstatus::AccountsCommand,
+    },
+    /// Run a `linear --config` campaign on a schedule and keep a rolling trend of the runs
+    Daemon(daemon::DaemonArgs),
+    /// Trend of the runs stored in a directory, flagging runs worse than the ones before
+    Trend(trend::TrendArgs),
+    /// Put the result files of workers that ran side by side together, with latency and
+    /// errors per --region
+    Merge {
+        /// Result files of the workers
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+
+        /// Write the merged results to this JSON file instead of stdout
+        #[arg(long)]
+        output: Option<PathBuf>,
+    },
+    /// Check a `linear --config` file before the run: endpoint, network, gas tokens,
+    /// account keys and scenario calldata, without sending a transaction
+    ValidateConfig {
+        /// Config file to check
+        file: PathBuf,
 
+        /// Write the checks to this JSON file instead of stdout
         #[arg(long)]
         output: Option<PathBuf>,
     },
+    /// Build a config file for `linear --config` step by step
+    Init {
+        /// Path the config file is written to
+        #[arg(long, default_value = "paymaster-stress.json")]
+        out: PathBuf,
+    },
+    /// Print a shell completion script, e.g. `source <(paymaster-stress completions bash)`
+    Completions {
+        /// Shell to complete for
+        #[arg(value_enum)]
+        shell: clap_complete::Shell,
+    },
+    /// Print ready-to-run command lines for common workflows
+    Examples,
 }
 
 type TestError = Box<dyn std::error::Error>;
 
-#[derive(Debug)]
-enum TransactionError {
-    Nonce,
-    Timeout,
-    Relayer,
-    JsonRpc,
-    Other,
-}
-
 #[tokio::main]
 async fn main() -> Result<(), TestError> {
-    let cli = Cli::parse();
+    let cli = Cli::parse_from(init::expand_config(std::env::args().collect())?);
 
     match cli.command {
-        Commands::Linear {
-            endpoint,
-            max_tps,
-            duration,
-            steps,
-            output,
-        } => {
-            let client = Client::new(&endpoint);
-            let duration = Duration::from_secs(duration as u64);
-            // Check if paymaster service is available
-            if !client.is_available().await? {
-                eprintln!("Paymaster service not available at {}", endpoint);
-                exit(1);
+        Commands::Linear(args) => {
+            let quiet = args.quiet;
+            let results = run_linear(args, Arc::new(LiveStats::default())).await?;
+            if !quiet {
+                print!("{}", report::summary(&results)?);
             }
-
-            println!("Starting single account stress test:");
-            println!("  Endpoint: {}", endpoint);
-            println!("  Max TPS: {}", max_tps);
-            println!("  Duration for Full Test: {:?}", duration);
-            println!("  Steps: {}", steps);
-            println!();
-
-            let config = envy::from_env::<Config>().unwrap();
-            let private_key = config.private_key;
-            let results = linear_ramp_test(client, private_key, max_tps, duration, steps).await?;
-
-            if let Some(output_path) = output {
-                fs::write(&output_path, serde_json::to_string_pretty(&results)?)?;
-                println!("Results saved to: {}", output_path.display());
-            } else {
+            if results.acceptance.as_ref().is_some_and(|a| !a.passed) {
+                return Err("the run doesn't meet the expectations of the manifest".into());
+            }
+        }
+        Commands::ServeResults { dir, listen } => {
+            serve::serve_results(dir, &listen).await?;
+        }
+        Commands::Control { listen } => {
+            control::serve_control(&listen).await?;
+        }
+        Commands::ProbeLimits(args) => {
+            let print = args.output.is_none();
+            let results = probe::probe_limits(args).await?;
+            if print {
                 println!("{}", serde_json::to_string_pretty(&results)?);
             }
         }
-    }
-
-    Ok(())
-}
-
-// We divide the test duration by number of steps into equally sized duration for each sample tps
-// For each such sub duration, we send the desired tps
-// tps ramps up from 1 to target max tps
-// We send txs asynchronously and wait for the results
-// For each result we update the metrics and errors
-// Finally we compile summary statistics
-async fn linear_ramp_test(
-    client: Client,
-    private_key: String,
-    max_tps: u32,
-    duration: Duration,
-    steps: u32,
-) -> Result<StressTestResults, TestError> {
-    let client = Arc::new(client);
-    let mut results = Vec::new();
-    let test_start = Instant::now();
-
-    // Test account (hardcoded for simplicity)
-    let user_address =
-        Felt::from_hex("0x059e0eaf58972c3b7de923ad6a280476430295f7ea967b768bd381bf5d90d50b")?;
-    let private_key =
-        Felt::from_hex(private_key.as_str())?;
-    let signing_key = SigningKey::from_secret_scalar(private_key);
-
-    // Simple STRK transfer call
-    let strk_token =
-        Felt::from_hex("0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d")?;
-    let transfer_call = Call {
-        to: strk_token,
-        selector: Felt::from_hex(
-            "0x83afd3f4caedc6eebf44246fe54e38c95e3179a5ec9ea81740eca5b482d12e",
-        )?, // transfer selector
-        calldata: vec![
-            Felt::from_hex("0x03f27a34e5e5483bf91257a3232ba753cc94e5b4ca19f8e200e8387e4a2ce555")?, // to
-            Felt::ONE,    // amount (low)
-            Felt::ZERO,   // amount (high)
-        ],
-    };
-
-    let step_duration = duration / steps;
-
-    for step in 1..=steps {
-        // Gradually increase tps on each run
-        let target_tps = (max_tps * step) / steps;
-        if target_tps == 0 {
-            continue;
+        Commands::ProbeCache(args) => {
+            let print = args.output.is_none();
+            let results = cacheprobe::probe_cache(args).await?;
+            if print {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            }
         }
-
-        println!("Testing TPS: {}", target_tps);
-
-        let mut task_set = JoinSet::new();
-        // Start interval timer
-        let mut ticker = interval(Duration::from_millis(1000 / target_tps as u64));
-        let step_start = Instant::now();
-
-        // Send transactions at target TPS for step_duration amount of time
-        while step_start.elapsed() < step_duration {
-            ticker.tick().await;
-
-            let task_client = Arc::clone(&client);
-            let task_call = transfer_call.clone();
-            let task_key = signing_key.clone();
-            task_set.spawn(async move {
-                send_single_transaction(task_client, user_address, task_call, task_key, strk_token)
-                    .await
-            });
+        Commands::ProbeColdStart(args) => {
+            let print = args.output.is_none();
+            let results = coldstart::probe_cold_start(args).await?;
+            if print {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            }
         }
-
-        // Wait for all in-flight tasks to complete
-        let mut metrics = Metrics::default();
-        let mut errors = ErrorBreakdown::default();
-        let mut latencies = Vec::new();
-
-        while let Some(result) = task_set.join_next().await {
-            match result? {
-                Ok(latency) => {
-                    metrics.successful_txs += 1;
-                    latencies.push(latency);
+        Commands::ProbeBatching(args) => {
+            let print = args.output.is_none();
+            let results = batching::probe_batching(args).await?;
+            if print {
+                println!("{}", serde_json::to_string_pretty(&results)?);
+            }
+        }
+        Commands::Report {
+            results,
+            format,
+            out,
+        } => {
+            let report = report::render(&results, format)?;
+            match out {
+                Some(path) => std::fs::write(path, report)?,
+                None => print!("{}", report),
+            }
+        }
+        Commands::Smoke(args) => {
+            let output = args.output.clone();
+            let report = smoke::smoke(args).await?;
+            match output {
+                Some(path) => std::fs::write(path, serde_json::to_string_pretty(&report)?)?,
+                None => println!("{}", serde_json::to_string_pretty(&report)?),
+            }
+            if !report.passed {
+                return Err("smoke test failed".into());
+            }
+        }
+        Commands::BenchSigning(args) => {
+            let report = benchsign::bench_signing(&args).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Commands::Assert(args) => {
+            if !assert::assert_load(args).await? {
+                return Err("the paymaster didn't sustain the load".into());
+            }
+        }
+        Commands::ReplayTx(args) => {
+            let output = args.output.clone();
+            let report = replay::replay_tx(args).await?;
+            if let Some(path) = output {
+                std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+            }
+        }
+        Commands::Fund(args) => {
+            let report = fund::fund(args).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if report.funded < report.requests.len() as u32 {
+                return Err("the faucet didn't fund every account".into());
+            }
+        }
+        Commands::Accounts { command } => match command {
+            poolstatus::AccountsCommand::Status(args) => {
+                let report = poolstatus::pool_status(&args).await?;
+                if let Some(path) = &args.output {
+                    std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
                 }
-                Err(error_type) => {
-                    metrics.failed_txs += 1;
-                    match error_type {
-                        TransactionError::Nonce => errors.nonce_conflicts += 1,
-                        TransactionError::Timeout => errors.timeouts += 1,
-                        TransactionError::Relayer => errors.relayer_exhaustion += 1,
-                        TransactionError::JsonRpc => errors.json_rpc_errors += 1,
-                        TransactionError::Other => errors.other += 1,
-                    }
+                if report.degraded > 0 {
+                    return Err("the pool has degraded accounts".into());
                 }
             }
-        }
-
-        metrics.total_txs = metrics.successful_txs + metrics.failed_txs;
-        metrics.avg_latency_ms = if !latencies.is_empty() {
-            latencies.iter().sum::<f64>() / latencies.len() as f64
-        } else {
-            0.0
-        };
-        metrics.success_rate = if metrics.total_txs > 0 {
-            metrics.successful_txs as f64 / metrics.total_txs as f64
-        } else {
-            0.0
-        };
-        results.push(TestResult {
-            metrics,
-            error_breakdown: errors,
-        });
-    }
-
-    let total_successful: u32 = results.iter().map(|r| r.metrics.successful_txs).sum();
-    let overall_success_rate =
-        results.iter().map(|r| r.metrics.success_rate).sum::<f64>() / results.len() as f64;
-
-    // We define sustainable tps as that at which tx success rate is more than 95%
-    let max_sustainable_tps = results
-        .iter()
-        .filter(|r| r.metrics.success_rate > 0.95)
-        .map(|r| r.metrics.target_tps)
-        .max()
-        .unwrap_or(0);
-
-    Ok(StressTestResults {
-        total_duration_secs: test_start.elapsed().as_secs(),
-        results,
-        summary: TestSummary {
-            max_sustainable_tps,
-            total_transactions: total_successful,
-            overall_success_rate,
         },
-    })
-}
-
-async fn send_single_transaction(
-    client: Arc<Client>,
-    user_address: Felt,
-    transfer_call: Call,
-    signing_key: SigningKey,
-    eth_token: Felt,
-) -> Result<f64, TransactionError> {
-    let tx_start = Instant::now();
-
-    // Build transaction
-    let build_request = BuildTransactionRequest {
-        transaction: TransactionParameters::Invoke {
-            invoke: InvokeParameters {
-                user_address,
-                calls: vec![transfer_call],
-            },
-        },
-        parameters: ExecutionParameters::V1 {
-            fee_mode: FeeMode::Default {
-                gas_token: eth_token,
-            },
-            time_bounds: None,
-        },
-    };
-
-    let invoke_tx = match client.build_transaction(build_request).await {
-        Ok(BuildTransactionResponse::Invoke(tx)) => tx,
-        Err(_) => return Err(TransactionError::Other),
-        _ => panic!("should not get this tx type"),
-    };
-
-    // Sign the transaction
-    let message_hash = invoke_tx
-        .typed_data
-        .message_hash(user_address)
-        .map_err(|_| TransactionError::Other)?;
-
-    let signature = signing_key
-        .sign(&message_hash)
-        .map_err(|_| TransactionError::Other)?;
-
-    // Execute transaction
-    let execute_request = ExecuteRequest {
-        transaction: ExecutableTransactionParameters::Invoke {
-            invoke: ExecutableInvokeParameters {
-                user_address,
-                typed_data: invoke_tx.typed_data,
-                signature: vec![signature.r, signature.s],
-            },
-        },
-        parameters: ExecutionParameters::V1 {
-            fee_mode: FeeMode::Default {
-                gas_token: eth_token,
-            },
-            time_bounds: None,
-        },
-    };
-
-    match client.execute_transaction(execute_request).await {
-        Ok(_) => Ok(tx_start.elapsed().as_millis() as f64),
-        Err(e) => {
-            let error_str = e.to_string();
-            if error_str.contains("nonce") {
-                Err(TransactionError::Nonce)
-            } else if error_str.contains("timeout") {
-                Err(TransactionError::Timeout)
-            } else if error_str.contains("relayer") || error_str.contains("unavailable") {
-                Err(TransactionError::Relayer)
-            } else if error_str.contains("JSON-RPC error") {
-                Err(TransactionError::JsonRpc)
-            } else {
-                Err(TransactionError::Other)
+        Commands::Daemon(args) => {
+            daemon::daemon(args).await?;
+        }
+        Commands::Trend(args) => {
+            let runs = trend::trend(&args)?;
+            if runs.last().is_some_and(|r| !r.regressions.is_empty()) {
+                return Err("the latest run regressed".into());
+            }
+        }
+        Commands::Merge { files, output } => {
+            merge::merge(&files, output.as_deref())?;
+        }
+        Commands::ValidateConfig { file, output } => {
+            let report = validate::validate_config(&file).await?;
+            if let Some(path) = output {
+                std::fs::write(path, serde_json::to_string_pretty(&report)?)?;
+            }
+            if !report.passed {
+                return Err("the config has errors".into());
+            }
+        }
+        Commands::Init { out } => {
+            init::init(&out).await?;
+        }
+        Commands::Completions { shell } => {
+            print!("{}", completions::generate(shell, &mut Cli::command())?);
+        }
+        Commands::Examples => {
+            print!("{}", examples::EXAMPLES);
+        }
+        Commands::SelfTest(args) => {
+            let report = selftest::self_test(args).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.passed {
+                return Err(
+                    "self-test failed, reported metrics don't match the injected ones".into(),
+                );
             }
         }
     }
+
+    Ok(())
 }
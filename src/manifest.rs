@@ -0,0 +1,131 @@
+use clap::Args;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct ManifestArgs {
+    /// JSON file of the capacity each environment is expected to sustain, e.g.
+    /// {"staging": [{"tps": 150, "min_success_rate": 0.97}]}
+    #[arg(long, requires = "environment")]
+    pub manifest: Option<PathBuf>,
+
+    /// Entry of the manifest the run is checked against
+    #[arg(long, requires = "manifest")]
+    pub environment: Option<String>,
+
+    /// Zip the results, failing traces, generator diagnostics and summary here when the
+    /// run misses the manifest, ready to attach to an incident ticket
+    #[arg(long, requires = "manifest")]
+    pub postmortem: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+pub struct Expectation {
+    pub tps: u32,
+    #[serde(default = "default_success_rate")]
+    pub min_success_rate: f64,
+    #[serde(default)]
+    pub max_p95_latency_ms: Option<f64>,
+}
+
+fn default_success_rate() -> f64 {
+    0.95
+}
+
+impl ManifestArgs {
+    // Read before the run so that a typo in the environment doesn't waste one
+    pub fn load(&self) -> Result<Option<(String, Vec<Expectation>)>, TestError> {
+        let (Some(path), Some(environment)) = (&self.manifest, &self.environment) else {
+            return Ok(None);
+        };
+        let mut manifest = read_manifest(path)?;
+        let expectations = manifest.remove(environment).ok_or_else(|| {
+            format!(
+                "no environment `{}` in {}, it has: {}",
+                environment,
+                path.display(),
+                manifest.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        Ok(Some((environment.clone(), expectations)))
+    }
+}
+
+fn read_manifest(path: &Path) -> Result<BTreeMap<String, Vec<Expectation>>, TestError> {
+    serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("invalid manifest {}: {}", path.display(), e).into())
+}
+
+// A step meets an expectation when both its success rate and p95 are within bounds, the
+// measured capacity is the highest target TPS of such a step. As for the summary, a peak
+// that failed its confirmation and anything above it don't count
+pub fn check(
+    environment: String,
+    expectations: &[Expectation],
+    results: &StressTestResults,
+) -> Acceptance {
+    let failed_peak = results
+        .peak_verification
+        .as_ref()
+        .filter(|v| !v.passed)
+        .map(|v| v.target_tps);
+    let mut checked = Vec::new();
+    for expectation in expectations {
+        let meets = |metrics: &Metrics| {
+            metrics.success_rate >= expectation.min_success_rate
+                && expectation
+                    .max_p95_latency_ms
+                    .is_none_or(|max| metrics.p95_latency_ms <= max)
+        };
+        let measured_tps = results
+            .results
+            .iter()
+            .map(|r| &r.metrics)
+            .filter(|m| meets(m))
+            .map(|m| m.target_tps)
+            .filter(|tps| failed_peak.is_none_or(|peak| *tps < peak))
+            .max()
+            .unwrap_or(0);
+        // First step at or above the expected rate, to show how far off it was there
+        let at_tps = results
+            .results
+            .iter()
+            .map(|r| &r.metrics)
+            .find(|m| m.target_tps >= expectation.tps);
+        checked.push(ExpectationResult {
+            tps: expectation.tps,
+            min_success_rate: expectation.min_success_rate,
+            max_p95_latency_ms: expectation.max_p95_latency_ms,
+            measured_tps,
+            tps_delta: measured_tps as i64 - expectation.tps as i64,
+            success_rate_delta: at_tps.map(|m| m.success_rate - expectation.min_success_rate),
+            p95_latency_delta_ms: at_tps.and_then(|m| {
+                expectation
+                    .max_p95_latency_ms
+                    .map(|max| m.p95_latency_ms - max)
+            }),
+            passed: measured_tps >= expectation.tps,
+        });
+    }
+    for expectation in &checked {
+        println!(
+            "{}: {} TPS at >= {:.1}%: {} (measured {} TPS, {:+})",
+            environment,
+            expectation.tps,
+            expectation.min_success_rate * 100.0,
+            if expectation.passed { "PASS" } else { "FAIL" },
+            expectation.measured_tps,
+            expectation.tps_delta
+        );
+    }
+    Acceptance {
+        environment,
+        passed: checked.iter().all(|e| e.passed),
+        expectations: checked,
+    }
+}
@@ -0,0 +1,132 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::types::*;
+use crate::TestError;
+
+// Runs without --region
+const UNLABELED: &str = "unlabeled";
+
+// Puts the result files of workers that ran side by side together, per --region, so
+// latency and errors of each region can be compared. Files are read as plain JSON like
+// `report` does
+pub fn merge(files: &[PathBuf], output: Option<&Path>) -> Result<MergedResults, TestError> {
+    let mut runs = Vec::new();
+    for path in files {
+        let run: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if !run["results"].is_array() {
+            return Err(format!("{} is not a result file", path.display()).into());
+        }
+        runs.push(run);
+    }
+    let region = |run: &Value| run["region"].as_str().unwrap_or(UNLABELED).to_string();
+
+    let mut overall: BTreeMap<String, RegionTotals> = BTreeMap::new();
+    let mut steps: BTreeMap<u32, (u32, BTreeMap<String, RegionTotals>)> = BTreeMap::new();
+    for run in &runs {
+        let region = region(run);
+        let worker = overall.entry(region.clone()).or_default();
+        worker.workers += 1;
+        for (i, step) in run["results"].as_array().into_iter().flatten().enumerate() {
+            let metrics = &step["metrics"];
+            worker.add(metrics);
+            let (target_tps, regions) = steps.entry(i as u32 + 1).or_default();
+            *target_tps += metrics["target_tps"].as_u64().unwrap_or(0) as u32;
+            let totals = regions.entry(region.clone()).or_default();
+            totals.workers += 1;
+            totals.add(metrics);
+        }
+    }
+
+    let merged = MergedResults {
+        files: files.to_vec(),
+        regions: overall
+            .into_iter()
+            .map(|(region, totals)| totals.finish(region))
+            .collect(),
+        steps: steps
+            .into_iter()
+            .map(|(step, (target_tps, regions))| MergedStep {
+                step,
+                target_tps,
+                regions: regions
+                    .into_iter()
+                    .map(|(region, totals)| totals.finish(region))
+                    .collect(),
+            })
+            .collect(),
+    };
+    print_regions(&merged);
+    if let Some(path) = output {
+        fs::write(path, serde_json::to_string_pretty(&merged)?)?;
+        println!("Merged results saved to: {}", path.display());
+    }
+    Ok(merged)
+}
+
+fn print_regions(merged: &MergedResults) {
+    println!(
+        "{:<16} {:>7} {:>9} {:>9} {:>10} {:>10}",
+        "region", "workers", "txs", "success", "p95 ms", "p99 ms"
+    );
+    for region in &merged.regions {
+        println!(
+            "{:<16} {:>7} {:>9} {:>8.1}% {:>10.1} {:>10.1}",
+            region.region,
+            region.workers,
+            region.total_txs,
+            region.success_rate * 100.0,
+            region.p95_latency_ms,
+            region.p99_latency_ms
+        );
+    }
+}
+
+#[derive(Default)]
+struct RegionTotals {
+    workers: u32,
+    total_txs: u64,
+    successful_txs: u64,
+    // Sums weighted by the successful transactions
+    latency_sum: f64,
+    p95_sum: f64,
+    p99_sum: f64,
+}
+
+impl RegionTotals {
+    fn add(&mut self, metrics: &Value) {
+        let successful = metrics["successful_txs"].as_u64().unwrap_or(0);
+        let weighted = |key: &str| metrics[key].as_f64().unwrap_or(0.0) * successful as f64;
+        self.total_txs += metrics["total_txs"].as_u64().unwrap_or(0);
+        self.successful_txs += successful;
+        self.latency_sum += weighted("avg_latency_ms");
+        self.p95_sum += weighted("p95_latency_ms");
+        self.p99_sum += weighted("p99_latency_ms");
+    }
+
+    fn finish(self, region: String) -> RegionMetrics {
+        let per_success = |sum: f64| {
+            if self.successful_txs > 0 {
+                sum / self.successful_txs as f64
+            } else {
+                0.0
+            }
+        };
+        RegionMetrics {
+            region,
+            workers: self.workers,
+            total_txs: self.total_txs,
+            successful_txs: self.successful_txs,
+            success_rate: if self.total_txs > 0 {
+                self.successful_txs as f64 / self.total_txs as f64
+            } else {
+                0.0
+            },
+            avg_latency_ms: per_success(self.latency_sum),
+            p95_latency_ms: per_success(self.p95_sum),
+            p99_latency_ms: per_success(self.p99_sum),
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use clap::{Args, ValueEnum};
+use starknet::core::types::Felt;
+use starknet::core::utils::{cairo_short_string_to_felt, parse_cairo_short_string};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider};
+
+use crate::types::PaymasterInfo;
+use crate::TestError;
+
+const STRK_TOKEN: &str = "0x04718f5a0fc34cc1af16a1cdee98ffb20c31f5cd61d6ab07201858f4287c938d";
+
+// Highest TPS allowed against mainnet without --no-safety-limits, every transaction costs real fees
+const MAINNET_MAX_TPS: u32 = 10;
+
+// Endpoints of live paymasters, matched as substrings of the URL on top of
+// --production-endpoint
+const PRODUCTION_ENDPOINTS: [&str; 2] = ["starknet.paymaster.avnu.fi", "mainnet"];
+
+pub const PRODUCTION_MAX_TPS: u32 = 50;
+pub const PRODUCTION_MAX_TRANSACTIONS: u64 = 10_000;
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Network {
+    Sepolia,
+    Mainnet,
+    /// Appchains and devnets, token and chain id come from --gas-token and --chain-id
+    Custom,
+}
+
+#[derive(Args, Clone)]
+pub struct NetworkArgs {
+    /// Starknet network the paymaster runs on, sets the default gas token and chain id
+    #[arg(long, value_enum, default_value = "sepolia")]
+    pub network: Network,
+
+    /// Token used for fees and transfers, overrides the network's default
+    #[arg(long, required_if_eq("network", "custom"))]
+    pub gas_token: Option<String>,
+
+    /// Expected chain id as a short string (e.g. SN_SEPOLIA), overrides the network's default
+    #[arg(long)]
+    pub chain_id: Option<String>,
+
+    /// Lift the network's TPS cap
+    #[arg(long)]
+    pub no_safety_limits: bool,
+
+    /// Required to run against production: mainnet, or an endpoint matching a known live
+    /// paymaster or --production-endpoint
+    #[arg(long)]
+    pub i_know_this_is_production: bool,
+
+    /// Additional substring of endpoint URLs that are production, can be repeated
+    #[arg(long)]
+    pub production_endpoint: Vec<String>,
+
+    /// Hard ceilings of a production run, --no-safety-limits doesn't lift them
+    #[arg(long, default_value_t = PRODUCTION_MAX_TPS)]
+    pub production_max_tps: u32,
+
+    /// Most transactions a production run may send in total
+    #[arg(long, default_value_t = PRODUCTION_MAX_TRANSACTIONS)]
+    pub production_max_transactions: u64,
+}
+
+// Settings of the network under test once presets and overrides were applied
+pub struct NetworkConfig {
+    pub gas_token: Felt,
+    pub chain_id: Option<String>,
+    pub max_tps: Option<u32>,
+}
+
+impl NetworkArgs {
+    pub fn resolve(&self) -> Result<NetworkConfig, TestError> {
+        let (token, chain_id, max_tps) = match self.network {
+            Network::Sepolia => (Some(STRK_TOKEN), Some("SN_SEPOLIA"), None),
+            Network::Mainnet => (Some(STRK_TOKEN), Some("SN_MAIN"), Some(MAINNET_MAX_TPS)),
+            Network::Custom => (None, None, None),
+        };
+        let token = self
+            .gas_token
+            .as_deref()
+            .or(token)
+            .ok_or("--gas-token is required for a custom network")?;
+        Ok(NetworkConfig {
+            gas_token: Felt::from_hex(token)?,
+            chain_id: self.chain_id.clone().or(chain_id.map(String::from)),
+            max_tps: max_tps.filter(|_| !self.no_safety_limits),
+        })
+    }
+
+    // Why a run against these endpoints is a production run, None when it isn't
+    pub fn production_reason(&self, endpoints: &[&str]) -> Option<String> {
+        let matched = endpoints.iter().find(|endpoint| {
+            let endpoint = endpoint.to_lowercase();
+            PRODUCTION_ENDPOINTS
+                .iter()
+                .copied()
+                .chain(self.production_endpoint.iter().map(String::as_str))
+                .any(|pattern| endpoint.contains(&pattern.to_lowercase()))
+        });
+        match (self.network, matched) {
+            (_, Some(endpoint)) => Some(format!("{} looks like a production paymaster", endpoint)),
+            (Network::Mainnet, None) => Some("the network is mainnet".to_string()),
+            _ => None,
+        }
+    }
+
+    // Stops a fat-fingered run against a live paymaster: production needs the explicit
+    // confirmation and stays within the production ceilings. Returns the cap on the
+    // number of transactions the run must keep to
+    pub fn guard_production(
+        &self,
+        endpoints: &[&str],
+        peak_tps: u32,
+        max_transactions: Option<u64>,
+    ) -> Result<Option<u64>, TestError> {
+        let Some(reason) = self.production_reason(endpoints) else {
+            return Ok(max_transactions);
+        };
+        if !self.i_know_this_is_production {
+            return Err(format!(
+                "{}, pass --i-know-this-is-production to run against it",
+                reason
+            )
+            .into());
+        }
+        if peak_tps > self.production_max_tps {
+            return Err(format!(
+                "{} TPS is above the production ceiling of {}, raise --production-max-tps to allow it",
+                peak_tps, self.production_max_tps
+            )
+            .into());
+        }
+        println!(
+            "Running against production: {}, capped at {} TPS and {} transactions",
+            reason, self.production_max_tps, self.production_max_transactions
+        );
+        Ok(Some(
+            max_transactions.map_or(self.production_max_transactions, |max| {
+                max.min(self.production_max_transactions)
+            }),
+        ))
+    }
+
+    // Same for commands sending a known number of transactions, which can't stop halfway
+    // at the cap and are refused up front when they would go over it
+    pub fn guard_production_total(
+        &self,
+        endpoint: &str,
+        peak_tps: u32,
+        transactions: u64,
+    ) -> Result<(), TestError> {
+        match self.guard_production(&[endpoint], peak_tps, None)? {
+            Some(cap) if transactions > cap => Err(format!(
+                "{} transactions are above the production ceiling of {}, raise --production-max-transactions to allow it",
+                transactions, cap
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn check_max_tps(&self, max_tps: u32) -> Result<(), TestError> {
+        match self.max_tps {
+            Some(limit) if max_tps > limit => Err(format!(
+                "--max-tps {} is above this network's limit of {}, pass --no-safety-limits to override",
+                max_tps, limit
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    // The paymaster API doesn't report its chain, a paymaster serving another network
+    // shows up as not supporting our gas token
+    pub fn check_paymaster(&self, paymaster: &PaymasterInfo) -> Result<(), TestError> {
+        if !paymaster.supported_tokens.contains(&self.gas_token) {
+            return Err(format!(
+                "paymaster does not accept {:#x} as gas token, is it serving this network?",
+                self.gas_token
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    pub async fn check_chain(
+        &self,
+        provider: &JsonRpcClient<HttpTransport>,
+    ) -> Result<(), TestError> {
+        let Some(expected) = &self.chain_id else {
+            return Ok(());
+        };
+        let actual = provider.chain_id().await?;
+        if actual != cairo_short_string_to_felt(expected)? {
+            return Err(format!(
+                "RPC node is on chain {}, expected {}",
+                parse_cairo_short_string(&actual).unwrap_or_else(|_| format!("{:#x}", actual)),
+                expected
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(network: Network, confirmed: bool) -> NetworkArgs {
+        NetworkArgs {
+            network,
+            gas_token: None,
+            chain_id: None,
+            no_safety_limits: false,
+            i_know_this_is_production: confirmed,
+            production_endpoint: vec!["live.example".to_string()],
+            production_max_tps: 5,
+            production_max_transactions: 100,
+        }
+    }
+
+    #[test]
+    fn fixed_totals_are_only_capped_in_production() {
+        let endpoint = "http://localhost:12777";
+        assert!(args(Network::Sepolia, false)
+            .guard_production_total(endpoint, 50, 10_000)
+            .is_ok());
+        let live = "https://LIVE.example/rpc";
+        assert!(args(Network::Sepolia, false)
+            .guard_production_total(live, 1, 1)
+            .is_err());
+        assert!(args(Network::Mainnet, true)
+            .guard_production_total(endpoint, 1, 100)
+            .is_ok());
+        assert!(args(Network::Mainnet, true)
+            .guard_production_total(endpoint, 1, 101)
+            .is_err());
+        assert!(args(Network::Mainnet, true)
+            .guard_production_total(endpoint, 6, 1)
+            .is_err());
+    }
+}
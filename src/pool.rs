@@ -0,0 +1,451 @@
+use clap::Args;
+use starknet::core::types::{BlockId, BlockTag, Felt};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+use crate::account::Account;
+use crate::types::{QuarantineEvent, ShardAssignment, TransactionError};
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct PoolArgs {
+    /// JSON file with the sending accounts, used instead of the single env account
+    #[arg(long)]
+    pub accounts: Option<PathBuf>,
+
+    /// Consecutive nonce errors after which an account is taken out of rotation
+    #[arg(long, default_value = "3", value_parser = clap::value_parser!(u32).range(1..))]
+    pub quarantine_after: u32,
+
+    /// Minimum time a quarantined account stays out of rotation
+    #[arg(long, default_value = "10")]
+    pub quarantine_secs: u64,
+
+    /// Only send from shard `i/n` (1-based) of the accounts, so workers sharing an accounts
+    /// file never use the same account. Accounts are sharded in address order, the order
+    /// in the file doesn't matter
+    #[arg(long, value_parser = parse_shard, requires = "accounts")]
+    pub shard: Option<(u32, u32)>,
+
+    /// File keeping the last known nonce of every account between runs. The paymaster
+    /// picks the nonces, so they can't be reused, but a run waits for accounts whose
+    /// on-chain nonce is still behind the cached one, i.e. whose transactions of the
+    /// previous run are still landing, instead of starting with a burst of nonce errors
+    #[arg(long, requires = "rpc_url")]
+    pub nonce_cache: Option<PathBuf>,
+
+    /// Most transactions per second any one account sends, whatever the size of the pool,
+    /// e.g. the per-user fair-use limit of the paymaster. Ticks finding every account at
+    /// its limit are skipped, so a step tops out at accounts x this rate
+    #[arg(long, value_parser = parse_account_tps)]
+    pub max_account_tps: Option<f64>,
+}
+
+fn parse_account_tps(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(tps) if tps > 0.0 && tps.is_finite() => Ok(tps),
+        _ => Err(format!("expected a positive rate, got `{}`", raw)),
+    }
+}
+
+// Why no account could be checked out
+#[derive(Clone, Copy, PartialEq)]
+pub enum Unavailable {
+    Quarantined,
+    // Every account not quarantined sent within its --max-account-tps interval
+    Throttled,
+}
+
+// Longest wait for the transactions of the previous run to land
+const NONCE_SYNC_TIMEOUT: Duration = Duration::from_secs(120);
+
+fn parse_shard(raw: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("expected `i/n` with 1 <= i <= n, got `{}`", raw);
+    let (index, count) = raw.split_once('/').ok_or_else(invalid)?;
+    let index: u32 = index.trim().parse().map_err(|_| invalid())?;
+    let count: u32 = count.trim().parse().map_err(|_| invalid())?;
+    if index == 0 || index > count {
+        return Err(invalid());
+    }
+    Ok((index, count))
+}
+
+// Accounts of shard `index` out of `count`, every account lands in exactly one shard
+pub fn shard_accounts(
+    mut accounts: Vec<Account>,
+    (index, count): (u32, u32),
+) -> Result<(Vec<Account>, ShardAssignment), TestError> {
+    accounts.sort_by_key(|account| account.address);
+    let shard: Vec<Account> = accounts
+        .into_iter()
+        .enumerate()
+        .filter(|(position, _)| *position as u32 % count == index - 1)
+        .map(|(_, account)| account)
+        .collect();
+    if shard.is_empty() {
+        return Err(format!("shard {}/{} has no accounts", index, count).into());
+    }
+    let assignment = ShardAssignment {
+        index,
+        count,
+        accounts: shard.iter().map(|account| account.address).collect(),
+    };
+    Ok((shard, assignment))
+}
+
+struct PoolState {
+    next: usize,
+    nonce_errors: Vec<u32>,
+    quarantined: Vec<bool>,
+    events: Vec<QuarantineEvent>,
+    // Last checkout of every account, only kept with a per-account rate limit
+    last_sent: Vec<Option<Instant>>,
+}
+
+// Round-robin pool of sending accounts
+// An account that keeps hitting nonce errors is quarantined so it doesn't drag the
+// success rate down for the rest of the run, and comes back once its on-chain nonce
+// settled (or after the quarantine period when no RPC is configured)
+pub struct AccountPool {
+    accounts: Vec<Account>,
+    quarantine_after: u32,
+    quarantine_for: Duration,
+    max_account_tps: Option<f64>,
+    provider: Option<Arc<JsonRpcClient<HttpTransport>>>,
+    nonce_cache: Option<PathBuf>,
+    start: Instant,
+    state: Mutex<PoolState>,
+}
+
+impl AccountPool {
+    pub fn new(
+        accounts: Vec<Account>,
+        args: &PoolArgs,
+        provider: Option<Arc<JsonRpcClient<HttpTransport>>>,
+    ) -> AccountPool {
+        let size = accounts.len();
+        AccountPool {
+            accounts,
+            quarantine_after: args.quarantine_after,
+            quarantine_for: Duration::from_secs(args.quarantine_secs),
+            max_account_tps: args.max_account_tps,
+            provider,
+            nonce_cache: args.nonce_cache.clone(),
+            start: Instant::now(),
+            state: Mutex::new(PoolState {
+                next: 0,
+                nonce_errors: vec![0; size],
+                quarantined: vec![false; size],
+                events: Vec::new(),
+                last_sent: vec![None; size],
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.accounts.len()
+    }
+
+    pub fn get(&self, index: usize) -> &Account {
+        &self.accounts[index]
+    }
+
+    pub fn provider(&self) -> Option<&Arc<JsonRpcClient<HttpTransport>>> {
+        self.provider.as_ref()
+    }
+
+    pub fn max_account_tps(&self) -> Option<f64> {
+        self.max_account_tps
+    }
+
+    // Next account in rotation, skipping quarantined ones and with --max-account-tps
+    // the ones that sent less than an interval of that rate ago
+    pub fn checkout(&self) -> Result<usize, Unavailable> {
+        let interval = self
+            .max_account_tps
+            .map(|tps| Duration::from_secs_f64(1.0 / tps));
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        let mut throttled = false;
+        for _ in 0..self.accounts.len() {
+            let index = state.next;
+            state.next = (state.next + 1) % self.accounts.len();
+            if state.quarantined[index] {
+                continue;
+            }
+            let Some(interval) = interval else {
+                return Ok(index);
+            };
+            if state.last_sent[index].is_some_and(|last| now < last + interval) {
+                throttled = true;
+                continue;
+            }
+            state.last_sent[index] = Some(now);
+            return Ok(index);
+        }
+        Err(if throttled {
+            Unavailable::Throttled
+        } else {
+            Unavailable::Quarantined
+        })
+    }
+
+    // Hands back an account checked out for a transaction that was never sent. Its last
+    // send before the checkout was already an interval ago, so forgetting it is the same
+    // as restoring it
+    pub fn checkin(&self, index: usize) {
+        self.state.lock().unwrap().last_sent[index] = None;
+    }
+
+    pub fn record(self: &Arc<Self>, index: usize, result: &Result<f64, TransactionError>) {
+        let mut state = self.state.lock().unwrap();
+        match result {
+            Err(TransactionError::Nonce) => state.nonce_errors[index] += 1,
+            _ => state.nonce_errors[index] = 0,
+        }
+        if state.quarantined[index] || state.nonce_errors[index] < self.quarantine_after {
+            return;
+        }
+
+        state.quarantined[index] = true;
+        let event = state.events.len();
+        state.events.push(QuarantineEvent {
+            address: self.accounts[index].address,
+            quarantined_at_secs: self.start.elapsed().as_secs_f64(),
+            recovered_at_secs: None,
+            chain_nonce: None,
+        });
+        tokio::spawn(Arc::clone(self).recover(index, event));
+    }
+
+    pub fn events(&self) -> Vec<QuarantineEvent> {
+        self.state.lock().unwrap().events.clone()
+    }
+
+    // Waits until every account caught up with its nonce of the previous run, see
+    // --nonce-cache. Accounts still behind after the timeout, or whose nonce couldn't be
+    // queried until then, are sent from anyway
+    pub async fn sync_nonces(&self) -> Result<(), TestError> {
+        let (Some(path), Some(provider)) = (&self.nonce_cache, &self.provider) else {
+            return Ok(());
+        };
+        let cached = read_nonce_cache(path)?;
+        let deadline = Instant::now() + NONCE_SYNC_TIMEOUT;
+        for account in &self.accounts {
+            let Some(&expected) = cached.get(&format!("{:#x}", account.address)) else {
+                continue;
+            };
+            loop {
+                let nonce = provider
+                    .get_nonce(BlockId::Tag(BlockTag::Pending), account.address)
+                    .await;
+                match nonce {
+                    Ok(nonce) if nonce >= expected => break,
+                    Ok(nonce) if Instant::now() >= deadline => {
+                        println!(
+                            "{:#x} is still at nonce {:#x} instead of {:#x}, expect nonce errors",
+                            account.address, nonce, expected
+                        );
+                        break;
+                    }
+                    Ok(nonce) => println!(
+                        "Waiting for {:#x} to reach nonce {:#x} (at {:#x})",
+                        account.address, expected, nonce
+                    ),
+                    Err(e) if Instant::now() >= deadline => {
+                        eprintln!(
+                            "Failed to query nonce of {:#x}, expect nonce errors: {}",
+                            account.address, e
+                        );
+                        break;
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to query nonce of {:#x}: {}", account.address, e)
+                    }
+                }
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+        Ok(())
+    }
+
+    // Stores the current nonce of every account in --nonce-cache, next to the ones of
+    // accounts this run didn't use
+    pub async fn save_nonces(&self) -> Result<(), TestError> {
+        let (Some(path), Some(provider)) = (&self.nonce_cache, &self.provider) else {
+            return Ok(());
+        };
+        let mut cached = read_nonce_cache(path)?;
+        for account in &self.accounts {
+            let nonce = provider
+                .get_nonce(BlockId::Tag(BlockTag::Pending), account.address)
+                .await?;
+            cached.insert(format!("{:#x}", account.address), nonce);
+        }
+        fs::write(path, serde_json::to_string_pretty(&cached)?)?;
+        Ok(())
+    }
+
+    async fn recover(self: Arc<Self>, index: usize, event: usize) {
+        sleep(self.quarantine_for).await;
+
+        // Wait until the on-chain nonce stops moving, i.e. whatever was still pending
+        // for this account landed and the paymaster will build against a settled nonce
+        let mut chain_nonce = None;
+        if let Some(provider) = &self.provider {
+            let address = self.accounts[index].address;
+            let mut last: Option<Felt> = None;
+            loop {
+                match provider
+                    .get_nonce(BlockId::Tag(BlockTag::Pending), address)
+                    .await
+                {
+                    Ok(nonce) if last == Some(nonce) => {
+                        chain_nonce = Some(nonce);
+                        break;
+                    }
+                    Ok(nonce) => last = Some(nonce),
+                    Err(e) => {
+                        eprintln!("Failed to query nonce of {:#x}: {}", address, e);
+                        break;
+                    }
+                }
+                sleep(Duration::from_secs(1)).await;
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        state.quarantined[index] = false;
+        state.nonce_errors[index] = 0;
+        state.events[event].recovered_at_secs = Some(self.start.elapsed().as_secs_f64());
+        state.events[event].chain_nonce = chain_nonce;
+    }
+}
+
+// Nonces by account address, empty until a run wrote the file
+fn read_nonce_cache(path: &Path) -> Result<BTreeMap<String, Felt>, TestError> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    serde_json::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| format!("invalid nonce cache {}: {}", path.display(), e).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::LocalSigner;
+    use starknet::signers::SigningKey;
+
+    fn accounts(addresses: &[u64]) -> Vec<Account> {
+        let signer = Arc::new(LocalSigner(SigningKey::from_secret_scalar(Felt::ONE)));
+        addresses
+            .iter()
+            .map(|&address| Account {
+                address: Felt::from(address),
+                signer: signer.clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shards_are_parsed_as_one_based_fractions() {
+        assert_eq!(parse_shard("1/3"), Ok((1, 3)));
+        assert_eq!(parse_shard(" 3 / 3 "), Ok((3, 3)));
+        assert!(parse_shard("0/3").is_err());
+        assert!(parse_shard("4/3").is_err());
+        assert!(parse_shard("1").is_err());
+        assert!(parse_shard("a/b").is_err());
+    }
+
+    #[test]
+    fn every_account_lands_in_exactly_one_shard() {
+        let mut seen = Vec::new();
+        for index in 1..=3 {
+            let (shard, assignment) =
+                shard_accounts(accounts(&[7, 3, 5, 1, 4, 2, 6]), (index, 3)).unwrap();
+            assert_eq!(assignment.index, index);
+            assert_eq!(assignment.count, 3);
+            let addresses: Vec<Felt> = shard.iter().map(|account| account.address).collect();
+            assert_eq!(addresses, assignment.accounts);
+            seen.extend(addresses);
+        }
+        seen.sort();
+        assert_eq!(seen, (1..=7).map(Felt::from).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn shards_ignore_the_order_of_the_file() {
+        let (a, _) = shard_accounts(accounts(&[1, 2, 3, 4]), (2, 2)).unwrap();
+        let (b, _) = shard_accounts(accounts(&[4, 3, 2, 1]), (2, 2)).unwrap();
+        let a: Vec<Felt> = a.iter().map(|account| account.address).collect();
+        let b: Vec<Felt> = b.iter().map(|account| account.address).collect();
+        assert_eq!(a, vec![Felt::from(2u64), Felt::from(4u64)]);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn empty_shards_are_an_error() {
+        assert!(shard_accounts(accounts(&[1, 2]), (3, 3)).is_err());
+    }
+
+    fn pool(size: u64, max_account_tps: Option<f64>) -> AccountPool {
+        let args = PoolArgs {
+            accounts: None,
+            quarantine_after: 3,
+            quarantine_secs: 10,
+            shard: None,
+            nonce_cache: None,
+            max_account_tps,
+        };
+        AccountPool::new(accounts(&(1..=size).collect::<Vec<_>>()), &args, None)
+    }
+
+    #[test]
+    fn checkout_rotates_through_the_accounts() {
+        let pool = pool(3, None);
+        let indices: Vec<usize> = (0..7).map(|_| pool.checkout().ok().unwrap()).collect();
+        assert_eq!(indices, vec![0, 1, 2, 0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn accounts_at_their_rate_are_throttled() {
+        let pool = pool(2, Some(0.001));
+        assert!(matches!(pool.checkout(), Ok(0)));
+        assert!(matches!(pool.checkout(), Ok(1)));
+        assert!(matches!(pool.checkout(), Err(Unavailable::Throttled)));
+    }
+
+    #[test]
+    fn accounts_come_back_after_their_interval() {
+        let pool = pool(1, Some(50.0));
+        assert!(matches!(pool.checkout(), Ok(0)));
+        assert!(matches!(pool.checkout(), Err(Unavailable::Throttled)));
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(matches!(pool.checkout(), Ok(0)));
+    }
+
+    #[test]
+    fn checked_in_accounts_can_send_again() {
+        let pool = pool(1, Some(0.001));
+        assert!(matches!(pool.checkout(), Ok(0)));
+        pool.checkin(0);
+        assert!(matches!(pool.checkout(), Ok(0)));
+    }
+
+    #[test]
+    fn quarantined_accounts_are_not_throttled() {
+        let pool = pool(2, Some(0.001));
+        pool.state.lock().unwrap().quarantined = vec![true, true];
+        assert!(matches!(pool.checkout(), Err(Unavailable::Quarantined)));
+        pool.state.lock().unwrap().quarantined[1] = false;
+        assert!(matches!(pool.checkout(), Ok(1)));
+        assert!(matches!(pool.checkout(), Err(Unavailable::Throttled)));
+    }
+}
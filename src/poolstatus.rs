@@ -0,0 +1,185 @@
+use clap::{Args, Subcommand};
+use starknet::core::types::{BlockId, BlockTag, Felt, FunctionCall};
+use starknet::macros::selector;
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider, Url};
+use std::path::PathBuf;
+
+use crate::account::{load_accounts, parse_felt, verify_account, Account};
+use crate::network::NetworkArgs;
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Subcommand)]
+pub enum AccountsCommand {
+    /// Deployment, signer, nonce and balances of every account of a pool file, accounts
+    /// that would fail their transactions are flagged
+    Status(StatusArgs),
+}
+
+#[derive(Args)]
+pub struct StatusArgs {
+    /// JSON file of `{address, private_key}` entries, the --accounts of a run
+    pub pool_file: PathBuf,
+
+    /// Starknet RPC node the nonces and balances are read from
+    #[arg(long)]
+    pub rpc_url: String,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    /// Also show the balance in this token, can be repeated. The network's gas token is
+    /// always shown
+    #[arg(long, value_parser = parse_felt)]
+    pub token: Vec<Felt>,
+
+    /// Balances below this many base units of the gas token mark an account as degraded
+    #[arg(long, default_value = "1")]
+    pub min_balance: u128,
+
+    /// Write the report to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+pub async fn pool_status(args: &StatusArgs) -> Result<PoolStatusReport, TestError> {
+    let network = args.network.resolve()?;
+    let provider = JsonRpcClient::new(HttpTransport::new(Url::parse(&args.rpc_url)?));
+    network.check_chain(&provider).await?;
+    let accounts = load_accounts(&args.pool_file)?;
+    let mut tokens = vec![network.gas_token];
+    tokens.extend(args.token.iter().filter(|t| **t != network.gas_token));
+
+    print!(
+        "{:<66} {:>8} {:>8} {:>8}",
+        "account", "deployed", "signer", "nonce"
+    );
+    for token in &tokens {
+        // Enough of the address to tell the tokens apart
+        let hex = format!("{:#x}", token);
+        print!(" {:>24}", &hex[..hex.len().min(10)]);
+    }
+    println!("  status");
+
+    let mut statuses = Vec::new();
+    for account in &accounts {
+        let status = account_status(&provider, account, &tokens, args.min_balance).await;
+        print!(
+            "{:<66} {:>8} {:>8} {:>8}",
+            format!("{:#x}", status.address),
+            if status.deployed { "yes" } else { "no" },
+            match status.signer_matches {
+                Some(true) => "ok",
+                Some(false) => "wrong",
+                None => "-",
+            },
+            status
+                .nonce
+                .map(|nonce| nonce.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        );
+        for balance in &status.balances {
+            print!(
+                " {:>24}",
+                balance
+                    .balance
+                    .map(|balance| balance.to_string())
+                    .unwrap_or_else(|| "-".to_string())
+            );
+        }
+        println!(
+            "  {}",
+            if status.problems.is_empty() {
+                "ok".to_string()
+            } else {
+                status.problems.join(", ")
+            }
+        );
+        statuses.push(status);
+    }
+
+    let degraded = statuses.iter().filter(|s| !s.problems.is_empty()).count() as u32;
+    println!("\n{} of {} account(s) degraded", degraded, statuses.len());
+    Ok(PoolStatusReport {
+        pool_file: args.pool_file.clone(),
+        degraded,
+        accounts: statuses,
+    })
+}
+
+async fn account_status(
+    provider: &JsonRpcClient<HttpTransport>,
+    account: &Account,
+    tokens: &[Felt],
+    min_balance: u128,
+) -> AccountStatus {
+    let block = BlockId::Tag(BlockTag::Pending);
+    let mut problems = Vec::new();
+    let deployed = provider
+        .get_class_hash_at(block, account.address)
+        .await
+        .is_ok();
+    // Undeployed accounts have no signer to ask
+    let signer_matches = if deployed {
+        Some(verify_account(provider, account).await.is_ok())
+    } else {
+        problems.push("not deployed".to_string());
+        None
+    };
+    if signer_matches == Some(false) {
+        problems.push("signer is not the pool's key".to_string());
+    }
+    let nonce = match provider.get_nonce(block, account.address).await {
+        Ok(nonce) => u64::try_from(nonce).ok(),
+        Err(_) => None,
+    };
+
+    let mut balances = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let balance = balance_of(provider, *token, account.address).await;
+        match balance {
+            // Only the gas token pays for the transactions
+            Some(balance) if i == 0 && balance < min_balance => {
+                problems.push("low gas token balance".to_string())
+            }
+            None => problems.push(format!("no balance of {:#x}", token)),
+            _ => {}
+        }
+        balances.push(TokenBalance {
+            token: *token,
+            balance,
+        });
+    }
+    AccountStatus {
+        address: account.address,
+        deployed,
+        signer_matches,
+        nonce,
+        balances,
+        problems,
+    }
+}
+
+// ERC-20 balance as a u256, capped to a u128 which no test balance gets near. Older
+// tokens only know the camel case entry point
+async fn balance_of(
+    provider: &JsonRpcClient<HttpTransport>,
+    token: Felt,
+    owner: Felt,
+) -> Option<u128> {
+    for entry_point_selector in [selector!("balance_of"), selector!("balanceOf")] {
+        let call = FunctionCall {
+            contract_address: token,
+            entry_point_selector,
+            calldata: vec![owner],
+        };
+        let Ok(result) = provider.call(call, BlockId::Tag(BlockTag::Pending)).await else {
+            continue;
+        };
+        let low = u128::try_from(*result.first()?).ok()?;
+        let high = result.get(1).copied().unwrap_or(Felt::ZERO);
+        return Some(if high == Felt::ZERO { low } else { u128::MAX });
+    }
+    None
+}
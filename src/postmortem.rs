@@ -0,0 +1,229 @@
+use flate2::write::DeflateEncoder;
+use flate2::{Compression, Crc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use crate::redact::redact;
+use crate::report;
+use crate::types::*;
+use crate::TestError;
+
+// Failing traces kept in the bundle, enough to see the patterns without a huge attachment
+const MAX_TRACES: usize = 50;
+
+// What the load generator itself went through, to rule it out before blaming the paymaster
+#[derive(Serialize)]
+struct Diagnostics<'a> {
+    version: &'static str,
+    stop_reason: &'a StopReason,
+    connection: &'a Option<ConnectionReport>,
+    websocket: &'a Option<WebSocketReport>,
+    failover: &'a Option<FailoverReport>,
+    health: &'a Option<HealthReport>,
+    long_tail: &'a [LongTailEvent],
+    steps: Vec<StepDiagnostics<'a>>,
+}
+
+#[derive(Serialize)]
+struct StepDiagnostics<'a> {
+    step: usize,
+    target_tps: u32,
+    offered_tps: f64,
+    schedule_avg_lag_ms: f64,
+    schedule_max_lag_ms: f64,
+    drain_time_ms: f64,
+    resources: &'a ResourceUsage,
+}
+
+// Zip of everything an incident ticket needs about a run that failed its manifest:
+// the results, the failing transactions of the --trace-sample, the generator's own
+// diagnostics and the summary as printed. With --redact the JSON files are redacted
+// together, a value gets the same pseudonym in all of them
+pub fn write_bundle(
+    path: &Path,
+    results: &StressTestResults,
+    redacted: bool,
+) -> Result<(), TestError> {
+    let traces: Vec<&TransactionTrace> = results
+        .results
+        .iter()
+        .flat_map(|result| &result.traces)
+        .filter(|trace| trace.error.is_some())
+        .take(MAX_TRACES)
+        .collect();
+    let diagnostics = Diagnostics {
+        version: env!("CARGO_PKG_VERSION"),
+        stop_reason: &results.stop_reason,
+        connection: &results.connection,
+        websocket: &results.websocket,
+        failover: &results.failover,
+        health: &results.health,
+        long_tail: &results.long_tail,
+        steps: results
+            .results
+            .iter()
+            .enumerate()
+            .map(|(i, result)| StepDiagnostics {
+                step: i + 1,
+                target_tps: result.metrics.target_tps,
+                offered_tps: result.metrics.offered_tps,
+                schedule_avg_lag_ms: result.metrics.schedule_avg_lag_ms,
+                schedule_max_lag_ms: result.metrics.schedule_max_lag_ms,
+                drain_time_ms: result.drain_time_ms,
+                resources: &result.resources,
+            })
+            .collect(),
+    };
+
+    let mut summary = report::summary(results)?;
+    if let Some(acceptance) = &results.acceptance {
+        writeln!(summary, "\nmanifest `{}`:", acceptance.environment)?;
+        for expectation in &acceptance.expectations {
+            writeln!(
+                summary,
+                "  {} TPS: measured {} TPS, {}",
+                expectation.tps,
+                expectation.measured_tps,
+                if expectation.passed { "met" } else { "missed" }
+            )?;
+        }
+    }
+    if traces.is_empty() {
+        writeln!(
+            summary,
+            "\nno failing traces, sample some with --trace-sample"
+        )?;
+    }
+
+    let entries = json!({
+        "results.json": results,
+        "failing-traces.json": traces,
+        "diagnostics.json": diagnostics,
+    });
+    let mut zip = Zip::default();
+    for (name, content) in json_files(entries, redacted)? {
+        zip.add(&name, &content)?;
+    }
+    zip.add("summary.txt", summary.as_bytes())?;
+    fs::write(path, zip.finish())?;
+    Ok(())
+}
+
+// Files of the bundle keyed by name, pretty printed
+fn json_files(mut entries: Value, redacted: bool) -> Result<Vec<(String, Vec<u8>)>, TestError> {
+    if redacted {
+        redact(&mut entries);
+    }
+    let Value::Object(entries) = entries else {
+        return Ok(Vec::new());
+    };
+    entries
+        .into_iter()
+        .map(|(name, entry)| Ok((name, serde_json::to_vec_pretty(&entry)?)))
+        .collect()
+}
+
+// Just enough of the zip format for a handful of deflated files, archive tools and ticket
+// systems open it without anything installed. Entries are dated 1980-01-01, the earliest
+// date zip can hold, the run's own times are in the files
+#[derive(Default)]
+struct Zip {
+    data: Vec<u8>,
+    directory: Vec<u8>,
+    entries: u16,
+}
+
+impl Zip {
+    fn add(&mut self, name: &str, content: &[u8]) -> Result<(), TestError> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content)?;
+        let compressed = encoder.finish()?;
+        let mut crc = Crc::new();
+        crc.update(content);
+        let offset = self.data.len() as u32;
+
+        // Version needed, flags, method (deflate), time, date, CRC and sizes, shared by the
+        // local header and the central directory entry
+        let mut common = Vec::new();
+        common.extend(20u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(8u16.to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+        common.extend(0x21u16.to_le_bytes());
+        common.extend(crc.sum().to_le_bytes());
+        common.extend((compressed.len() as u32).to_le_bytes());
+        common.extend((content.len() as u32).to_le_bytes());
+        common.extend((name.len() as u16).to_le_bytes());
+        common.extend(0u16.to_le_bytes());
+
+        self.data.extend(0x04034b50u32.to_le_bytes());
+        self.data.extend(&common);
+        self.data.extend(name.as_bytes());
+        self.data.extend(&compressed);
+
+        self.directory.extend(0x02014b50u32.to_le_bytes());
+        self.directory.extend(20u16.to_le_bytes());
+        self.directory.extend(&common);
+        // Comment length, disk, internal and external attributes
+        self.directory.extend([0u8; 10]);
+        self.directory.extend(offset.to_le_bytes());
+        self.directory.extend(name.as_bytes());
+        self.entries += 1;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let offset = self.data.len() as u32;
+        let size = self.directory.len() as u32;
+        self.data.append(&mut self.directory);
+        self.data.extend(0x06054b50u32.to_le_bytes());
+        self.data.extend([0u8; 4]);
+        self.data.extend(self.entries.to_le_bytes());
+        self.data.extend(self.entries.to_le_bytes());
+        self.data.extend(size.to_le_bytes());
+        self.data.extend(offset.to_le_bytes());
+        self.data.extend(0u16.to_le_bytes());
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(files: &[(String, Vec<u8>)], name: &str) -> Value {
+        let (_, content) = files.iter().find(|(file, _)| file == name).unwrap();
+        serde_json::from_slice(content).unwrap()
+    }
+
+    #[test]
+    fn bundles_are_redacted_like_the_results() {
+        let entries = json!({
+            "results.json": {"results": [{"account": "0xabc", "transaction_hash": "0x123"}]},
+            "failing-traces.json": [{
+                "account": "0x0abc",
+                "legs": [{"execute_request": {"calldata": ["0x1", "0x2"], "signature": ["0x3"]}}],
+            }],
+        });
+        let files = json_files(entries, true).unwrap();
+        let results = file(&files, "results.json");
+        let traces = file(&files, "failing-traces.json");
+        assert_eq!(results["results"][0]["account"], "account-1");
+        assert_eq!(results["results"][0]["transaction_hash"], "tx-1");
+        assert_eq!(traces[0]["account"], "account-1");
+        let request = &traces[0]["legs"][0]["execute_request"];
+        assert_eq!(request["calldata"], "2 felt(s) redacted");
+        assert_eq!(request["signature"], "1 felt(s) redacted");
+    }
+
+    #[test]
+    fn bundles_keep_real_values_without_redact() {
+        let entries = json!({"results.json": {"account": "0xabc"}});
+        let files = json_files(entries, false).unwrap();
+        assert_eq!(file(&files, "results.json")["account"], "0xabc");
+    }
+}
@@ -0,0 +1,166 @@
+use clap::{Args, ValueEnum};
+use paymaster_rpc::PaymasterAPIClient;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::{interval, Instant};
+
+use crate::client::{build_client, ClientArgs, PaymasterClient};
+use crate::types::{ProbeResults, ProbeStep, ProbeStop};
+use crate::TestError;
+
+#[derive(Args)]
+pub struct ProbeArgs {
+    /// Headers and bearer token select the API key the quota is discovered for
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    /// Request sent to probe the limits, one that costs the paymaster as little as possible
+    #[arg(long, value_enum, default_value = "is-available")]
+    pub method: ProbeMethod,
+
+    /// Request rate of the first step, in requests per minute
+    #[arg(long, default_value = "60")]
+    pub start_rpm: u32,
+
+    /// Rate added on every following step
+    #[arg(long, default_value = "60")]
+    pub step_rpm: u32,
+
+    /// Highest rate tried, the probe ends there if nothing pushed back
+    #[arg(long, default_value = "6000")]
+    pub max_rpm: u32,
+
+    /// Length of each step, a full minute matches how quotas are usually enforced
+    #[arg(long, default_value = "60")]
+    pub step_secs: u64,
+
+    /// Stop once at least this share of a step's requests was rate limited or rejected
+    #[arg(long, default_value = "0.5")]
+    pub stop_share: f64,
+
+    /// Write the results to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Read-only calls, probing costs no gas
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ProbeMethod {
+    IsAvailable,
+    SupportedTokens,
+}
+
+enum ProbeOutcome {
+    Accepted,
+    RateLimited,
+    Rejected,
+    Failed,
+}
+
+// Raises the request rate step by step until the paymaster pushes back with 429s or
+// rejections, and reports the rate it still accepted as the quota of the API key
+// The quota per IP is whatever the host running the probe gets, it can't be varied from here
+pub async fn probe_limits(mut args: ProbeArgs) -> Result<ProbeResults, TestError> {
+    let run_tag = args.client.tag_run();
+    let client = Arc::new(build_client(&args.client).await?);
+    let mut steps = Vec::new();
+    let mut stopped_by = ProbeStop::MaxRate;
+
+    println!("Probing {} for rate limits", args.client.endpoint);
+    let mut rpm = args.start_rpm.max(1);
+    while rpm <= args.max_rpm {
+        println!("Offering {} requests/min", rpm);
+        let step = probe_step(&client, args.method, rpm, args.step_secs).await?;
+        let pushed_back = step.rate_limited + step.rejected;
+        let limited = step.sent > 0 && pushed_back as f64 / step.sent as f64 >= args.stop_share;
+        let limiter = if step.rate_limited >= step.rejected {
+            ProbeStop::RateLimited
+        } else {
+            ProbeStop::Rejected
+        };
+        steps.push(step);
+        if limited {
+            stopped_by = limiter;
+            break;
+        }
+        rpm += args.step_rpm.max(1);
+    }
+
+    let clean = |s: &&ProbeStep| s.rate_limited == 0 && s.rejected == 0;
+    let results = ProbeResults {
+        endpoint: args.client.endpoint.clone(),
+        run_tag,
+        last_clean_rpm: steps.iter().filter(clean).map(|s| s.offered_rpm).max(),
+        // What the paymaster still let through once it started pushing back
+        quota_rpm: match stopped_by {
+            ProbeStop::MaxRate => None,
+            _ => steps.last().map(|s| s.accepted_rpm),
+        },
+        stopped_by,
+        steps,
+    };
+    if let Some(path) = &args.output {
+        fs::write(path, serde_json::to_string_pretty(&results)?)?;
+        println!("Results saved to: {}", path.display());
+    }
+    Ok(results)
+}
+
+async fn probe_step(
+    client: &Arc<PaymasterClient>,
+    method: ProbeMethod,
+    rpm: u32,
+    step_secs: u64,
+) -> Result<ProbeStep, TestError> {
+    let duration = Duration::from_secs(step_secs);
+    let mut ticker = interval(Duration::from_secs_f64(60.0 / rpm as f64));
+    let mut tasks = JoinSet::new();
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        ticker.tick().await;
+        let client = Arc::clone(client);
+        tasks.spawn(async move {
+            let result = match method {
+                ProbeMethod::IsAvailable => client.is_available().await.map(|_| ()),
+                ProbeMethod::SupportedTokens => client.get_supported_tokens().await.map(|_| ()),
+            };
+            match result {
+                Ok(()) => ProbeOutcome::Accepted,
+                Err(e) => classify_rejection(&e.to_string()),
+            }
+        });
+    }
+
+    let mut step = ProbeStep {
+        offered_rpm: rpm,
+        ..Default::default()
+    };
+    while let Some(outcome) = tasks.join_next().await {
+        step.sent += 1;
+        match outcome? {
+            ProbeOutcome::Accepted => step.accepted += 1,
+            ProbeOutcome::RateLimited => step.rate_limited += 1,
+            ProbeOutcome::Rejected => step.rejected += 1,
+            ProbeOutcome::Failed => step.failed += 1,
+        }
+    }
+    step.accepted_rpm = step.accepted as f64 * 60.0 / duration.as_secs_f64();
+    Ok(step)
+}
+
+// 429s surface as transport errors carrying the status, JSON-RPC errors are the
+// paymaster refusing the call itself
+fn classify_rejection(message: &str) -> ProbeOutcome {
+    let lower = message.to_lowercase();
+    if lower.contains("429") || lower.contains("too many requests") || lower.contains("rate limit")
+    {
+        ProbeOutcome::RateLimited
+    } else if message.contains("JSON-RPC error") || lower.contains("quota") {
+        ProbeOutcome::Rejected
+    } else {
+        ProbeOutcome::Failed
+    }
+}
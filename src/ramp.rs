@@ -0,0 +1,1937 @@
+use clap::Args;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use starknet::core::types::Felt;
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::JsonRpcClient;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinSet;
+use tokio::time::{interval, sleep, sleep_until, Instant};
+
+use crate::account::{parse_felt, Account};
+use crate::crash;
+use crate::db::ResultsDb;
+use crate::failover::Failover;
+use crate::health::{HealthMonitor, StatusMonitor, StatusPage};
+use crate::hooks::{run_hook, HookArgs};
+use crate::pool::{AccountPool, Unavailable};
+use crate::receipts::{
+    criterion_metrics, relayer_of, wait_for_receipt, Inclusion, ReceiptTracker, SuccessCriterion,
+};
+use crate::resources::{GuardArgs, GuardState, Guardrails, ResourceSampler};
+use crate::scenario::{Growth, Workload};
+use crate::selftest::Injection;
+use crate::sketch::LatencySketch;
+use crate::stats::{
+    keep_sampled, keep_slowest, BudgetHistogram, ConcurrencyProfiler, HeatmapBuilder, LinearFit,
+    SegmentTimeline, ThroughputTimeline,
+};
+use crate::statsd::Statsd;
+use crate::taxonomy::Taxonomy;
+use crate::transaction::{run_legs, ErrorDetail, Fee, Pipeline, Sent};
+use crate::txlog::TxLog;
+use crate::types::*;
+use crate::watchdog::Watchdog;
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct RampArgs {
+    /// TPS of the last step, the steps ramp up to it evenly (or all run at it with --grow)
+    #[arg(long, required_unless_present = "step_tps")]
+    pub max_tps: Option<u32>,
+
+    /// Seconds the whole ramp lasts, split evenly between the steps
+    #[arg(long, default_value = "5")]
+    pub duration: u32,
+
+    /// Number of steps of the ramp
+    #[arg(long, default_value = "5", value_parser = clap::value_parser!(u32).range(1..))]
+    pub steps: u32,
+
+    /// Target TPS of every step, e.g. `10,50,100,150`, instead of the linear ramp up to
+    /// --max-tps. Steps are equal slices of --duration unless --step-durations is given
+    #[arg(long, value_delimiter = ',', conflicts_with_all = ["max_tps", "grow", "users"])]
+    pub step_tps: Vec<u32>,
+
+    /// Length of every step of --step-tps, e.g. `30s,30s,60s,2m`
+    #[arg(long, value_delimiter = ',', value_parser = parse_duration, requires = "step_tps")]
+    pub step_durations: Vec<Duration>,
+
+    /// Pause between steps, after the previous step's in-flight requests drained
+    #[arg(long, default_value = "0")]
+    pub cooldown_secs: u64,
+
+    /// Number of slowest transactions kept per step for investigation
+    #[arg(long, default_value = "5")]
+    pub slowest_k: usize,
+
+    /// Send this many transactions back to back on each tick instead of spacing them evenly,
+    /// ticks are spread out so the average rate still matches the step's TPS
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub burst_size: u32,
+
+    /// Run every step twice at the same TPS, evenly paced and then in bursts of --burst-size,
+    /// and compare the latency distributions
+    #[arg(long, requires = "burst_size")]
+    pub burst_compare: bool,
+
+    /// Run every step once per fee mode at the same TPS: in the gas token, in each
+    /// --compare-gas-token and sponsored, and compare them against the sponsored run
+    #[arg(long, conflicts_with = "burst_compare")]
+    pub compare_fee_modes: bool,
+
+    /// Goal seeking: instead of ramping, bisect between 0 and --max-tps for the highest rate
+    /// whose p99 latency stays under this many milliseconds at the sustainable success rate.
+    /// Every step tries the middle of the remaining range, the run ends once it is narrowed
+    /// down to one TPS or after --steps steps
+    #[arg(long, conflicts_with_all = ["step_tps", "grow", "users", "burst_compare", "compare_fee_modes", "verify_peak"])]
+    pub p99_target_ms: Option<f64>,
+
+    /// Additional gas token compared by --compare-fee-modes, can be repeated
+    #[arg(long, value_parser = parse_felt, requires = "compare_fee_modes")]
+    pub compare_gas_token: Vec<Felt>,
+
+    /// Keep TPS at --max-tps and grow the payload of each transaction across steps instead,
+    /// to find the paymaster's limits on calls per transaction and calldata size
+    #[arg(long, value_enum)]
+    pub grow: Option<Growth>,
+
+    /// Payload size reached at the last step, in calls or calldata felts
+    #[arg(long, default_value = "64")]
+    pub grow_max: u32,
+
+    /// Stop the ramp at the first failing step and confirm the step before it with a
+    /// second run before reporting it as the max sustainable TPS
+    #[arg(long, conflicts_with = "grow")]
+    pub verify_peak: bool,
+
+    /// Length of the confirmation run, defaults to the step duration
+    #[arg(long, requires = "verify_peak")]
+    pub verify_secs: Option<u64>,
+
+    /// Relative error of the latency quantiles, the sketch holding them takes memory
+    /// proportional to the log of the latency range rather than to the number of transactions
+    #[arg(long, default_value = "0.01", value_parser = parse_accuracy)]
+    pub sketch_accuracy: f64,
+
+    /// Transactions built and signed at the same time, unlimited by default
+    #[arg(long)]
+    pub build_concurrency: Option<usize>,
+
+    /// Signed transactions being executed at the same time, unlimited by default
+    #[arg(long)]
+    pub execute_concurrency: Option<usize>,
+
+    /// Rebuild and retry a transaction once when its execution is rejected for a stale
+    /// nonce or expired time bounds, successes on the retry are counted as recovered
+    #[arg(long)]
+    pub retry_stale: bool,
+
+    /// Skip the remaining steps once this many steps in a row fell below the sustainable
+    /// success rate, higher rates would only burn time and fees
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub stop_after_failures: Option<u32>,
+
+    /// Run the steps in random order instead of ascending, to tell whether the paymaster's
+    /// performance at a rate depends on the rates it saw before
+    #[arg(long, conflicts_with_all = ["p99_target_ms", "grow", "users", "verify_peak", "stop_after_failures"])]
+    pub shuffle_steps: bool,
+
+    /// Seed of the --shuffle-steps order, random when not given. The one used is stored with
+    /// the results so the order can be run again
+    #[arg(long, requires = "shuffle_steps")]
+    pub shuffle_seed: Option<u64>,
+
+    /// Stop once this many requests were sent, quotes included, even if steps remain. For
+    /// when funds or the relayer quota run out before the time does
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_transactions: Option<u64>,
+
+    #[command(flatten)]
+    pub hooks: HookArgs,
+
+    #[command(flatten)]
+    pub guard: GuardArgs,
+
+    /// Collect a heatmap of send time against latency over the run, in time buckets of this
+    /// many seconds
+    #[arg(long, value_parser = parse_bucket_secs)]
+    pub heatmap_secs: Option<f64>,
+
+    /// Closed loop: ramp up to this many virtual users, each sending its next transaction
+    /// only after the previous one completed, instead of sending at a fixed rate
+    #[arg(long, conflicts_with_all = ["grow", "burst_compare", "compare_fee_modes", "verify_peak"])]
+    pub users: Option<u32>,
+
+    /// Pause of a virtual user between two transactions, e.g. `500ms..2s`, sampled uniformly
+    #[arg(long, default_value = "0ms", value_parser = parse_think_time, requires = "users")]
+    pub think_time: ThinkTime,
+
+    /// Wallet flow: pause between signing a transaction and executing it, e.g. `2s..15s`,
+    /// sampled uniformly like a user reading the fee before confirming. Quotes and typed
+    /// data going stale in the meantime show up as stale_txs
+    #[arg(long, value_parser = parse_think_time)]
+    pub confirm_delay: Option<ThinkTime>,
+
+    /// Share of the requests that only build the transaction, the fee quotes wallets ask for
+    /// far more often than they execute. Quotes are reported apart from the executed transactions
+    #[arg(long, default_value = "0", value_parser = parse_ratio)]
+    pub quote_ratio: f64,
+
+    /// Make every transaction's calldata differ by raising the transferred amounts with a
+    /// run-wide counter, so responses deduplicated or cached by the paymaster can't pass for
+    /// successes. Scenario files need a `{{counter}}` or `{{random_felt}}` instead. Hashes
+    /// returned for more than one transaction are reported as duplicates
+    #[arg(long)]
+    pub unique_calldata: bool,
+
+    /// Keep the requests, responses, timings and on-chain outcome of this many random
+    /// transactions per step in the results, as reproductions for the paymaster's developers
+    #[arg(long, default_value = "0")]
+    pub trace_sample: u32,
+
+    /// Look up the relayer that submitted this many random successful transactions per step
+    /// once the run is over, to see whether the paymaster spreads the load over its relayers.
+    /// Needs --rpc-url
+    #[arg(long, default_value = "0")]
+    pub relayer_sample: u32,
+
+    /// Summarize long runs in slices of this many minutes as well, e.g. hour by hour of a
+    /// soak, so a late degradation isn't averaged away. Runs shorter than one slice get none
+    #[arg(long, default_value = "60", value_parser = clap::value_parser!(u64).range(1..))]
+    pub segment_mins: u64,
+}
+
+#[derive(Clone, Copy)]
+pub struct ThinkTime {
+    min: Duration,
+    max: Duration,
+}
+
+fn parse_think_time(raw: &str) -> Result<ThinkTime, String> {
+    let (min, max) = match raw.split_once("..") {
+        Some((min, max)) => (parse_duration(min)?, parse_duration(max)?),
+        None => (parse_duration(raw)?, parse_duration(raw)?),
+    };
+    if min > max {
+        return Err(format!("empty range `{}`", raw));
+    }
+    Ok(ThinkTime { min, max })
+}
+
+// `250ms`, `2s`, `1.5s` or `2m`, seconds without a unit
+pub fn parse_duration(raw: &str) -> Result<Duration, String> {
+    let raw = raw.trim();
+    let (number, scale) = if let Some(ms) = raw.strip_suffix("ms") {
+        (ms, 0.001)
+    } else if let Some(minutes) = raw.strip_suffix('m') {
+        (minutes, 60.0)
+    } else {
+        (raw.strip_suffix('s').unwrap_or(raw), 1.0)
+    };
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration `{}`", raw))?;
+    Duration::try_from_secs_f64(value * scale).map_err(|e| e.to_string())
+}
+
+impl RampArgs {
+    // Highest target TPS of the run
+    pub fn peak_tps(&self) -> u32 {
+        self.step_tps
+            .iter()
+            .copied()
+            .max()
+            .or(self.max_tps)
+            .unwrap_or(0)
+    }
+
+    // Picks the --shuffle-seed when none was given, before anything reads the schedule so
+    // every reader sees the steps in the same order
+    pub fn seed_shuffle(&mut self) {
+        if self.shuffle_steps && self.shuffle_seed.is_none() {
+            self.shuffle_seed = Some(rand::random());
+        }
+    }
+
+    // Target TPS and length of every step, in the order they run
+    pub fn schedule(&self) -> Result<Vec<(u32, Duration)>, TestError> {
+        let mut schedule = self.ascending_schedule()?;
+        if let Some(seed) = self.shuffle_seed.filter(|_| self.shuffle_steps) {
+            schedule.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        Ok(schedule)
+    }
+
+    fn ascending_schedule(&self) -> Result<Vec<(u32, Duration)>, TestError> {
+        if !self.step_tps.is_empty() {
+            let durations = if self.step_durations.is_empty() {
+                let slice = Duration::from_secs(self.duration as u64) / self.step_tps.len() as u32;
+                vec![slice; self.step_tps.len()]
+            } else if self.step_durations.len() == self.step_tps.len() {
+                self.step_durations.clone()
+            } else {
+                return Err(format!(
+                    "--step-durations has {} entries but --step-tps has {}",
+                    self.step_durations.len(),
+                    self.step_tps.len()
+                )
+                .into());
+            };
+            return Ok(self.step_tps.iter().copied().zip(durations).collect());
+        }
+        let max_tps = self.max_tps.unwrap_or(0);
+        let step_duration = Duration::from_secs(self.duration as u64) / self.steps;
+        Ok((1..=self.steps)
+            .map(|step| {
+                // Gradually increase tps on each run, or the payload at constant tps
+                let target_tps = match self.grow {
+                    Some(_) => max_tps,
+                    None => (max_tps * step) / self.steps,
+                };
+                (target_tps, step_duration)
+            })
+            .collect())
+    }
+}
+
+// Range the equilibrium rate of --p99-target-ms is known to be in
+struct GoalSeeker {
+    target_ms: f64,
+    // Highest rate that met the goal, 0 until one did
+    met: u32,
+    // Lowest rate that missed it, one past --max-tps until one did
+    missed: u32,
+}
+
+impl GoalSeeker {
+    fn new(target_ms: f64, max_tps: u32) -> GoalSeeker {
+        GoalSeeker {
+            target_ms,
+            met: 0,
+            missed: max_tps + 1,
+        }
+    }
+
+    // Rate of the next step, none once the range is down to a single TPS
+    fn next(&self) -> Option<u32> {
+        (self.missed - self.met > 1).then(|| self.met + (self.missed - self.met) / 2)
+    }
+
+    fn record(&mut self, metrics: &Metrics) {
+        if metrics.success_rate > SUSTAINABLE_SUCCESS_RATE
+            && metrics.p99_latency_ms <= self.target_ms
+        {
+            self.met = self.met.max(metrics.target_tps);
+        } else {
+            self.missed = self.missed.min(metrics.target_tps);
+        }
+    }
+
+    fn finish(self, max_tps: u32) -> GoalSeek {
+        GoalSeek {
+            p99_target_ms: self.target_ms,
+            equilibrium_tps: self.met,
+            lowest_missed_tps: (self.missed <= max_tps).then_some(self.missed),
+            converged: self.next().is_none(),
+        }
+    }
+}
+
+fn sample_think_time(think_time: ThinkTime) -> Duration {
+    if think_time.min == think_time.max {
+        return think_time.min;
+    }
+    rand::thread_rng().gen_range(think_time.min..=think_time.max)
+}
+
+fn parse_bucket_secs(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(secs) if secs > 0.0 => Ok(secs),
+        _ => Err(format!(
+            "expected a positive number of seconds, got `{}`",
+            raw
+        )),
+    }
+}
+
+pub fn parse_ratio(raw: &str) -> Result<f64, String> {
+    let ratio: f64 = raw
+        .parse()
+        .map_err(|_| format!("invalid number `{}`", raw))?;
+    if (0.0..=1.0).contains(&ratio) {
+        Ok(ratio)
+    } else {
+        Err("ratio must be between 0 and 1".to_string())
+    }
+}
+
+fn parse_accuracy(raw: &str) -> Result<f64, String> {
+    let accuracy: f64 = raw
+        .parse()
+        .map_err(|_| format!("invalid number `{}`", raw))?;
+    if accuracy > 0.0 && accuracy < 1.0 {
+        Ok(accuracy)
+    } else {
+        Err("accuracy must be between 0 and 1".to_string())
+    }
+}
+
+// Longest wait for a traced transaction to be accepted once the run is over
+const TRACE_RECEIPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+// We define sustainable tps as that at which tx success rate is more than 95%
+const SUSTAINABLE_SUCCESS_RATE: f64 = 0.95;
+
+// Counters updated while the run is in progress, read by the control API
+#[derive(Default)]
+pub struct LiveStats {
+    pub step: AtomicU32,
+    pub target_tps: AtomicU32,
+    pub sent: AtomicU64,
+    pub succeeded: AtomicU64,
+    pub failed: AtomicU64,
+    // Requests the watchdog flagged as hanging
+    pub long_tail: AtomicU64,
+    stop: AtomicBool,
+}
+
+impl LiveStats {
+    pub fn snapshot(&self) -> LiveSnapshot {
+        LiveSnapshot {
+            step: self.step.load(Ordering::Relaxed),
+            target_tps: self.target_tps.load(Ordering::Relaxed),
+            sent: self.sent.load(Ordering::Relaxed),
+            succeeded: self.succeeded.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            long_tail: self.long_tail.load(Ordering::Relaxed),
+        }
+    }
+
+    // Ends the current step early and skips the remaining ones
+    pub fn request_stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop_requested(&self) -> bool {
+        self.stop.load(Ordering::Relaxed)
+    }
+}
+
+pub struct RunContext {
+    pub failover: Failover,
+    pub pool: AccountPool,
+    // Accounts filling the non-sending roles of the scenario
+    pub helpers: Vec<Account>,
+    pub scenario: Workload,
+    // Token paying the fees and moved by the scenario
+    pub gas_token: Felt,
+    pub tx_log: Option<TxLog>,
+    pub db: Option<ResultsDb>,
+    pub statsd: Option<Statsd>,
+    pub label: Option<String>,
+    // Sent with every request of the run, see --tag-header
+    pub run_tag: String,
+    pub shard: Option<ShardAssignment>,
+    pub paymaster: PaymasterInfo,
+    pub live: Arc<LiveStats>,
+    pub request_timeout: Duration,
+    // How often the paymaster's availability is polled during the run
+    pub health_interval: Option<Duration>,
+    // Status page scraped during the run, see --status-url
+    pub status_page: Option<StatusPage>,
+    // Multiple of the running median latency past which in-flight requests get flagged
+    pub watchdog_multiple: Option<f64>,
+    // Follows executed transactions on-chain, see --lost-after-secs
+    pub receipts: Option<ReceiptTracker>,
+    pub success_criteria: Vec<SuccessCriterion>,
+    // Categories of paymaster errors on top of the built-in classification
+    pub taxonomy: Option<Taxonomy>,
+    // Simulated paymaster of the self-test, replaces the requests when set
+    pub injection: Option<Injection>,
+}
+
+// What a send task hands back to the step loop
+struct Completed {
+    in_flight: u32,
+    // When the schedule meant to send it, before sent_at when the sender fell behind
+    intended_at: Duration,
+    sent_at: Duration,
+    completed_at: Duration,
+    build_only: bool,
+    account: Felt,
+    // Size of all legs of the transaction together
+    calls: u32,
+    calldata_felts: u32,
+    phases: PhaseTimings,
+    transaction_hashes: Vec<Felt>,
+    tracking_ids: Vec<Felt>,
+    result: Result<f64, TransactionError>,
+    error_detail: Option<ErrorDetail>,
+    trace: Vec<TracedLeg>,
+}
+
+// Running totals of the step in progress
+struct StepAggregate {
+    step: u32,
+    target_tps: u32,
+    metrics: Metrics,
+    errors: ErrorBreakdown,
+    latencies: LatencySketch,
+    // Same latencies measured from the intended send time
+    corrected_latencies: LatencySketch,
+    // Server-Timing split of the latencies, for transactions answered with the header
+    server_latencies: LatencySketch,
+    network_latencies: LatencySketch,
+    concurrency: ConcurrencyProfiler,
+    // Latency of every attempt, failed ones included, for the timeout budget
+    budget: BudgetHistogram,
+    slowest: Vec<SlowTransaction>,
+    build_queue_ms: f64,
+    execute_queue_ms: f64,
+    // Fees estimated for the successful transactions
+    fee_in_gas_token: f64,
+    fee_in_strk: f64,
+    quotes: u32,
+    successful_quotes: u32,
+    quote_latencies: LatencySketch,
+    // Only buffered when the step goes to the results database
+    records: Vec<TxRecord>,
+    // Reservoir of --trace-sample, out of `traced` candidates
+    traces: Vec<TransactionTrace>,
+    traced: u32,
+    // Reservoir of --relayer-sample, out of `relayer_seen` successful transactions
+    relayer_sample: Vec<Felt>,
+    relayer_seen: u32,
+}
+
+impl StepAggregate {
+    fn skip(&mut self, unavailable: Unavailable) {
+        self.metrics.skipped_txs += 1;
+        if unavailable == Unavailable::Throttled {
+            self.metrics.throttled_txs += 1;
+        }
+    }
+}
+
+// State of --unique-calldata
+#[derive(Default)]
+struct Uniqueness {
+    // Added to the amounts of the next transaction
+    next: u64,
+    hashes: HashSet<Felt>,
+    duplicates: u32,
+}
+
+// Shared state of a run, every step executes against it
+struct Runner {
+    failover: Arc<Failover>,
+    pool: Arc<AccountPool>,
+    helpers: Vec<Account>,
+    scenario: Workload,
+    tx_log: Option<TxLog>,
+    db: Option<ResultsDb>,
+    statsd: Option<Statsd>,
+    live: Arc<LiveStats>,
+    budget_ms: f64,
+    slowest_k: usize,
+    sketch_accuracy: f64,
+    pipeline: Pipeline,
+    retry_stale: bool,
+    quote_ratio: f64,
+    trace_sample: u32,
+    relayer_sample: usize,
+    // Chance of a transaction of the current step to be traced
+    trace_probability: f64,
+    // Fee mode of the step being run
+    fee: Fee,
+    heatmap: Option<HeatmapBuilder>,
+    throughput: ThroughputTimeline,
+    segments: SegmentTimeline,
+    // Latency over the size of the transactions, fitted within every step
+    calls_fit: LinearFit,
+    calldata_fit: LinearFit,
+    gas_token: Felt,
+    watchdog: Option<Arc<Watchdog>>,
+    receipts: Option<ReceiptTracker>,
+    taxonomy: Option<Taxonomy>,
+    injection: Option<Injection>,
+    guard: Option<Guardrails>,
+    // Payload growth applied to every transaction of the current step
+    payload: Option<(Growth, u32)>,
+    unique: Option<Uniqueness>,
+    chain_length: Option<u32>,
+    test_start: Instant,
+    // Wall clock at `test_start`, transaction timestamps are offsets from it on the
+    // monotonic clock so that wall clock adjustments during the run don't show up
+    started_at_unix_ms: u64,
+    run_tag: String,
+    max_transactions: Option<u64>,
+    // Requests sent over the whole run
+    sent: u64,
+    // Number of the last executed step, a ramp step run in several variants counts once per variant
+    step: u32,
+}
+
+impl Runner {
+    // Sends `burst_size` transactions back to back on every tick, ticks are spaced so the
+    // average rate matches `target_tps`, then waits for the step to drain
+    // Tick k is due at exactly `step_start + k * period`, a late tick doesn't push the
+    // following ones back, and how late ticks went out is reported as schedule lag
+    async fn run_step(
+        &mut self,
+        target_tps: u32,
+        duration: Duration,
+        burst_size: u32,
+    ) -> Result<TestResult, TestError> {
+        let mut aggregate = self.start_step(target_tps, target_tps as f64 * duration.as_secs_f64());
+        let mut task_set = JoinSet::new();
+        let in_flight = Arc::new(AtomicU32::new(0));
+        let period = Duration::from_secs_f64(burst_size as f64 / target_tps as f64);
+        let step_start = Instant::now();
+        let step_end = step_start + duration;
+        let resources = ResourceSampler::start();
+        let mut ticks = 0u32;
+        let mut next_tick = step_start;
+        let mut total_lag = Duration::ZERO;
+        let mut max_lag = Duration::ZERO;
+
+        // Send transactions at target TPS for the step duration, results are folded in
+        // as they come back so nothing piles up in the join set on long steps
+        while Instant::now() < step_end && !self.live.stop_requested() && !self.budget_spent() {
+            tokio::select! {
+                biased;
+                _ = sleep_until(next_tick), if next_tick < step_end => {
+                    let lag = next_tick.elapsed();
+                    total_lag += lag;
+                    max_lag = max_lag.max(lag);
+                    if !self.guard() {
+                        aggregate.metrics.shed_txs += burst_size;
+                    } else {
+                        for _ in 0..burst_size {
+                            if self.budget_spent() {
+                                break;
+                            }
+                            if let Some(unavailable) = self.spawn_tx(&mut task_set, &in_flight, Duration::ZERO, Some(next_tick))? {
+                                aggregate.skip(unavailable);
+                            }
+                        }
+                    }
+                    ticks += 1;
+                    next_tick = step_start + period.mul_f64(ticks as f64);
+                }
+                Some(completed) = task_set.join_next() => {
+                    self.record(&mut aggregate, completed?)?;
+                }
+                _ = sleep_until(step_end) => {}
+            }
+        }
+        let send_secs = step_start.elapsed().as_secs_f64();
+        let resources = resources.finish();
+        aggregate.metrics.schedule_max_lag_ms = max_lag.as_secs_f64() * 1000.0;
+        if ticks > 0 {
+            aggregate.metrics.schedule_avg_lag_ms = total_lag.as_secs_f64() * 1000.0 / ticks as f64;
+        }
+
+        let drain_start = Instant::now();
+        self.drain(&mut aggregate, &mut task_set).await?;
+        let drain_time_ms = drain_start.elapsed().as_secs_f64() * 1000.0;
+        self.finish_step(
+            aggregate,
+            send_secs,
+            resources,
+            drain_time_ms,
+            burst_size,
+            None,
+        )
+    }
+
+    // Closed loop: every virtual user sends a transaction, waits for it to complete, thinks
+    // for a random time out of `think_time` and sends the next one. The rate that comes out
+    // is whatever the paymaster's latency and the think time allow
+    async fn run_closed_step(
+        &mut self,
+        users: u32,
+        think_time: ThinkTime,
+        duration: Duration,
+    ) -> Result<TestResult, TestError> {
+        // Users send about once a second at best, a guess only sizes the trace sample
+        let mut aggregate = self.start_step(0, users as f64 * duration.as_secs_f64());
+        let mut task_set = JoinSet::new();
+        let in_flight = Arc::new(AtomicU32::new(0));
+        let step_start = Instant::now();
+        let resources = ResourceSampler::start();
+        let deadline = step_start + duration;
+        // Users that found no account available, they retry on the next tick
+        let mut waiting = 0;
+        let mut retry = interval(Duration::from_millis(100));
+
+        for _ in 0..users {
+            if self.budget_spent() {
+                break;
+            }
+            if let Some(unavailable) =
+                self.spawn_tx(&mut task_set, &in_flight, Duration::ZERO, None)?
+            {
+                aggregate.skip(unavailable);
+                waiting += 1;
+            }
+        }
+        while Instant::now() < deadline && !self.live.stop_requested() && !self.budget_spent() {
+            tokio::select! {
+                Some(completed) = task_set.join_next() => {
+                    self.record(&mut aggregate, completed?)?;
+                    let think = sample_think_time(think_time);
+                    if self.budget_spent() {
+                        continue;
+                    }
+                    // The user waits for the next retry tick like one without an account
+                    if !self.guard() {
+                        aggregate.metrics.shed_txs += 1;
+                        waiting += 1;
+                    } else if let Some(unavailable) = self.spawn_tx(&mut task_set, &in_flight, think, None)? {
+                        aggregate.skip(unavailable);
+                        waiting += 1;
+                    }
+                }
+                _ = retry.tick(), if waiting > 0 => {
+                    while waiting > 0
+                        && !self.budget_spent()
+                        && self.guard()
+                        && self.spawn_tx(&mut task_set, &in_flight, Duration::ZERO, None)?.is_none()
+                    {
+                        waiting -= 1;
+                    }
+                }
+                _ = sleep_until(deadline) => {}
+            }
+        }
+        let send_secs = step_start.elapsed().as_secs_f64();
+        let resources = resources.finish();
+
+        // Users still thinking have nothing in flight, only wait for requests already sent
+        let drain_start = Instant::now();
+        self.drain(&mut aggregate, &mut task_set).await?;
+        let drain_time_ms = drain_start.elapsed().as_secs_f64() * 1000.0;
+        self.finish_step(
+            aggregate,
+            send_secs,
+            resources,
+            drain_time_ms,
+            1,
+            Some(users),
+        )
+    }
+
+    // True once --max-transactions requests went out
+    fn budget_spent(&self) -> bool {
+        self.max_transactions.is_some_and(|max| self.sent >= max)
+    }
+
+    // `expected_txs` is the number of transactions the step should send
+    fn start_step(&mut self, target_tps: u32, expected_txs: f64) -> StepAggregate {
+        self.step += 1;
+        // Twice the sample size is traced, the reservoir then keeps a uniform sample of
+        // those even when the step sends fewer transactions than expected
+        self.trace_probability = if self.trace_sample > 0 && expected_txs > 0.0 {
+            (2.0 * self.trace_sample as f64 / expected_txs).min(1.0)
+        } else {
+            0.0
+        };
+        self.live.step.store(self.step, Ordering::Relaxed);
+        self.live.target_tps.store(target_tps, Ordering::Relaxed);
+        StepAggregate {
+            step: self.step,
+            target_tps,
+            metrics: Metrics {
+                target_tps,
+                ..Default::default()
+            },
+            errors: ErrorBreakdown::default(),
+            latencies: LatencySketch::new(self.sketch_accuracy),
+            corrected_latencies: LatencySketch::new(self.sketch_accuracy),
+            server_latencies: LatencySketch::new(self.sketch_accuracy),
+            network_latencies: LatencySketch::new(self.sketch_accuracy),
+            concurrency: ConcurrencyProfiler::default(),
+            budget: BudgetHistogram::new(self.budget_ms),
+            slowest: Vec::new(),
+            build_queue_ms: 0.0,
+            execute_queue_ms: 0.0,
+            fee_in_gas_token: 0.0,
+            fee_in_strk: 0.0,
+            quotes: 0,
+            successful_quotes: 0,
+            quote_latencies: LatencySketch::new(self.sketch_accuracy),
+            records: Vec::new(),
+            traces: Vec::new(),
+            traced: 0,
+            relayer_sample: Vec::new(),
+            relayer_seen: 0,
+        }
+    }
+
+    // Wait for all in-flight tasks to complete
+    async fn drain(
+        &mut self,
+        aggregate: &mut StepAggregate,
+        task_set: &mut JoinSet<Completed>,
+    ) -> Result<(), TestError> {
+        while let Some(completed) = task_set.join_next().await {
+            self.record(aggregate, completed?)?;
+        }
+        Ok(())
+    }
+
+    fn finish_step(
+        &mut self,
+        aggregate: StepAggregate,
+        send_secs: f64,
+        resources: ResourceUsage,
+        drain_time_ms: f64,
+        burst_size: u32,
+        users: Option<u32>,
+    ) -> Result<TestResult, TestError> {
+        let StepAggregate {
+            step,
+            target_tps,
+            mut metrics,
+            errors,
+            latencies,
+            corrected_latencies,
+            server_latencies,
+            network_latencies,
+            concurrency,
+            budget,
+            slowest,
+            build_queue_ms,
+            execute_queue_ms,
+            fee_in_gas_token,
+            fee_in_strk,
+            quotes,
+            successful_quotes,
+            quote_latencies,
+            records,
+            traces,
+            relayer_sample,
+            ..
+        } = aggregate;
+        metrics.total_txs = metrics.successful_txs + metrics.failed_txs;
+        if metrics.total_txs > 0 {
+            metrics.avg_build_queue_ms = build_queue_ms / metrics.total_txs as f64;
+            metrics.avg_execute_queue_ms = execute_queue_ms / metrics.total_txs as f64;
+        }
+        if metrics.successful_txs > 0 {
+            metrics.avg_fee_in_gas_token = fee_in_gas_token / metrics.successful_txs as f64;
+            metrics.avg_fee_in_strk = fee_in_strk / metrics.successful_txs as f64;
+        }
+        if send_secs > 0.0 {
+            metrics.effective_tps = metrics.successful_txs as f64 / send_secs;
+            metrics.offered_tps = metrics.total_txs as f64 / send_secs;
+        }
+        metrics.avg_latency_ms = latencies.mean();
+        metrics.p50_latency_ms = latencies.quantile(0.50);
+        metrics.p95_latency_ms = latencies.quantile(0.95);
+        metrics.p99_latency_ms = latencies.quantile(0.99);
+        metrics.latency_stddev_ms = latencies.std_dev();
+        metrics.latency_cov = latencies.coefficient_of_variation();
+        metrics.latency_mad_ms = latencies.median_absolute_deviation();
+        metrics.corrected_avg_latency_ms = corrected_latencies.mean();
+        metrics.corrected_p50_latency_ms = corrected_latencies.quantile(0.50);
+        metrics.corrected_p95_latency_ms = corrected_latencies.quantile(0.95);
+        metrics.corrected_p99_latency_ms = corrected_latencies.quantile(0.99);
+        metrics.server_p50_ms = server_latencies.quantile(0.50);
+        metrics.server_p95_ms = server_latencies.quantile(0.95);
+        metrics.server_p99_ms = server_latencies.quantile(0.99);
+        metrics.network_p50_ms = network_latencies.quantile(0.50);
+        metrics.network_p95_ms = network_latencies.quantile(0.95);
+        metrics.network_p99_ms = network_latencies.quantile(0.99);
+        metrics.update_success_rate();
+        let serial_tps = self
+            .chain_length
+            .filter(|_| metrics.avg_latency_ms > 0.0)
+            .map(|length| length as f64 * 1000.0 / metrics.avg_latency_ms);
+        let quotes = (self.quote_ratio > 0.0).then(|| QuoteMetrics {
+            total: quotes,
+            successful: successful_quotes,
+            success_rate: if quotes > 0 {
+                successful_quotes as f64 / quotes as f64
+            } else {
+                0.0
+            },
+            avg_latency_ms: quote_latencies.mean(),
+            p50_latency_ms: quote_latencies.quantile(0.50),
+            p95_latency_ms: quote_latencies.quantile(0.95),
+            p99_latency_ms: quote_latencies.quantile(0.99),
+        });
+        let result = TestResult {
+            metrics,
+            burst_size,
+            users,
+            payload_size: self.payload.map(|(_, size)| size),
+            fee: self.fee,
+            quotes,
+            serial_tps,
+            error_breakdown: errors,
+            concurrency: concurrency.finish(),
+            queue: None,
+            drain_time_ms,
+            timeout_budget: budget.finish(),
+            slowest,
+            traces,
+            resources,
+            criteria: Vec::new(),
+            relayers: None,
+            relayer_sample,
+        };
+        if let Some(db) = &mut self.db {
+            db.record_step(step, target_tps, &result, &records)?;
+        }
+        Ok(result)
+    }
+
+    fn record(
+        &mut self,
+        aggregate: &mut StepAggregate,
+        completed: Completed,
+    ) -> Result<(), TestError> {
+        let Completed {
+            in_flight: concurrency,
+            intended_at,
+            sent_at,
+            completed_at,
+            build_only,
+            account,
+            calls,
+            calldata_felts,
+            phases,
+            transaction_hashes,
+            tracking_ids,
+            result,
+            error_detail,
+            trace,
+        } = completed;
+        // Only built when something keeps it, the run tag is copied into every record
+        if self.tx_log.is_some() || self.db.is_some() {
+            let record = TxRecord {
+                run_tag: self.run_tag.clone(),
+                step: aggregate.step,
+                target_tps: aggregate.target_tps,
+                sent_at_ms: sent_at.as_millis() as u64,
+                sent_at_unix_ms: self.started_at_unix_ms + sent_at.as_millis() as u64,
+                completed_at_ms: completed_at.as_millis() as u64,
+                completed_at_unix_ms: self.started_at_unix_ms + completed_at.as_millis() as u64,
+                build_only,
+                in_flight: concurrency,
+                latency_ms: result.as_ref().ok().copied(),
+                error: result.as_ref().err().copied(),
+                transaction_hashes: transaction_hashes.clone(),
+                tracking_ids,
+            };
+            if let Some(log) = &mut self.tx_log {
+                log.write(&record)?;
+            }
+            if self.db.is_some() {
+                aggregate.records.push(record);
+            }
+        }
+        if let Some(statsd) = &self.statsd {
+            statsd.record(
+                aggregate.step,
+                aggregate.target_tps,
+                phases.total_ms(),
+                build_only,
+                &result,
+            );
+        }
+        if build_only {
+            aggregate.quotes += 1;
+            if let Ok(latency) = result {
+                aggregate.successful_quotes += 1;
+                aggregate.quote_latencies.add(latency);
+            }
+            return Ok(());
+        }
+        self.throughput.add(sent_at, completed_at, result.is_ok());
+        self.segments.add(sent_at, &result);
+        aggregate.budget.add(phases.total_ms());
+        aggregate.build_queue_ms += phases.build_queue_ms;
+        aggregate.execute_queue_ms += phases.execute_queue_ms;
+        keep_slowest(
+            &mut aggregate.slowest,
+            SlowTransaction {
+                account,
+                sent_at_ms: sent_at.as_millis() as u64,
+                latency_ms: phases.total_ms(),
+                phases,
+                error: result.as_ref().err().copied(),
+            },
+            self.slowest_k,
+        );
+        if !trace.is_empty() {
+            let capture_id = format!(
+                "{}-{}-{}",
+                self.run_tag,
+                aggregate.step,
+                aggregate.traced + 1
+            );
+            keep_sampled(
+                &mut aggregate.traces,
+                &mut aggregate.traced,
+                TransactionTrace {
+                    capture_id,
+                    account,
+                    sent_at_ms: sent_at.as_millis() as u64,
+                    latency_ms: result.as_ref().ok().copied(),
+                    error: result.as_ref().err().copied(),
+                    phases,
+                    legs: trace,
+                    transaction_hashes: transaction_hashes.clone(),
+                    receipts: Vec::new(),
+                },
+                self.trace_sample as usize,
+            );
+        }
+        if let Some(unique) = &mut self.unique {
+            for hash in &transaction_hashes {
+                if !unique.hashes.insert(*hash) {
+                    unique.duplicates += 1;
+                }
+            }
+        }
+        let metrics = &mut aggregate.metrics;
+        let errors = &mut aggregate.errors;
+        if phases.stale_rejections > 0 {
+            metrics.stale_txs += 1;
+        }
+        match result {
+            Ok(latency) => {
+                metrics.successful_txs += 1;
+                if let Some(heatmap) = &mut self.heatmap {
+                    heatmap.add(sent_at, latency);
+                }
+                if phases.retries > 0 {
+                    metrics.recovered_txs += 1;
+                }
+                aggregate.fee_in_gas_token += phases.fee_in_gas_token.unwrap_or(0.0);
+                aggregate.fee_in_strk += phases.fee_in_strk.unwrap_or(0.0);
+                if let Some(hash) = transaction_hashes
+                    .first()
+                    .filter(|_| self.relayer_sample > 0)
+                {
+                    keep_sampled(
+                        &mut aggregate.relayer_sample,
+                        &mut aggregate.relayer_seen,
+                        *hash,
+                        self.relayer_sample,
+                    );
+                }
+                if let Some(receipts) = &mut self.receipts {
+                    receipts.track(
+                        aggregate.step,
+                        account,
+                        transaction_hashes,
+                        self.test_start + sent_at,
+                        completed_at,
+                    );
+                }
+                aggregate.latencies.add(latency);
+                self.calls_fit.add(aggregate.step, calls as f64, latency);
+                self.calldata_fit
+                    .add(aggregate.step, calldata_felts as f64, latency);
+                let late = sent_at.saturating_sub(intended_at);
+                aggregate
+                    .corrected_latencies
+                    .add(latency + late.as_secs_f64() * 1000.0);
+                if let (Some(server_ms), Some(network_ms)) = (phases.server_ms, phases.network_ms) {
+                    metrics.server_timed_txs += 1;
+                    aggregate.server_latencies.add(server_ms);
+                    aggregate.network_latencies.add(network_ms);
+                }
+                aggregate.concurrency.add(concurrency, latency);
+            }
+            Err(error_type) => {
+                metrics.failed_txs += 1;
+                let category = self
+                    .taxonomy
+                    .as_ref()
+                    .zip(error_detail.as_ref())
+                    .and_then(|(taxonomy, detail)| taxonomy.categorize(detail));
+                if let Some(category) = category {
+                    *errors.categories.entry(category.to_string()).or_default() += 1;
+                    return Ok(());
+                }
+                match error_type {
+                    TransactionError::Nonce => errors.nonce_conflicts += 1,
+                    TransactionError::Timeout => errors.timeouts += 1,
+                    TransactionError::Relayer => errors.relayer_exhaustion += 1,
+                    TransactionError::JsonRpc => errors.json_rpc_errors += 1,
+                    TransactionError::Tls => errors.tls_handshake += 1,
+                    TransactionError::Other => errors.other += 1,
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Whether sends may go out with the generator's memory and file descriptors where they
+    // are. Close to a limit the run is stopped, its in-flight requests still complete
+    fn guard(&mut self) -> bool {
+        match self.guard.as_mut().map(Guardrails::check) {
+            Some(GuardState::Abort) => {
+                self.live.request_stop();
+                false
+            }
+            Some(GuardState::Shedding) => false,
+            _ => true,
+        }
+    }
+
+    // Returns why the tick was skipped when no account was available to send from
+    // The transaction goes out after `delay`, the think time of closed-loop users
+    fn spawn_tx(
+        &mut self,
+        task_set: &mut JoinSet<Completed>,
+        in_flight: &Arc<AtomicU32>,
+        delay: Duration,
+        // Tick of the open-loop schedule the transaction belongs to
+        scheduled: Option<Instant>,
+    ) -> Result<Option<Unavailable>, TestError> {
+        let sender = match self.pool.checkout() {
+            Ok(sender) => sender,
+            Err(unavailable) => return Ok(Some(unavailable)),
+        };
+        // The pool account takes the sending role, helpers fill the remaining ones
+        let account = self.pool.get(sender).address;
+        let mut tx_accounts = vec![self.pool.get(sender).clone()];
+        tx_accounts.extend(self.helpers.iter().cloned());
+        let legs = self
+            .scenario
+            .legs(&tx_accounts, self.gas_token)
+            .and_then(|mut legs| {
+                if let Some(unique) = &self.unique {
+                    self.scenario.make_unique(&mut legs, unique.next)?;
+                }
+                Ok(legs)
+            });
+        let mut legs = match legs {
+            Ok(legs) => legs,
+            Err(e) => {
+                self.pool.checkin(sender);
+                return Err(e);
+            }
+        };
+        self.sent += 1;
+        if let Some(unique) = &mut self.unique {
+            unique.next += 1;
+        }
+        if let Some((growth, size)) = self.payload {
+            growth.apply(&mut legs, size);
+        }
+        // Later legs depend on the earlier ones being executed, a quote is for the first one
+        let build_only = self.quote_ratio > 0.0 && rand::thread_rng().gen_bool(self.quote_ratio);
+        let trace = !build_only
+            && self.trace_probability > 0.0
+            && rand::thread_rng().gen_bool(self.trace_probability);
+        if build_only {
+            legs.truncate(1);
+        }
+        let calls = legs.iter().map(|leg| leg.calls.len() as u32).sum();
+        let calldata_felts = legs
+            .iter()
+            .flat_map(|leg| &leg.calls)
+            .map(|call| call.calldata.len() as u32)
+            .sum();
+
+        let task_failover = Arc::clone(&self.failover);
+        let task_pool = Arc::clone(&self.pool);
+        let task_live = Arc::clone(&self.live);
+        let task_in_flight = Arc::clone(in_flight);
+        let fee = self.fee;
+        let pipeline = self.pipeline.clone();
+        let retry_stale = self.retry_stale;
+        let injection = self.injection;
+        let task_watchdog = self.watchdog.clone();
+        let test_start = self.test_start;
+        let step = self.step;
+        task_set.spawn(async move {
+            if !delay.is_zero() {
+                sleep(delay).await;
+            }
+            let sent_at = test_start.elapsed();
+            // A closed-loop user sends when it is ready, there is nothing to be late for
+            let intended_at =
+                scheduled.map_or(sent_at, |tick| tick.saturating_duration_since(test_start));
+            let task_watchdog = task_watchdog.map(|watchdog| {
+                let id = watchdog.register(step, account, sent_at.as_millis() as u64);
+                (watchdog, id)
+            });
+            task_live.sent.fetch_add(1, Ordering::Relaxed);
+            // Number of requests in flight including this one at the time it is sent
+            let concurrency = task_in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            let task_client = task_failover.client();
+            let Sent {
+                phases,
+                transaction_hashes,
+                tracking_ids,
+                result,
+                error_detail,
+                trace,
+            } = match injection {
+                Some(injection) => injection.send().await,
+                None => {
+                    run_legs(
+                        &task_client,
+                        &pipeline,
+                        &tx_accounts,
+                        legs,
+                        fee,
+                        retry_stale,
+                        build_only,
+                        trace,
+                    )
+                    .await
+                }
+            };
+            if let Some((watchdog, id)) = task_watchdog {
+                watchdog.complete(id, result.as_ref().ok().copied());
+            }
+            task_failover.record(result.is_ok());
+            task_pool.record(sender, &result);
+            match result {
+                Ok(_) => task_live.succeeded.fetch_add(1, Ordering::Relaxed),
+                Err(_) => task_live.failed.fetch_add(1, Ordering::Relaxed),
+            };
+            task_in_flight.fetch_sub(1, Ordering::SeqCst);
+            Completed {
+                in_flight: concurrency,
+                intended_at,
+                sent_at,
+                completed_at: test_start.elapsed(),
+                build_only,
+                account,
+                calls,
+                calldata_felts,
+                phases,
+                transaction_hashes,
+                tracking_ids,
+                result,
+                error_detail,
+                trace,
+            }
+        });
+        Ok(None)
+    }
+}
+
+// Needs the whole run, the baseline is the lowest latency of any step
+fn add_queue_estimates(results: Vec<&mut TestResult>) {
+    let Some(baseline_latency_ms) = results
+        .iter()
+        .filter(|r| r.metrics.successful_txs > 0)
+        .map(|r| r.metrics.avg_latency_ms)
+        .min_by(f64::total_cmp)
+    else {
+        return;
+    };
+    for result in results {
+        let metrics = &result.metrics;
+        if metrics.successful_txs == 0 {
+            continue;
+        }
+        let arrivals_per_ms = metrics.offered_tps / 1000.0;
+        result.queue = Some(QueueEstimate {
+            in_system: arrivals_per_ms * metrics.avg_latency_ms,
+            queue_depth: arrivals_per_ms * (metrics.avg_latency_ms - baseline_latency_ms),
+            baseline_latency_ms,
+        });
+    }
+}
+
+// Outcome of every transaction of the traces, they had the rest of the run to land
+fn add_criteria(
+    result: &mut TestResult,
+    criteria: &[SuccessCriterion],
+    inclusions: &[&Inclusion],
+    accuracy: f64,
+) {
+    result.criteria = criteria
+        .iter()
+        .map(|c| criterion_metrics(*c, result.metrics.total_txs, inclusions, accuracy))
+        .collect();
+}
+
+// Relayer of every sampled transaction, the sender of the invoke the paymaster submitted
+async fn add_relayer_usage(
+    provider: &Arc<JsonRpcClient<HttpTransport>>,
+    results: Vec<&mut TestResult>,
+) {
+    let sampled: usize = results.iter().map(|r| r.relayer_sample.len()).sum();
+    if sampled == 0 {
+        return;
+    }
+    println!("Looking up the relayers of {} transaction(s)", sampled);
+    for result in results {
+        let mut lookups = JoinSet::new();
+        for hash in std::mem::take(&mut result.relayer_sample) {
+            let provider = Arc::clone(provider);
+            lookups.spawn(async move { relayer_of(&provider, hash).await });
+        }
+        let mut counts: BTreeMap<Felt, u32> = BTreeMap::new();
+        let mut unresolved = 0;
+        while let Some(lookup) = lookups.join_next().await {
+            match lookup.ok().flatten() {
+                Some(relayer) => *counts.entry(relayer).or_default() += 1,
+                None => unresolved += 1,
+            }
+        }
+        let resolved: u32 = counts.values().sum();
+        let mut relayers: Vec<RelayerShare> = counts
+            .into_iter()
+            .map(|(relayer, transactions)| RelayerShare {
+                relayer,
+                transactions,
+                share: transactions as f64 / resolved as f64,
+            })
+            .collect();
+        relayers.sort_by_key(|r| std::cmp::Reverse(r.transactions));
+        result.relayers = Some(RelayerUsage {
+            unresolved,
+            relayers,
+        });
+    }
+}
+
+async fn add_trace_receipts(
+    provider: &Arc<JsonRpcClient<HttpTransport>>,
+    mut traces: Vec<&mut TransactionTrace>,
+) {
+    if traces.is_empty() {
+        return;
+    }
+    println!(
+        "Fetching receipts of {} traced transaction(s)",
+        traces.len()
+    );
+    let mut lookups = JoinSet::new();
+    for (i, trace) in traces.iter().enumerate() {
+        let provider = Arc::clone(provider);
+        let hashes = trace.transaction_hashes.clone();
+        lookups.spawn(async move {
+            let mut receipts = Vec::new();
+            for hash in hashes {
+                receipts.push(wait_for_receipt(&provider, hash, TRACE_RECEIPT_TIMEOUT).await);
+            }
+            (i, receipts)
+        });
+    }
+    while let Some(lookup) = lookups.join_next().await {
+        if let Ok((i, receipts)) = lookup {
+            traces[i].receipts = receipts;
+        }
+    }
+}
+
+// We divide the test duration by number of steps into equally sized duration for each sample tps
+// For each such sub duration, we send the desired tps
+// tps ramps up from 1 to target max tps
+// We send txs asynchronously and wait for the results
+// For each result we update the metrics and errors
+// Finally we compile summary statistics
+pub async fn linear_ramp_test(
+    context: RunContext,
+    args: &RampArgs,
+) -> Result<StressTestResults, TestError> {
+    let RunContext {
+        failover,
+        pool,
+        helpers,
+        scenario,
+        gas_token,
+        tx_log,
+        db,
+        statsd,
+        label,
+        run_tag,
+        shard,
+        paymaster,
+        live,
+        request_timeout,
+        health_interval,
+        status_page,
+        watchdog_multiple,
+        receipts,
+        success_criteria,
+        taxonomy,
+        injection,
+    } = context;
+    let test_start = Instant::now();
+    let started_at_unix_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+    let failover = Arc::new(failover);
+    let health = health_interval
+        .map(|period| HealthMonitor::start(Arc::clone(&failover), period, test_start));
+    let status_page = match status_page {
+        Some(page) => Some(StatusMonitor::start(page, test_start)?),
+        None => None,
+    };
+    let watchdog = watchdog_multiple.map(|multiple| Watchdog::new(multiple, Arc::clone(&live)));
+    let watchdog_task = watchdog.as_ref().map(Watchdog::start);
+    let chain_length = scenario.chain_length();
+    let mut runner = Runner {
+        failover,
+        pool: Arc::new(pool),
+        helpers,
+        scenario,
+        tx_log,
+        db,
+        statsd,
+        live,
+        budget_ms: request_timeout.as_secs_f64() * 1000.0,
+        slowest_k: args.slowest_k,
+        sketch_accuracy: args.sketch_accuracy,
+        pipeline: Pipeline::new(
+            args.build_concurrency,
+            args.execute_concurrency,
+            args.confirm_delay.map(|delay| (delay.min, delay.max)),
+        ),
+        retry_stale: args.retry_stale,
+        quote_ratio: args.quote_ratio,
+        trace_sample: args.trace_sample,
+        relayer_sample: args.relayer_sample as usize,
+        trace_probability: 0.0,
+        fee: Fee::GasToken(gas_token),
+        heatmap: args
+            .heatmap_secs
+            .map(|secs| HeatmapBuilder::new(secs, request_timeout.as_secs_f64() * 1000.0)),
+        throughput: ThroughputTimeline::default(),
+        calls_fit: LinearFit::default(),
+        calldata_fit: LinearFit::default(),
+        segments: SegmentTimeline::new(
+            Duration::from_secs(args.segment_mins * 60),
+            args.sketch_accuracy,
+        ),
+        gas_token,
+        watchdog,
+        receipts,
+        taxonomy,
+        injection,
+        guard: Guardrails::new(&args.guard),
+        payload: None,
+        unique: args.unique_calldata.then(Uniqueness::default),
+        chain_length,
+        test_start,
+        started_at_unix_ms,
+        run_tag: run_tag.clone(),
+        max_transactions: args.max_transactions,
+        sent: 0,
+        step: 0,
+    };
+    let mut results = Vec::new();
+    let mut burst_comparison = Vec::new();
+    let mut fee_comparison = Vec::new();
+    let mut stop_reason = StopReason::Completed;
+    let mut consecutive_failures = 0;
+    let mut hooks = Vec::new();
+
+    let schedule = args.schedule()?;
+    let steps = schedule.len() as u32;
+    let mut seeker = args
+        .p99_target_ms
+        .map(|target_ms| GoalSeeker::new(target_ms, args.peak_tps()));
+
+    for (step, &(scheduled_tps, step_duration)) in (1..).zip(&schedule) {
+        if runner.guard.as_ref().is_some_and(Guardrails::aborted) {
+            stop_reason = StopReason::ResourceLimit;
+            break;
+        }
+        if runner.live.stop_requested() {
+            stop_reason = StopReason::StopRequested;
+            break;
+        }
+        let target_tps = match &seeker {
+            Some(seeker) => match seeker.next() {
+                Some(target_tps) => target_tps,
+                None => {
+                    stop_reason = StopReason::Converged;
+                    break;
+                }
+            },
+            None => scheduled_tps,
+        };
+
+        // Closed loop ramps the number of virtual users instead of the rate
+        let users = args
+            .users
+            .map(|max_users| (max_users * step / steps).max(1));
+        if users.is_none() && target_tps == 0 {
+            continue;
+        }
+
+        let mut hook_env = vec![
+            ("STEP", step.to_string()),
+            ("RUN_TAG", run_tag.clone()),
+            match users {
+                Some(users) => ("USERS", users.to_string()),
+                None => ("TARGET_TPS", target_tps.to_string()),
+            },
+        ];
+        if let Some(command) = &args.hooks.pre_step_hook {
+            hooks.push(run_hook(HookStage::Pre, command, step, &hook_env).await?);
+        }
+
+        if let Some(users) = users {
+            println!("Testing users: {}", users);
+            results.push(
+                runner
+                    .run_closed_step(users, args.think_time, step_duration)
+                    .await?,
+            );
+        } else {
+            if let Some(growth) = args.grow {
+                let size = (args.grow_max * step / steps).max(1);
+                println!("Testing payload size: {}", size);
+                runner.payload = Some((growth, size));
+            }
+
+            if args.burst_compare {
+                println!(
+                    "Testing TPS: {} (smooth, then bursts of {})",
+                    target_tps, args.burst_size
+                );
+                let smooth = runner.run_step(target_tps, step_duration, 1).await?;
+                // Variants the --max-transactions budget ran out before are left out,
+                // along with the comparison
+                if !runner.budget_spent() {
+                    let burst = runner
+                        .run_step(target_tps, step_duration, args.burst_size)
+                        .await?;
+                    burst_comparison.push(BurstComparison {
+                        target_tps,
+                        burst_size: args.burst_size,
+                        smooth: LatencyDistribution::from(&smooth.metrics),
+                        burst: LatencyDistribution::from(&burst.metrics),
+                    });
+                    results.push(smooth);
+                    results.push(burst);
+                } else {
+                    results.push(smooth);
+                }
+            } else if args.compare_fee_modes {
+                println!("Testing TPS: {} (once per fee mode)", target_tps);
+                let mut fees = vec![Fee::GasToken(gas_token)];
+                fees.extend(args.compare_gas_token.iter().copied().map(Fee::GasToken));
+                fees.push(Fee::Sponsored);
+                let mut runs = Vec::new();
+                let modes = fees.len();
+                for fee in fees {
+                    if runner.budget_spent() {
+                        break;
+                    }
+                    runner.fee = fee;
+                    runs.push(
+                        runner
+                            .run_step(target_tps, step_duration, args.burst_size)
+                            .await?,
+                    );
+                }
+                runner.fee = Fee::GasToken(gas_token);
+                if runs.len() == modes {
+                    fee_comparison.push(FeeModeComparison::new(target_tps, &runs));
+                }
+                results.extend(runs);
+            } else {
+                println!("Testing TPS: {}", target_tps);
+                results.push(
+                    runner
+                        .run_step(target_tps, step_duration, args.burst_size)
+                        .await?,
+                );
+            }
+        }
+
+        crash::keep_steps(&results);
+
+        if let (Some(seeker), Some(result)) = (&mut seeker, results.last()) {
+            seeker.record(&result.metrics);
+        }
+
+        if let (Some(command), Some(result)) = (&args.hooks.post_step_hook, results.last()) {
+            let metrics = &result.metrics;
+            hook_env.extend([
+                ("SUCCESS_RATE", metrics.success_rate.to_string()),
+                ("EFFECTIVE_TPS", metrics.effective_tps.to_string()),
+                ("P95_LATENCY_MS", metrics.p95_latency_ms.to_string()),
+            ]);
+            hooks.push(run_hook(HookStage::Post, command, step, &hook_env).await?);
+        }
+
+        if seeker.as_ref().is_some_and(|s| s.next().is_none()) {
+            stop_reason = StopReason::Converged;
+            break;
+        }
+        if runner.budget_spent() {
+            println!("{} transactions sent, skipping the rest", runner.sent);
+            stop_reason = StopReason::MaxTransactions;
+            break;
+        }
+        let failed = results
+            .last()
+            .is_some_and(|r| r.metrics.success_rate <= SUSTAINABLE_SUCCESS_RATE);
+        if args.verify_peak && failed {
+            stop_reason = StopReason::StepFailed;
+            break;
+        }
+        consecutive_failures = if failed { consecutive_failures + 1 } else { 0 };
+        if args
+            .stop_after_failures
+            .is_some_and(|limit| consecutive_failures >= limit)
+        {
+            println!(
+                "{} failing steps in a row, skipping the rest",
+                consecutive_failures
+            );
+            stop_reason = StopReason::Saturated;
+            break;
+        }
+
+        if step < steps && args.cooldown_secs > 0 {
+            sleep(Duration::from_secs(args.cooldown_secs)).await;
+        }
+    }
+    // Tripped during the last step
+    if runner.guard.as_ref().is_some_and(Guardrails::aborted) {
+        stop_reason = StopReason::ResourceLimit;
+    }
+
+    // A single passing step right below the failing one may have been lucky
+    let mut peak_verification = None;
+    let peak = results
+        .iter()
+        .filter(|r| r.metrics.success_rate > SUSTAINABLE_SUCCESS_RATE)
+        .map(|r| r.metrics.target_tps)
+        .max();
+    if let (StopReason::StepFailed, Some(peak)) = (stop_reason, peak) {
+        if args.cooldown_secs > 0 {
+            sleep(Duration::from_secs(args.cooldown_secs)).await;
+        }
+        println!("Re-verifying TPS: {}", peak);
+        let step_duration = schedule
+            .iter()
+            .find(|(tps, _)| *tps == peak)
+            .map(|(_, duration)| *duration)
+            .unwrap_or_default();
+        let duration = args
+            .verify_secs
+            .map(Duration::from_secs)
+            .unwrap_or(step_duration);
+        let result = runner.run_step(peak, duration, args.burst_size).await?;
+        peak_verification = Some(PeakVerification {
+            target_tps: peak,
+            passed: result.metrics.success_rate > SUSTAINABLE_SUCCESS_RATE,
+            result,
+        });
+    }
+
+    let goal_seek = seeker.map(|seeker| seeker.finish(args.peak_tps()));
+    if let Some(goal_seek) = &goal_seek {
+        println!(
+            "Highest TPS with p99 under {} ms: {}",
+            goal_seek.p99_target_ms, goal_seek.equilibrium_tps
+        );
+    }
+
+    let Runner {
+        failover,
+        pool,
+        tx_log,
+        mut db,
+        watchdog,
+        heatmap,
+        throughput,
+        segments,
+        calls_fit,
+        calldata_fit,
+        receipts,
+        ..
+    } = runner;
+    if let Some(task) = watchdog_task {
+        task.abort();
+    }
+
+    // Steps are numbered in the order they ran, the peak verification last. Comparisons
+    // and stored steps were built before and keep counting lost transactions as successes
+    let (lost_transactions, inclusions) = match receipts {
+        Some(receipts) => {
+            println!("Waiting for executed transactions to show up on-chain");
+            receipts.finish().await
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    for lost in &lost_transactions {
+        match results.get_mut(lost.step as usize - 1) {
+            Some(result) => result.mark_lost(),
+            None => {
+                if let Some(verification) = &mut peak_verification {
+                    verification.result.mark_lost();
+                    verification.passed =
+                        verification.result.metrics.success_rate > SUSTAINABLE_SUCCESS_RATE;
+                }
+            }
+        }
+    }
+    if !lost_transactions.is_empty() {
+        println!(
+            "{} executed transaction(s) never showed up on-chain",
+            lost_transactions.len()
+        );
+    }
+    add_queue_estimates(
+        results
+            .iter_mut()
+            .chain(peak_verification.as_mut().map(|v| &mut v.result))
+            .collect(),
+    );
+    let on_chain_criteria = success_criteria
+        .iter()
+        .any(|c| *c != SuccessCriterion::Execute);
+    if on_chain_criteria {
+        let steps = results.len();
+        for (i, result) in results.iter_mut().enumerate() {
+            let step: Vec<&Inclusion> = inclusions
+                .iter()
+                .filter(|c| c.step as usize == i + 1)
+                .collect();
+            add_criteria(result, &success_criteria, &step, args.sketch_accuracy);
+        }
+        if let Some(verification) = &mut peak_verification {
+            let step: Vec<&Inclusion> = inclusions
+                .iter()
+                .filter(|c| c.step as usize > steps)
+                .collect();
+            add_criteria(
+                &mut verification.result,
+                &success_criteria,
+                &step,
+                args.sketch_accuracy,
+            );
+        }
+    }
+    if let Some(provider) = pool.provider() {
+        add_relayer_usage(
+            provider,
+            results
+                .iter_mut()
+                .chain(peak_verification.as_mut().map(|v| &mut v.result))
+                .collect(),
+        )
+        .await;
+        let traces = results
+            .iter_mut()
+            .chain(peak_verification.as_mut().map(|v| &mut v.result))
+            .flat_map(|result| result.traces.iter_mut())
+            .filter(|trace| !trace.transaction_hashes.is_empty())
+            .collect();
+        add_trace_receipts(provider, traces).await;
+    }
+    // The results are worth more than the cache, a failed update only costs the next run
+    if let Err(e) = pool.save_nonces().await {
+        eprintln!("Failed to update the nonce cache: {}", e);
+    }
+    let tx_log_segments = match tx_log {
+        Some(log) => log.finish()?,
+        None => Vec::new(),
+    };
+
+    let total_successful: u32 = results.iter().map(|r| r.metrics.successful_txs).sum();
+    // A run stopped before its first step has no rate to average
+    let overall_success_rate =
+        results.iter().map(|r| r.metrics.success_rate).sum::<f64>() / results.len().max(1) as f64;
+
+    // A peak that failed its confirmation doesn't count, nor does anything above it
+    let max_sustainable_tps = results
+        .iter()
+        .filter(|r| r.metrics.success_rate > SUSTAINABLE_SUCCESS_RATE)
+        .map(|r| r.metrics.target_tps)
+        .filter(|tps| {
+            peak_verification
+                .as_ref()
+                .is_none_or(|v| v.passed || *tps < v.target_tps)
+        })
+        .max()
+        .unwrap_or(0);
+
+    let payload_limits = args.grow.map(|growth| {
+        let first_failing = results.iter().find(|r| {
+            r.payload_size.is_some() && r.metrics.success_rate <= SUSTAINABLE_SUCCESS_RATE
+        });
+        PayloadLimits {
+            growth,
+            max_passing_size: results
+                .iter()
+                .filter(|r| r.metrics.success_rate > SUSTAINABLE_SUCCESS_RATE)
+                .filter_map(|r| r.payload_size)
+                .max(),
+            first_failing_size: first_failing.and_then(|r| r.payload_size),
+            failing_errors: first_failing.map(|r| r.error_breakdown.clone()),
+        }
+    });
+
+    // Each criterion on its own, the peak verification only confirms the execute ramp
+    let criteria = if on_chain_criteria {
+        success_criteria
+            .iter()
+            .enumerate()
+            .map(|(k, criterion)| {
+                let rates: Vec<(u32, f64)> = results
+                    .iter()
+                    .filter_map(|r| {
+                        r.criteria
+                            .get(k)
+                            .map(|m| (r.metrics.target_tps, m.success_rate))
+                    })
+                    .collect();
+                CriterionSummary {
+                    criterion: *criterion,
+                    max_sustainable_tps: rates
+                        .iter()
+                        .filter(|(_, rate)| *rate > SUSTAINABLE_SUCCESS_RATE)
+                        .map(|(tps, _)| *tps)
+                        .max()
+                        .unwrap_or(0),
+                    overall_success_rate: rates.iter().map(|(_, rate)| rate).sum::<f64>()
+                        / rates.len().max(1) as f64,
+                }
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let results = StressTestResults {
+        label,
+        run_tag,
+        shard,
+        paymaster,
+        started_at_unix_ms,
+        total_duration_secs: test_start.elapsed().as_secs(),
+        results,
+        summary: TestSummary {
+            max_sustainable_tps,
+            total_transactions: total_successful,
+            overall_success_rate,
+        },
+        stop_reason,
+        failover: failover.report(),
+        quarantine_events: pool.events(),
+        burst_comparison,
+        fee_comparison,
+        health: health.map(HealthMonitor::finish),
+        status_page: status_page.map(StatusMonitor::finish),
+        long_tail: watchdog.map(|w| w.events()).unwrap_or_default(),
+        payload_limits,
+        peak_verification,
+        tx_log_segments,
+        hooks,
+        heatmap: heatmap.map(HeatmapBuilder::finish),
+        throughput: throughput.finish(),
+        segments: segments.finish(test_start.elapsed()),
+        lost_transactions,
+        criteria,
+        goal_seek,
+        shuffle_seed: args.shuffle_seed.filter(|_| args.shuffle_steps),
+        duplicate_hashes: runner.unique.as_ref().map(|unique| unique.duplicates),
+        connection: None,
+        websocket: failover.websocket(),
+        ci: None,
+        region: None,
+        acceptance: None,
+        resource_guard: runner.guard.as_ref().map(Guardrails::report),
+        max_account_tps: pool.max_account_tps(),
+        payload_regression: match (calls_fit.finish(), calldata_fit.finish()) {
+            (None, None) => None,
+            (calls, calldata_felts) => Some(PayloadRegression {
+                calls,
+                calldata_felts,
+            }),
+        },
+    };
+    if let Some(db) = &mut db {
+        db.finish_run(&results)?;
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_take_a_unit_or_default_to_seconds() {
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration("2m"), Ok(Duration::from_secs(120)));
+        assert_eq!(parse_duration(" 3 "), Ok(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn invalid_durations_are_rejected() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("fast").is_err());
+        assert!(parse_duration("2h").is_err());
+        assert!(parse_duration("-1s").is_err());
+    }
+
+    fn step(target_tps: u32, p99_latency_ms: f64) -> Metrics {
+        Metrics {
+            target_tps,
+            success_rate: 1.0,
+            p99_latency_ms,
+            ..Default::default()
+        }
+    }
+
+    // Latency under the target up to `capacity` TPS and far over it past that
+    fn seek(max_tps: u32, capacity: u32) -> GoalSeek {
+        let mut seeker = GoalSeeker::new(500.0, max_tps);
+        while let Some(tps) = seeker.next() {
+            let latency = if tps <= capacity { 200.0 } else { 2000.0 };
+            seeker.record(&step(tps, latency));
+        }
+        seeker.finish(max_tps)
+    }
+
+    #[test]
+    fn goal_seek_converges_on_the_highest_rate_meeting_the_target() {
+        let seek = seek(100, 37);
+        assert_eq!(seek.equilibrium_tps, 37);
+        assert_eq!(seek.lowest_missed_tps, Some(38));
+        assert!(seek.converged);
+    }
+
+    #[test]
+    fn goal_seek_reaches_the_ends_of_the_range() {
+        let seek_all = seek(100, 100);
+        assert_eq!(seek_all.equilibrium_tps, 100);
+        assert_eq!(seek_all.lowest_missed_tps, None);
+
+        let seek_none = seek(100, 0);
+        assert_eq!(seek_none.equilibrium_tps, 0);
+        assert_eq!(seek_none.lowest_missed_tps, Some(1));
+    }
+
+    #[test]
+    fn failing_transactions_miss_the_goal() {
+        let mut seeker = GoalSeeker::new(500.0, 10);
+        let tps = seeker.next().unwrap();
+        seeker.record(&Metrics {
+            success_rate: 0.5,
+            ..step(tps, 100.0)
+        });
+        assert_eq!(seeker.next(), Some(tps / 2));
+        assert!(!seeker.finish(10).converged);
+    }
+}
@@ -0,0 +1,271 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use starknet::core::types::{
+    Felt, InvokeTransaction, Transaction, TransactionExecutionStatus, TransactionStatus,
+};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::{sleep, Instant};
+
+use crate::sketch::LatencySketch;
+use crate::types::{CriterionMetrics, LostTransaction, ReceiptStatus};
+
+const POLL_PERIOD: Duration = Duration::from_secs(2);
+
+// What counts as a successful transaction, every criterion given gets its own success
+// rate and latencies
+#[derive(ValueEnum, Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SuccessCriterion {
+    /// The paymaster's execute call returned a hash, what the step metrics count
+    Execute,
+    /// The node knows every leg, in any state
+    Received,
+    /// Every leg made it into a block, reverted or not
+    Accepted,
+    /// Every leg made it into a block and none reverted
+    Succeeded,
+}
+
+// On-chain outcome of one executed transaction, as the latency from its send to the
+// point it met each criterion. Times past the node are known to the poll period
+pub struct Inclusion {
+    pub step: u32,
+    executed: Duration,
+    received: Option<Duration>,
+    accepted: Option<Duration>,
+    succeeded: Option<Duration>,
+}
+
+impl Inclusion {
+    // None when the transaction doesn't meet `criterion`
+    pub fn latency(&self, criterion: SuccessCriterion) -> Option<Duration> {
+        match criterion {
+            SuccessCriterion::Execute => Some(self.executed),
+            SuccessCriterion::Received => self.received,
+            SuccessCriterion::Accepted => self.accepted,
+            SuccessCriterion::Succeeded => self.succeeded,
+        }
+    }
+}
+
+// Follows every hash the paymaster returned until the node knows the transaction, and
+// until it is in a block when a criterion asks for it. Hashes still unknown after
+// `lost_after` are reported as lost: the paymaster said it submitted them, yet they never
+// made it anywhere on-chain
+pub struct ReceiptTracker {
+    provider: Arc<JsonRpcClient<HttpTransport>>,
+    lost_after: Option<Duration>,
+    // Time a transaction has to meet the on-chain criteria, None without any
+    inclusion_timeout: Option<Duration>,
+    follow_acceptance: bool,
+    checks: JoinSet<(Option<LostTransaction>, Inclusion)>,
+}
+
+impl ReceiptTracker {
+    // None when neither lost transactions nor on-chain criteria are asked for
+    pub fn new(
+        provider: Arc<JsonRpcClient<HttpTransport>>,
+        lost_after: Option<Duration>,
+        criteria: &[SuccessCriterion],
+        inclusion_timeout: Duration,
+    ) -> Option<ReceiptTracker> {
+        let on_chain = criteria.iter().any(|c| *c != SuccessCriterion::Execute);
+        if lost_after.is_none() && !on_chain {
+            return None;
+        }
+        Some(ReceiptTracker {
+            provider,
+            lost_after,
+            inclusion_timeout: on_chain.then_some(inclusion_timeout),
+            follow_acceptance: criteria
+                .iter()
+                .any(|c| matches!(c, SuccessCriterion::Accepted | SuccessCriterion::Succeeded)),
+            checks: JoinSet::new(),
+        })
+    }
+
+    // Hashes of all the legs of one scenario transaction, sent at `sent` and executed at
+    // `executed_at` into the run. It is lost if any of them is
+    pub fn track(
+        &mut self,
+        step: u32,
+        account: Felt,
+        hashes: Vec<Felt>,
+        sent: Instant,
+        executed_at: Duration,
+    ) {
+        let provider = Arc::clone(&self.provider);
+        let lost_after = self.lost_after;
+        let inclusion_timeout = self.inclusion_timeout;
+        let follow_acceptance = self.follow_acceptance;
+        let executed = sent.elapsed();
+        let horizon = lost_after.max(inclusion_timeout).unwrap_or_default();
+        let deadline = Instant::now() + horizon;
+        self.checks.spawn(async move {
+            let mut seen: Vec<Option<Duration>> = vec![None; hashes.len()];
+            let mut status: Vec<Option<ReceiptStatus>> = vec![None; hashes.len()];
+            let mut accepted = None;
+            loop {
+                // Any status, rejected and reverted included, means the transaction
+                // was seen. RPC errors count as not seen and are retried until the deadline
+                for (i, hash) in hashes.iter().enumerate() {
+                    if status[i].is_some() || (seen[i].is_some() && !follow_acceptance) {
+                        continue;
+                    }
+                    let Ok(current) = provider.get_transaction_status(*hash).await else {
+                        continue;
+                    };
+                    seen[i].get_or_insert(sent.elapsed());
+                    status[i] = receipt_status(current);
+                }
+                let in_block = |s: &Option<ReceiptStatus>| {
+                    matches!(s, Some(ReceiptStatus::Succeeded | ReceiptStatus::Reverted))
+                };
+                if accepted.is_none() && status.iter().all(in_block) {
+                    accepted = Some(sent.elapsed());
+                }
+                let done = if follow_acceptance {
+                    status.iter().all(Option::is_some)
+                } else {
+                    seen.iter().all(Option::is_some)
+                };
+                if done || Instant::now() >= deadline {
+                    break;
+                }
+                sleep(POLL_PERIOD).await;
+            }
+
+            let lost = lost_after.and_then(|after| {
+                let unseen: Vec<Felt> = hashes
+                    .iter()
+                    .zip(&seen)
+                    .filter(|(_, seen)| seen.is_none_or(|at| at > executed + after))
+                    .map(|(hash, _)| *hash)
+                    .collect();
+                (!unseen.is_empty()).then_some(LostTransaction {
+                    step,
+                    account,
+                    transaction_hashes: unseen,
+                    executed_at_secs: executed_at.as_secs_f64(),
+                })
+            });
+            let within = |at: Option<Duration>| {
+                at.filter(|at| inclusion_timeout.is_some_and(|timeout| *at <= timeout))
+            };
+            let received = within(
+                seen.iter()
+                    .copied()
+                    .collect::<Option<Vec<_>>>()
+                    .and_then(|seen| seen.into_iter().max()),
+            );
+            let accepted = within(accepted);
+            let reverted = status.contains(&Some(ReceiptStatus::Reverted));
+            let inclusion = Inclusion {
+                step,
+                executed,
+                received,
+                accepted,
+                succeeded: accepted.filter(|_| !reverted),
+            };
+            (lost, inclusion)
+        });
+    }
+
+    // Waits for the checks still running, at most the longer of the two horizons
+    pub async fn finish(mut self) -> (Vec<LostTransaction>, Vec<Inclusion>) {
+        let mut lost = Vec::new();
+        let mut inclusions = Vec::new();
+        while let Some(check) = self.checks.join_next().await {
+            if let Ok((transaction, inclusion)) = check {
+                lost.extend(transaction);
+                inclusions.push(inclusion);
+            }
+        }
+        lost.sort_by(|a, b| a.executed_at_secs.total_cmp(&b.executed_at_secs));
+        (lost, inclusions)
+    }
+}
+
+// Success rate and latencies of a step of `total` transactions under `criterion`, from
+// the inclusions of its executed ones
+pub fn criterion_metrics(
+    criterion: SuccessCriterion,
+    total: u32,
+    inclusions: &[&Inclusion],
+    accuracy: f64,
+) -> CriterionMetrics {
+    let mut latencies = LatencySketch::new(accuracy);
+    let mut successful = 0;
+    for latency in inclusions.iter().filter_map(|i| i.latency(criterion)) {
+        successful += 1;
+        latencies.add(latency.as_secs_f64() * 1000.0);
+    }
+    CriterionMetrics {
+        criterion,
+        successful_txs: successful,
+        success_rate: if total > 0 {
+            successful as f64 / total as f64
+        } else {
+            0.0
+        },
+        avg_latency_ms: latencies.mean(),
+        p50_latency_ms: latencies.quantile(0.50),
+        p95_latency_ms: latencies.quantile(0.95),
+        p99_latency_ms: latencies.quantile(0.99),
+    }
+}
+
+// Accepted and rejected transactions are final, None while the node only received it
+fn receipt_status(status: TransactionStatus) -> Option<ReceiptStatus> {
+    match status {
+        TransactionStatus::AcceptedOnL2(status) | TransactionStatus::AcceptedOnL1(status) => {
+            Some(match status {
+                TransactionExecutionStatus::Succeeded => ReceiptStatus::Succeeded,
+                TransactionExecutionStatus::Reverted => ReceiptStatus::Reverted,
+            })
+        }
+        TransactionStatus::Rejected => Some(ReceiptStatus::Rejected),
+        TransactionStatus::Received => None,
+    }
+}
+
+// Polls the status of one transaction until it is accepted or rejected, or `timeout` passed
+pub async fn wait_for_receipt(
+    provider: &JsonRpcClient<HttpTransport>,
+    transaction_hash: Felt,
+    timeout: Duration,
+) -> ReceiptStatus {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let status = provider.get_transaction_status(transaction_hash).await;
+        if let Some(status) = status.ok().and_then(receipt_status) {
+            return status;
+        }
+        if Instant::now() >= deadline {
+            return ReceiptStatus::TimedOut;
+        }
+        sleep(POLL_PERIOD).await;
+    }
+}
+
+// Account that submitted the transaction, for a paymaster transaction its relayer. None
+// when the node doesn't know the transaction
+pub async fn relayer_of(
+    provider: &JsonRpcClient<HttpTransport>,
+    transaction_hash: Felt,
+) -> Option<Felt> {
+    match provider
+        .get_transaction_by_hash(transaction_hash)
+        .await
+        .ok()?
+    {
+        Transaction::Invoke(InvokeTransaction::V0(invoke)) => Some(invoke.contract_address),
+        Transaction::Invoke(InvokeTransaction::V1(invoke)) => Some(invoke.sender_address),
+        Transaction::Invoke(InvokeTransaction::V3(invoke)) => Some(invoke.sender_address),
+        _ => None,
+    }
+}
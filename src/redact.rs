@@ -0,0 +1,161 @@
+use serde_json::Value;
+use starknet::core::types::Felt;
+use std::collections::HashMap;
+
+// Fields holding an account address, a transaction hash or a tracking id, named the same
+// in the results and in the requests and responses of traced transactions
+const PSEUDONYMS: &[(&str, &str)] = &[
+    ("account", "account"),
+    ("accounts", "account"),
+    ("address", "account"),
+    ("user_address", "account"),
+    ("transaction_hash", "tx"),
+    ("transaction_hashes", "tx"),
+    ("relayer_sample", "tx"),
+    ("tracking_id", "tracking"),
+    ("tracking_ids", "tracking"),
+];
+
+// Fields only their length is kept of, the calls and signatures of a transaction tell
+// what its account did
+const STRIPPED: &[&str] = &["calldata", "signature"];
+
+// Replaces the account addresses, transaction hashes and tracking ids of the results with
+// pseudonyms like `account-3`, and calldata and signatures with their length. A value gets
+// the same pseudonym wherever it shows up, typed data of traces included, so the results
+// still tell which transactions came from the same account
+pub fn redact(results: &mut Value) {
+    let mut pseudonyms = Pseudonyms::default();
+    pseudonyms.collect(results);
+    pseudonyms.replace(results);
+}
+
+#[derive(Default)]
+struct Pseudonyms {
+    names: HashMap<Felt, String>,
+    counts: HashMap<&'static str, u32>,
+}
+
+impl Pseudonyms {
+    // Names the values of pseudonymized fields in the order they first appear
+    fn collect(&mut self, value: &Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    let kind = PSEUDONYMS
+                        .iter()
+                        .find(|(field, _)| key.eq_ignore_ascii_case(field))
+                        .map(|(_, kind)| *kind);
+                    if let Some(kind) = kind {
+                        match value {
+                            Value::Array(values) => values.iter().for_each(|v| self.add(kind, v)),
+                            value => self.add(kind, value),
+                        }
+                    }
+                    self.collect(value);
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|v| self.collect(v)),
+            _ => {}
+        }
+    }
+
+    fn add(&mut self, kind: &'static str, value: &Value) {
+        let Some(felt) = as_felt(value) else {
+            return;
+        };
+        if self.names.contains_key(&felt) {
+            return;
+        }
+        let count = self.counts.entry(kind).or_default();
+        *count += 1;
+        self.names.insert(felt, format!("{}-{}", kind, count));
+    }
+
+    fn replace(&self, value: &mut Value) {
+        match value {
+            Value::Object(fields) => {
+                for (key, value) in fields.iter_mut() {
+                    if STRIPPED.iter().any(|field| key.eq_ignore_ascii_case(field)) {
+                        if let Value::Array(values) = value {
+                            *value = Value::String(format!("{} felt(s) redacted", values.len()));
+                            continue;
+                        }
+                    }
+                    self.replace(value);
+                }
+            }
+            Value::Array(values) => values.iter_mut().for_each(|v| self.replace(v)),
+            Value::String(_) => {
+                if let Some(name) = as_felt(value).and_then(|felt| self.names.get(&felt)) {
+                    *value = Value::String(name.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Hex strings only, padded or not, a decimal number is never an address or a hash
+fn as_felt(value: &Value) -> Option<Felt> {
+    let raw = value.as_str()?;
+    if !raw.starts_with("0x") {
+        return None;
+    }
+    Felt::from_hex(raw).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn values_get_the_same_pseudonym_everywhere() {
+        let mut results = json!({
+            "accounts": ["0xa", "0xb"],
+            "results": [
+                {"account": "0x0b", "transaction_hash": "0x123"},
+                {"account": "0xa", "transaction_hash": "0x456"},
+            ],
+            "trace": {"typed_data": {"user_address": "0x000a", "note": "0x123"}},
+        });
+        redact(&mut results);
+        assert_eq!(
+            results,
+            json!({
+                "accounts": ["account-1", "account-2"],
+                "results": [
+                    {"account": "account-2", "transaction_hash": "tx-1"},
+                    {"account": "account-1", "transaction_hash": "tx-2"},
+                ],
+                "trace": {"typed_data": {"user_address": "account-1", "note": "tx-1"}},
+            })
+        );
+    }
+
+    #[test]
+    fn calldata_and_signatures_keep_only_their_length() {
+        let mut results = json!({
+            "calls": [{"to": "0x1", "calldata": ["0x1", "0x2", "0x3"]}],
+            "Signature": ["0x4", "0x5"],
+        });
+        redact(&mut results);
+        assert_eq!(results["calls"][0]["calldata"], "3 felt(s) redacted");
+        assert_eq!(results["Signature"], "2 felt(s) redacted");
+        assert_eq!(results["calls"][0]["to"], "0x1");
+    }
+
+    #[test]
+    fn other_values_are_left_alone() {
+        let mut results = json!({
+            "account": "12",
+            "tracking_id": 7,
+            "target_tps": 10,
+            "error": "nonce too low",
+        });
+        let expected = results.clone();
+        redact(&mut results);
+        assert_eq!(results, expected);
+    }
+}
@@ -0,0 +1,176 @@
+use clap::Args;
+use paymaster_rpc::{
+    BuildTransactionRequest, ExecutionParameters, FeeMode, InvokeParameters, TransactionParameters,
+};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::account::{load_accounts, Account, AccountArgs};
+use crate::client::{build_client, ClientArgs};
+use crate::scenario::Leg;
+use crate::transaction::{run_legs, Fee, Pipeline};
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Capture id of a transaction of the --trace-sample, as listed in the results file
+    pub capture_id: String,
+
+    /// Results file of the run the transaction was captured in
+    #[arg(long)]
+    pub results: PathBuf,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// JSON file of `{address, private_key}` entries, e.g. the --accounts of the run. The
+    /// transaction is signed by whichever of them sent it
+    #[arg(long)]
+    pub accounts: Option<PathBuf>,
+
+    /// Second account used by multi-account scenarios, its key is read from SPENDER_PRIVATE_KEY
+    #[arg(long)]
+    pub spender_address: Option<String>,
+
+    /// Report with every request and response, the same as printed
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Sends a traced transaction of a past run once more, leg by leg, and prints every request
+// and response. Typed data and signatures of the capture are long stale, each leg is built
+// again from its original build request and signed with the key of its sender
+pub async fn replay_tx(mut args: ReplayArgs) -> Result<ReplayReport, TestError> {
+    let run: Value = serde_json::from_str(&fs::read_to_string(&args.results)?)?;
+    let trace = run["results"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|step| step["traces"].as_array())
+        .flatten()
+        .find(|trace| trace["capture_id"].as_str() == Some(args.capture_id.as_str()))
+        .ok_or_else(|| {
+            format!(
+                "no transaction {} in {}, only --trace-sample transactions are captured",
+                args.capture_id,
+                args.results.display()
+            )
+        })?;
+
+    // Retries of a leg repeat its build request
+    let mut requests: Vec<&Value> = Vec::new();
+    for leg in trace["legs"].as_array().into_iter().flatten() {
+        let request = &leg["build_request"];
+        if !request.is_null() && requests.last() != Some(&request) {
+            requests.push(request);
+        }
+    }
+    if requests.is_empty() {
+        return Err(format!("transaction {} has no build request", args.capture_id).into());
+    }
+
+    let config = envy::from_env::<Config>()?;
+    let mut signers = match &args.accounts {
+        Some(path) => load_accounts(path)?,
+        None => vec![args.account.resolve(&config.private_key)?],
+    };
+    if let (Some(address), Some(key)) = (&args.spender_address, &config.spender_private_key) {
+        signers.push(Account::new(address, key)?);
+    }
+
+    let original_error = trace["error"].as_str().map(str::to_string);
+    // Sent along with the requests, to find the replay in the paymaster's logs
+    let run_tag = args.client.tag_run();
+    println!(
+        "Replaying {} ({} leg(s)) as run {}, it failed with {}",
+        args.capture_id,
+        requests.len(),
+        run_tag,
+        original_error.as_deref().unwrap_or("no error")
+    );
+    let client = build_client(&args.client).await?;
+    let pipeline = Pipeline::default();
+    let mut latency_ms = 0.0;
+    let mut error = None;
+    let mut error_message = None;
+    let mut transaction_hashes = Vec::new();
+    let mut legs = Vec::new();
+    for (i, request) in requests.into_iter().enumerate() {
+        let request: BuildTransactionRequest = serde_json::from_value(request.clone())?;
+        let TransactionParameters::Invoke {
+            invoke:
+                InvokeParameters {
+                    user_address,
+                    calls,
+                },
+        } = request.transaction
+        else {
+            return Err(format!("leg {} is not an invoke", i + 1).into());
+        };
+        let ExecutionParameters::V1 { fee_mode, .. } = request.parameters;
+        let fee = match fee_mode {
+            FeeMode::Sponsored => Fee::Sponsored,
+            FeeMode::Default { gas_token } => Fee::GasToken(gas_token),
+        };
+        let account = signers
+            .iter()
+            .find(|account| account.address == user_address)
+            .ok_or_else(|| {
+                format!(
+                    "no key for {:#x}, pass the run's --accounts or --spender-address",
+                    user_address
+                )
+            })?;
+
+        let sent = run_legs(
+            &client,
+            &pipeline,
+            std::slice::from_ref(account),
+            vec![Leg { account: 0, calls }],
+            fee,
+            false,
+            false,
+            true,
+        )
+        .await;
+        println!("\nleg {} from {:#x}", i + 1, user_address);
+        for attempt in &sent.trace {
+            println!("{}", serde_json::to_string_pretty(attempt)?);
+        }
+        legs.extend(sent.trace);
+        transaction_hashes.extend(sent.transaction_hashes);
+        match sent.result {
+            Ok(latency) => latency_ms += latency,
+            Err(e) => {
+                error = Some(e);
+                error_message = sent.error_detail.map(|detail| detail.message);
+                break;
+            }
+        }
+    }
+
+    println!(
+        "\n{}",
+        match (&error, &error_message) {
+            (Some(_), Some(message)) => format!("Failed again: {}", message),
+            (Some(e), None) => format!("Failed again: {}", e.name()),
+            (None, _) => format!("Went through this time in {:.0} ms", latency_ms),
+        }
+    );
+    Ok(ReplayReport {
+        capture_id: args.capture_id,
+        results_file: args.results,
+        original_error,
+        latency_ms: error.is_none().then_some(latency_ms),
+        reproduced: error.is_some(),
+        error,
+        error_message,
+        transaction_hashes,
+        legs,
+    })
+}
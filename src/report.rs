@@ -0,0 +1,473 @@
+use clap::ValueEnum;
+use serde_json::{json, Map, Value};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use crate::types::StressTestResults;
+use crate::TestError;
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum ReportFormat {
+    Html,
+    Md,
+    Csv,
+    /// One vegeta `report -type=json` object per step, one per line, like a vegeta attack
+    /// run at each rate
+    Vegeta,
+    /// k6 `--summary-export` JSON, every step as a submetric tagged with its target TPS
+    K6,
+}
+
+const COLUMNS: [&str; 13] = [
+    "step",
+    "target_tps",
+    "effective_tps",
+    "users",
+    "burst_size",
+    "payload_size",
+    "total",
+    "succeeded",
+    "success_rate",
+    "avg_ms",
+    "p50_ms",
+    "p95_ms",
+    "p99_ms",
+];
+
+// Renders a stored result file, reading it as plain JSON so files written by older
+// versions of the tool can still be rendered
+pub fn render(path: &Path, format: ReportFormat) -> Result<String, TestError> {
+    let run: Value = serde_json::from_str(&fs::read_to_string(path)?)?;
+    if !run["results"].is_array() {
+        return Err(format!("{} is not a result file", path.display()).into());
+    }
+    render_run(&run, format)
+}
+
+// Same from results already in memory, in their JSON form
+pub fn render_run(run: &Value, format: ReportFormat) -> Result<String, TestError> {
+    let steps = run["results"].as_array().ok_or("not a result file")?;
+    match format {
+        ReportFormat::Vegeta => return vegeta(steps),
+        ReportFormat::K6 => return k6(run, steps),
+        _ => {}
+    }
+    let rows: Vec<Vec<String>> = steps.iter().enumerate().map(|(i, r)| row(i, r)).collect();
+    let summary = &run["summary"];
+    let title = run["label"].as_str().unwrap_or("Paymaster stress test");
+    let facts = [
+        ("Duration (s)", cell(&run["total_duration_secs"])),
+        ("Max sustainable TPS", cell(&summary["max_sustainable_tps"])),
+        (
+            "Successful transactions",
+            cell(&summary["total_transactions"]),
+        ),
+        (
+            "Overall success rate",
+            cell(&summary["overall_success_rate"]),
+        ),
+    ];
+
+    let mut out = String::new();
+    match format {
+        ReportFormat::Vegeta | ReportFormat::K6 => {}
+        ReportFormat::Csv => {
+            writeln!(out, "{}", COLUMNS.join(","))?;
+            for row in &rows {
+                writeln!(out, "{}", row.join(","))?;
+            }
+        }
+        ReportFormat::Md => {
+            writeln!(out, "# {}\n", title)?;
+            for (name, value) in &facts {
+                writeln!(out, "- {}: {}", name, value)?;
+            }
+            writeln!(out, "\n| {} |", COLUMNS.join(" | "))?;
+            writeln!(out, "|{}", "---|".repeat(COLUMNS.len()))?;
+            for row in &rows {
+                writeln!(out, "| {} |", row.join(" | "))?;
+            }
+        }
+        ReportFormat::Html => {
+            writeln!(out, "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">")?;
+            writeln!(out, "<title>{}</title>", escape(title))?;
+            writeln!(
+                out,
+                "<style>body{{font-family:sans-serif}}td,th{{padding:4px 8px;text-align:right}}</style>"
+            )?;
+            writeln!(out, "</head><body>\n<h1>{}</h1>\n<ul>", escape(title))?;
+            for (name, value) in &facts {
+                writeln!(out, "<li>{}: {}</li>", name, escape(value))?;
+            }
+            writeln!(out, "</ul>\n<table>\n<tr>")?;
+            for column in COLUMNS {
+                write!(out, "<th>{}</th>", column)?;
+            }
+            writeln!(out, "</tr>")?;
+            for row in &rows {
+                write!(out, "<tr>")?;
+                for value in row {
+                    write!(out, "<td>{}</td>", escape(value))?;
+                }
+                writeln!(out, "</tr>")?;
+            }
+            writeln!(out, "</table>\n</body></html>")?;
+        }
+    }
+    Ok(out)
+}
+
+// Conclusion of a run for the terminal, one line per step
+pub fn summary(results: &StressTestResults) -> Result<String, TestError> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "\n{:>4} {:>8} {:>9} {:>13} {:>10} {:>11} {:>7}  top error",
+        "step", "tps", "success", "95% ci", "p95 ms", "co p95 ms", "queue"
+    )?;
+    for (i, result) in results.results.iter().enumerate() {
+        let metrics = &result.metrics;
+        let rate = match result.users {
+            Some(users) => format!("{}u", users),
+            None => metrics.target_tps.to_string(),
+        };
+        writeln!(
+            out,
+            "{:>4} {:>8} {:>8.1}% {:>13} {:>10.1} {:>11.1} {:>7}  {}",
+            i + 1,
+            rate,
+            metrics.success_rate * 100.0,
+            format!(
+                "{:.1}-{:.1}%",
+                metrics.success_rate_ci_low * 100.0,
+                metrics.success_rate_ci_high * 100.0
+            ),
+            metrics.p95_latency_ms,
+            metrics.corrected_p95_latency_ms,
+            result
+                .queue
+                .as_ref()
+                .map(|q| format!("{:.1}", q.queue_depth))
+                .unwrap_or_else(|| "-".to_string()),
+            match result.error_breakdown.top() {
+                Some((name, count)) => format!("{} ({})", name, count),
+                None => "-".to_string(),
+            }
+        )?;
+    }
+    if results
+        .results
+        .iter()
+        .any(|result| result.metrics.server_timed_txs > 0)
+    {
+        writeln!(
+            out,
+            "\n{:>4} {:>8} {:>14} {:>14} {:>15} {:>15}",
+            "step", "timed", "server p50 ms", "server p95 ms", "network p50 ms", "network p95 ms"
+        )?;
+        for (i, result) in results.results.iter().enumerate() {
+            let metrics = &result.metrics;
+            writeln!(
+                out,
+                "{:>4} {:>8} {:>14.1} {:>14.1} {:>15.1} {:>15.1}",
+                i + 1,
+                metrics.server_timed_txs,
+                metrics.server_p50_ms,
+                metrics.server_p95_ms,
+                metrics.network_p50_ms,
+                metrics.network_p95_ms
+            )?;
+        }
+    }
+    if !results.segments.is_empty() {
+        writeln!(
+            out,
+            "\n{:>7} {:>9} {:>9} {:>10}  top error",
+            "segment", "tps", "success", "p95 ms"
+        )?;
+        for segment in &results.segments {
+            writeln!(
+                out,
+                "{:>7} {:>9.1} {:>8.1}% {:>10.1}  {}",
+                segment.index,
+                segment.effective_tps,
+                segment.success_rate * 100.0,
+                segment.p95_latency_ms,
+                segment.top_error.as_deref().unwrap_or("-")
+            )?;
+        }
+    }
+    writeln!(
+        out,
+        "\nMax sustainable TPS: {}, stopped: {:?}",
+        results.summary.max_sustainable_tps, results.stop_reason
+    )?;
+    if let Some(duplicates) = results.duplicate_hashes.filter(|d| *d > 0) {
+        writeln!(
+            out,
+            "Duplicates: {} transaction hash(es) returned for more than one transaction",
+            duplicates
+        )?;
+    }
+    if let Some(regression) = &results.payload_regression {
+        for (fit, unit) in [
+            (&regression.calls, "call"),
+            (&regression.calldata_felts, "calldata felt"),
+        ] {
+            if let Some(fit) = fit {
+                writeln!(
+                    out,
+                    "Payload: each additional {} adds ~{:.2} ms (r² {:.2}, {} to {} over {} txs)",
+                    unit, fit.ms_per_unit, fit.r_squared, fit.min_size, fit.max_size, fit.samples
+                )?;
+            }
+        }
+    }
+    if let Some(status) = results
+        .status_page
+        .as_ref()
+        .filter(|s| s.samples.len() > 1 || s.failed_fetches > 0)
+    {
+        writeln!(
+            out,
+            "Status page: changed {} time(s) during the run, {} of {} fetch(es) failed",
+            status.samples.len().saturating_sub(1),
+            status.failed_fetches,
+            status.fetches
+        )?;
+    }
+    if let Some(max_account_tps) = results.max_account_tps {
+        let throttled: u32 = results
+            .results
+            .iter()
+            .map(|result| result.metrics.throttled_txs)
+            .sum();
+        writeln!(
+            out,
+            "Fair use: {} transaction(s) not sent to keep every account at {} TPS or below",
+            throttled, max_account_tps
+        )?;
+    }
+    if let Some(guard) = results
+        .resource_guard
+        .as_ref()
+        .filter(|g| g.shed || g.aborted)
+    {
+        writeln!(
+            out,
+            "Guardrails: {} near the generator's limits (peak memory {:.0}%, file descriptors {:.0}%)",
+            if guard.aborted {
+                "stopped the run"
+            } else {
+                "shed load"
+            },
+            guard.peak_memory_share * 100.0,
+            guard.peak_fd_share * 100.0
+        )?;
+    }
+    if let Some(websocket) = &results.websocket {
+        writeln!(
+            out,
+            "WebSocket: {} reconnect(s), up to {} requests pending on the socket",
+            websocket.reconnects, websocket.max_pending_requests
+        )?;
+    }
+    for summary in &results.criteria {
+        writeln!(
+            out,
+            "  {:?}: max sustainable TPS {}, success {:.1}%",
+            summary.criterion,
+            summary.max_sustainable_tps,
+            summary.overall_success_rate * 100.0
+        )?;
+    }
+    Ok(out)
+}
+
+// Vegeta reports latencies in nanoseconds. Quantiles the results don't keep, 90th, min
+// and max, are left out. Latencies are of the successful transactions, the total is
+// their exact sum as the mean of the step is kept exactly
+fn vegeta(steps: &[Value]) -> Result<String, TestError> {
+    let ns = |ms: &Value| (ms.as_f64().unwrap_or_default() * 1e6) as u64;
+    let mut out = String::new();
+    for step in steps {
+        let metrics = &step["metrics"];
+        let requests = metrics["total_txs"].as_u64().unwrap_or_default();
+        let successful = metrics["successful_txs"].as_f64().unwrap_or_default();
+        let latency_sum_ms = metrics["avg_latency_ms"].as_f64().unwrap_or_default() * successful;
+        let offered_tps = metrics["offered_tps"].as_f64().unwrap_or_default();
+        let duration_secs = if offered_tps > 0.0 {
+            requests as f64 / offered_tps
+        } else {
+            0.0
+        };
+        let errors = step["error_breakdown"]
+            .as_object()
+            .map(|breakdown| {
+                breakdown
+                    .iter()
+                    .filter(|(_, count)| count.as_u64().is_some_and(|n| n > 0))
+                    .map(|(kind, _)| kind.clone())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let report = json!({
+            "latencies": {
+                "total": (latency_sum_ms * 1e6) as u64,
+                "mean": ns(&metrics["avg_latency_ms"]),
+                "50th": ns(&metrics["p50_latency_ms"]),
+                "95th": ns(&metrics["p95_latency_ms"]),
+                "99th": ns(&metrics["p99_latency_ms"]),
+            },
+            "duration": (duration_secs * 1e9) as u64,
+            "requests": requests,
+            "rate": offered_tps,
+            "throughput": metrics["effective_tps"],
+            "success": metrics["success_rate"],
+            "errors": errors,
+        });
+        writeln!(out, "{}", serde_json::to_string(&report)?)?;
+    }
+    Ok(out)
+}
+
+// Transactions stand in for k6's HTTP requests. Quantiles can't be merged across steps,
+// the run-wide duration only has the average, of the successful transactions like the
+// averages of the steps, and the steps carry the rest
+fn k6(run: &Value, steps: &[Value]) -> Result<String, TestError> {
+    let field = |step: &Value, name: &str| step["metrics"][name].as_f64().unwrap_or_default();
+    let total: f64 = steps.iter().map(|s| field(s, "total_txs")).sum();
+    let failed: f64 = steps.iter().map(|s| field(s, "failed_txs")).sum();
+    let successful: f64 = steps.iter().map(|s| field(s, "successful_txs")).sum();
+    let latency_sum: f64 = steps
+        .iter()
+        .map(|s| field(s, "avg_latency_ms") * field(s, "successful_txs"))
+        .sum();
+    let duration_secs = run["total_duration_secs"].as_f64().unwrap_or_default();
+
+    let mut metrics = Map::new();
+    metrics.insert(
+        "http_reqs".to_string(),
+        json!({
+            "count": total as u64,
+            "rate": if duration_secs > 0.0 { total / duration_secs } else { 0.0 },
+        }),
+    );
+    metrics.insert(
+        "http_req_failed".to_string(),
+        json!({
+            "passes": failed as u64,
+            "fails": (total - failed) as u64,
+            "value": if total > 0.0 { failed / total } else { 0.0 },
+        }),
+    );
+    metrics.insert(
+        "http_req_duration".to_string(),
+        json!({ "avg": if successful > 0.0 { latency_sum / successful } else { 0.0 } }),
+    );
+    // Comparison runs repeat a rate, the step number keeps their submetrics apart
+    for (i, step) in steps.iter().enumerate() {
+        let tag = format!(
+            "{{step:{},target_tps:{}}}",
+            i + 1,
+            step["metrics"]["target_tps"]
+        );
+        metrics.insert(
+            format!("http_reqs{}", tag),
+            json!({
+                "count": field(step, "total_txs") as u64,
+                "rate": field(step, "offered_tps"),
+            }),
+        );
+        metrics.insert(
+            format!("http_req_duration{}", tag),
+            json!({
+                "avg": field(step, "avg_latency_ms"),
+                "med": field(step, "p50_latency_ms"),
+                "p(95)": field(step, "p95_latency_ms"),
+                "p(99)": field(step, "p99_latency_ms"),
+            }),
+        );
+    }
+    Ok(serde_json::to_string_pretty(&json!({ "metrics": metrics }))? + "\n")
+}
+
+fn row(index: usize, result: &Value) -> Vec<String> {
+    let metrics = &result["metrics"];
+    vec![
+        (index + 1).to_string(),
+        cell(&metrics["target_tps"]),
+        cell(&metrics["effective_tps"]),
+        cell(&result["users"]),
+        cell(&result["burst_size"]),
+        cell(&result["payload_size"]),
+        cell(&metrics["total_txs"]),
+        cell(&metrics["successful_txs"]),
+        cell(&metrics["success_rate"]),
+        cell(&metrics["avg_latency_ms"]),
+        cell(&metrics["p50_latency_ms"]),
+        cell(&metrics["p95_latency_ms"]),
+        cell(&metrics["p99_latency_ms"]),
+    ]
+}
+
+// Missing fields render empty, floats are rounded to two decimals
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Number(n) if n.is_f64() => format!("{:.2}", n.as_f64().unwrap_or_default()),
+        Value::Number(n) => n.to_string(),
+        Value::String(s) => s.clone(),
+        _ => String::new(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vegeta_total_is_the_sum_of_successful_latencies() {
+        let step = json!({
+            "metrics": {
+                "total_txs": 4,
+                "successful_txs": 3,
+                "avg_latency_ms": 12.5,
+                "offered_tps": 2.0,
+            },
+        });
+        let report: Value = serde_json::from_str(&vegeta(&[step]).unwrap()).unwrap();
+        assert_eq!(report["latencies"]["total"], 37_500_000);
+        assert_eq!(report["latencies"]["mean"], 12_500_000);
+        assert_eq!(report["requests"], 4);
+        assert_eq!(report["duration"], 2_000_000_000u64);
+    }
+
+    #[test]
+    fn k6_average_is_weighted_by_successful_transactions() {
+        let steps = [
+            json!({"metrics": {"total_txs": 10, "successful_txs": 2, "failed_txs": 8, "avg_latency_ms": 100.0}}),
+            json!({"metrics": {"total_txs": 10, "successful_txs": 6, "failed_txs": 4, "avg_latency_ms": 20.0}}),
+        ];
+        let run = json!({"total_duration_secs": 10});
+        let report: Value = serde_json::from_str(&k6(&run, &steps).unwrap()).unwrap();
+        let metrics = &report["metrics"];
+        assert_eq!(metrics["http_req_duration"]["avg"], 40.0);
+        assert_eq!(metrics["http_reqs"]["count"], 20);
+        assert_eq!(metrics["http_req_failed"]["passes"], 12);
+    }
+
+    #[test]
+    fn k6_average_of_a_run_without_successes_is_zero() {
+        let steps =
+            [json!({"metrics": {"total_txs": 3, "successful_txs": 0, "avg_latency_ms": 0.0}})];
+        let report: Value = serde_json::from_str(&k6(&json!({}), &steps).unwrap()).unwrap();
+        assert_eq!(report["metrics"]["http_req_duration"]["avg"], 0.0);
+    }
+}
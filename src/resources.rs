@@ -0,0 +1,251 @@
+use clap::Args;
+use std::fs;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::types::{GuardReport, ResourceUsage};
+
+// Kernel clock ticks per second used by /proc/<pid>/stat, 100 on every mainstream Linux
+const CLOCK_TICKS: f64 = 100.0;
+
+// Reading the fd table of a busy process isn't free, once a second is enough to see a
+// limit coming
+const GUARD_INTERVAL: Duration = Duration::from_secs(1);
+
+// cgroup v1 reports no limit as a number close to the largest i64
+const UNLIMITED_BYTES: u64 = 1 << 60;
+
+#[derive(Args, Clone)]
+pub struct GuardArgs {
+    /// Memory the generator may use, defaults to its cgroup limit or else the machine's RAM
+    #[arg(long)]
+    pub memory_limit_mb: Option<u64>,
+
+    /// Share of the memory or file descriptor limit above which ticks send nothing, until
+    /// in-flight requests drained and usage is back under it
+    #[arg(long, default_value = "0.85", value_parser = parse_share)]
+    pub shed_at: f64,
+
+    /// Share of a limit above which the run stops and writes its results, rather than
+    /// being OOM-killed or running out of sockets
+    #[arg(long, default_value = "0.95", value_parser = parse_share)]
+    pub abort_at: f64,
+
+    /// Watch neither memory nor file descriptors
+    #[arg(long)]
+    pub no_guardrails: bool,
+}
+
+fn parse_share(raw: &str) -> Result<f64, String> {
+    match raw.parse::<f64>() {
+        Ok(share) if share > 0.0 && share <= 1.0 => Ok(share),
+        _ => Err(format!("expected a share between 0 and 1, got `{}`", raw)),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum GuardState {
+    Normal,
+    Shedding,
+    Abort,
+}
+
+// Keeps the generator's memory and file descriptors away from their limits: it sheds load
+// above --shed-at and stops the run above --abort-at. Limits that can't be read are not
+// watched
+pub struct Guardrails {
+    memory_limit_bytes: Option<u64>,
+    fd_limit: Option<u64>,
+    shed_at: f64,
+    abort_at: f64,
+    state: GuardState,
+    next_check: Instant,
+    peak_memory_share: f64,
+    peak_fd_share: f64,
+    shed: bool,
+    aborted: bool,
+}
+
+impl Guardrails {
+    pub fn new(args: &GuardArgs) -> Option<Guardrails> {
+        if args.no_guardrails {
+            return None;
+        }
+        let memory_limit_bytes = match args.memory_limit_mb {
+            Some(mb) => Some(mb * 1024 * 1024),
+            None => memory_limit(),
+        };
+        Some(Guardrails {
+            memory_limit_bytes,
+            fd_limit: fd_limit(),
+            shed_at: args.shed_at,
+            abort_at: args.abort_at,
+            state: GuardState::Normal,
+            next_check: Instant::now(),
+            peak_memory_share: 0.0,
+            peak_fd_share: 0.0,
+            shed: false,
+            aborted: false,
+        })
+    }
+
+    // State as of the last sample, a new one is taken once GUARD_INTERVAL passed
+    pub fn check(&mut self) -> GuardState {
+        if Instant::now() < self.next_check || self.aborted {
+            return self.state;
+        }
+        self.next_check = Instant::now() + GUARD_INTERVAL;
+        let memory = self
+            .memory_limit_bytes
+            .zip(status_kb("VmRSS:"))
+            .map_or(0.0, |(limit, kb)| (kb * 1024) as f64 / limit as f64);
+        let fds = self
+            .fd_limit
+            .zip(open_fds())
+            .map_or(0.0, |(limit, open)| open as f64 / limit as f64);
+        self.peak_memory_share = self.peak_memory_share.max(memory);
+        self.peak_fd_share = self.peak_fd_share.max(fds);
+        let usage = memory.max(fds);
+        let state = if usage >= self.abort_at {
+            GuardState::Abort
+        } else if usage >= self.shed_at {
+            GuardState::Shedding
+        } else {
+            GuardState::Normal
+        };
+        if state != self.state {
+            match state {
+                GuardState::Normal => println!("Resource usage back to normal, sending again"),
+                GuardState::Shedding => println!(
+                    "Memory at {:.0}% and file descriptors at {:.0}% of their limits, shedding load",
+                    memory * 100.0,
+                    fds * 100.0
+                ),
+                GuardState::Abort => println!(
+                    "Memory at {:.0}% and file descriptors at {:.0}% of their limits, stopping the run",
+                    memory * 100.0,
+                    fds * 100.0
+                ),
+            }
+        }
+        self.shed |= state == GuardState::Shedding;
+        self.aborted |= state == GuardState::Abort;
+        self.state = state;
+        state
+    }
+
+    pub fn aborted(&self) -> bool {
+        self.aborted
+    }
+
+    pub fn report(&self) -> GuardReport {
+        GuardReport {
+            memory_limit_bytes: self.memory_limit_bytes,
+            fd_limit: self.fd_limit,
+            peak_memory_share: self.peak_memory_share,
+            peak_fd_share: self.peak_fd_share,
+            shed: self.shed,
+            aborted: self.aborted,
+        }
+    }
+}
+
+// The tighter of the cgroup limit, v2 or v1, and the machine's RAM
+fn memory_limit() -> Option<u64> {
+    let cgroup = [
+        "/sys/fs/cgroup/memory.max",
+        "/sys/fs/cgroup/memory/memory.limit_in_bytes",
+    ]
+    .iter()
+    .filter_map(|path| fs::read_to_string(path).ok())
+    .filter_map(|limit| limit.trim().parse::<u64>().ok())
+    .find(|limit| *limit < UNLIMITED_BYTES);
+    let meminfo = fs::read_to_string("/proc/meminfo").ok();
+    let ram = meminfo
+        .as_deref()
+        .and_then(|meminfo| meminfo.lines().find(|line| line.starts_with("MemTotal:")))
+        .and_then(|line| line["MemTotal:".len()..].split_whitespace().next())
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .map(|kb| kb * 1024);
+    match (cgroup, ram) {
+        (Some(cgroup), Some(ram)) => Some(cgroup.min(ram)),
+        (cgroup, ram) => cgroup.or(ram),
+    }
+}
+
+// Soft limit on open files, the one the process runs into
+fn fd_limit() -> Option<u64> {
+    let limits = fs::read_to_string("/proc/self/limits").ok()?;
+    let line = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?;
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn open_fds() -> Option<u64> {
+    Some(fs::read_dir("/proc/self/fd").ok()?.count() as u64)
+}
+
+// Load generator's own resource usage over a step, so a throughput plateau can be told apart
+// from the generator running out of CPU or sockets. Process figures come from /proc and
+// are left empty on other platforms
+pub struct ResourceSampler {
+    cpu_secs: Option<f64>,
+    start: Instant,
+}
+
+impl ResourceSampler {
+    pub fn start() -> ResourceSampler {
+        ResourceSampler {
+            cpu_secs: cpu_secs(),
+            start: Instant::now(),
+        }
+    }
+
+    pub fn finish(&self) -> ResourceUsage {
+        let wall = self.start.elapsed().as_secs_f64();
+        let cpu_pct = match (self.cpu_secs, cpu_secs()) {
+            (Some(start), Some(end)) if wall > 0.0 => Some((end - start) / wall * 100.0),
+            _ => None,
+        };
+        ResourceUsage {
+            cpu_pct,
+            rss_bytes: status_kb("VmRSS:").map(|kb| kb * 1024),
+            peak_rss_bytes: status_kb("VmHWM:").map(|kb| kb * 1024),
+            open_sockets: open_sockets(),
+            tokio_tasks: tokio::runtime::Handle::current()
+                .metrics()
+                .num_alive_tasks(),
+        }
+    }
+}
+
+// User plus system CPU time of the process
+fn cpu_secs() -> Option<f64> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, fields are counted from after its closing paren
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let utime: f64 = fields.get(11)?.parse().ok()?;
+    let stime: f64 = fields.get(12)?.parse().ok()?;
+    Some((utime + stime) / CLOCK_TICKS)
+}
+
+fn status_kb(key: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with(key))?;
+    line[key.len()..].split_whitespace().next()?.parse().ok()
+}
+
+fn open_sockets() -> Option<u32> {
+    let entries = fs::read_dir("/proc/self/fd").ok()?;
+    let sockets = entries
+        .flatten()
+        .filter_map(|entry| fs::read_link(entry.path()).ok())
+        .filter(|target| target.to_string_lossy().starts_with("socket:"))
+        .count();
+    Some(sockets as u32)
+}
@@ -0,0 +1,385 @@
+use clap::Args;
+use paymaster_rpc::PaymasterAPIClient;
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Url};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::account::{load_accounts, verify_account, Account, AccountArgs};
+use crate::ci;
+use crate::client::{build_client, probe_connection, ClientArgs};
+use crate::crash;
+use crate::db::ResultsDb;
+use crate::discover::discover;
+use crate::encrypt::EncryptArgs;
+use crate::failover::{Failover, FailoverArgs};
+use crate::health::HealthArgs;
+use crate::manifest::{check, ManifestArgs};
+use crate::network::NetworkArgs;
+use crate::pool::{shard_accounts, AccountPool, PoolArgs};
+use crate::postmortem::write_bundle;
+use crate::ramp::{linear_ramp_test, LiveStats, RampArgs, RunContext};
+use crate::receipts::{ReceiptTracker, SuccessCriterion};
+use crate::redact::redact;
+use crate::scenario::{CustomScenario, Scenario, Workload};
+use crate::sink::{sinks, Sink, SinkArgs};
+use crate::statsd::{Statsd, StatsdArgs};
+use crate::taxonomy::Taxonomy;
+use crate::txlog::{TxLog, TxLogArgs};
+use crate::types::*;
+use crate::watchdog::WatchdogArgs;
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct LinearArgs {
+    /// JSON file of options keyed by flag name, as written by `init`
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    #[command(flatten)]
+    pub ramp: RampArgs,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// Transactions sent by every account, unless --scenario-file is given
+    #[arg(long, value_enum, default_value = "transfer")]
+    pub scenario: Scenario,
+
+    /// JSON scenario file, replaces the built-in --scenario
+    #[arg(long, conflicts_with = "scenario")]
+    pub scenario_file: Option<PathBuf>,
+
+    /// Transactions per chain of the nonce-chain scenario
+    #[arg(long, default_value = "10", value_parser = clap::value_parser!(u32).range(1..))]
+    pub chain_length: u32,
+
+    /// Second account used by multi-account scenarios, its key is read from SPENDER_PRIVATE_KEY
+    #[arg(long)]
+    pub spender_address: Option<String>,
+
+    #[command(flatten)]
+    pub pool: PoolArgs,
+
+    /// Starknet JSON-RPC endpoint used for on-chain checks
+    #[arg(long)]
+    pub rpc_url: Option<String>,
+
+    /// Report transactions the paymaster executed but the node still doesn't know this
+    /// many seconds later as lost, and count them as failures
+    #[arg(long, requires = "rpc_url")]
+    pub lost_after_secs: Option<u64>,
+
+    /// What counts as a success, can be repeated: every criterion gets its own success
+    /// rate and latencies per step. Anything past execute follows the transactions through
+    /// --rpc-url
+    #[arg(long = "success", value_enum, default_value = "execute")]
+    pub success_criteria: Vec<SuccessCriterion>,
+
+    /// Time a transaction has from its send to meet the on-chain --success criteria
+    #[arg(long, default_value = "120")]
+    pub inclusion_timeout_secs: u64,
+
+    /// TOML file naming categories of paymaster errors by code or message, errors it
+    /// matches are counted under those names in the error breakdown
+    #[arg(long)]
+    pub error_taxonomy: Option<PathBuf>,
+
+    /// Where the results go, can be repeated. `.csv`, `.html` and `.md` files get that
+    /// report of them, any other file the results JSON
+    #[arg(long)]
+    pub output: Vec<PathBuf>,
+
+    /// Don't print the per-step summary table once the run is over
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Free-form name stored with the results, e.g. the paymaster build under test
+    #[arg(long)]
+    pub label: Option<String>,
+
+    /// Where this worker runs, e.g. `eu-west-1`. `merge` reports latency and errors per
+    /// region, so a distant worker isn't taken for a paymaster at capacity
+    #[arg(long)]
+    pub region: Option<String>,
+
+    /// SQLite database the run, its steps and transactions are appended to
+    #[arg(long)]
+    pub db: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub tx_log: TxLogArgs,
+
+    #[command(flatten)]
+    pub failover: FailoverArgs,
+
+    #[command(flatten)]
+    pub health: HealthArgs,
+
+    #[command(flatten)]
+    pub watchdog: WatchdogArgs,
+
+    #[command(flatten)]
+    pub statsd: StatsdArgs,
+
+    #[command(flatten)]
+    pub manifest: ManifestArgs,
+
+    /// Upload the results to `s3://bucket/prefix` or `gs://bucket/prefix` when the run completes
+    #[arg(long)]
+    pub upload: Option<String>,
+
+    /// Upload the transaction log and the results database along with the results
+    #[arg(long, requires = "upload")]
+    pub upload_artifacts: bool,
+
+    /// Replace account addresses, transaction hashes and tracking ids in the results with
+    /// pseudonyms and drop calldata and signatures, to share them outside the team. The
+    /// transaction log and the results database keep the real values and aren't uploaded
+    /// with it
+    #[arg(long, conflicts_with = "upload_artifacts")]
+    pub redact: bool,
+
+    #[command(flatten)]
+    pub sinks: SinkArgs,
+
+    #[command(flatten)]
+    pub encrypt: EncryptArgs,
+}
+
+// Sets up clients and accounts from the CLI options and runs the linear ramp
+pub async fn run_linear(
+    args: LinearArgs,
+    live: Arc<LiveStats>,
+) -> Result<StressTestResults, TestError> {
+    let sinks = sinks(&args);
+    run_with_sinks(args, live, sinks).await
+}
+
+// Same as `run_linear`, the results only go to `sinks`
+pub async fn run_with_sinks(
+    mut args: LinearArgs,
+    live: Arc<LiveStats>,
+    sinks: Vec<Box<dyn Sink>>,
+) -> Result<StressTestResults, TestError> {
+    let endpoint = args.client.endpoint.clone();
+    let run_tag = args.client.tag_run();
+    crash::install(
+        &run_tag,
+        args.output.first().map(PathBuf::as_path),
+        args.redact,
+        &args.encrypt,
+    );
+    let fallback = match &args.failover.fallback_endpoint {
+        Some(fallback_endpoint) => Some(
+            build_client(&ClientArgs {
+                endpoint: fallback_endpoint.clone(),
+                ..args.client.clone()
+            })
+            .await?,
+        ),
+        None => None,
+    };
+    let client = build_client(&args.client).await?;
+    let network = args.network.resolve()?;
+    network.check_max_tps(args.ramp.peak_tps())?;
+    let mut endpoints = vec![endpoint.as_str()];
+    endpoints.extend(args.failover.fallback_endpoint.as_deref());
+    args.ramp.max_transactions = args.network.guard_production(
+        &endpoints,
+        args.ramp.peak_tps(),
+        args.ramp.max_transactions,
+    )?;
+    args.ramp.seed_shuffle();
+    let schedule = args.ramp.schedule()?;
+    let expectations = args.manifest.load()?;
+    // Check if paymaster service is available
+    if !client.is_available().await? {
+        return Err(format!("Paymaster service not available at {}", endpoint).into());
+    }
+    let paymaster = discover(&client).await?;
+    paymaster.check_required()?;
+    network.check_paymaster(&paymaster)?;
+    let connection = probe_connection(&args.client).await?;
+
+    println!("Starting stress test:");
+    println!("  Endpoint: {}", endpoint);
+    println!("  Run Tag: {}: {}", args.client.tag_header, run_tag);
+    if let Some(dns_ms) = connection.dns_ms {
+        println!("  DNS Lookup: {:.1} ms", dns_ms);
+    }
+    for timing in &connection.addresses {
+        match timing.connect_ms {
+            Some(connect_ms) => println!("  Connect {}: {:.1} ms", timing.address, connect_ms),
+            None => println!(
+                "  Connect {}: failed ({})",
+                timing.address,
+                timing.error.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+    if let Some(version) = &paymaster.version {
+        println!("  Paymaster Version: {}", version);
+    }
+    println!("  Max TPS: {}", args.ramp.peak_tps());
+    println!(
+        "  Duration for Full Test: {:?}",
+        schedule
+            .iter()
+            .map(|(_, duration)| *duration)
+            .sum::<Duration>()
+    );
+    println!("  Steps: {}", schedule.len());
+    if let Some(fallback_endpoint) = &args.failover.fallback_endpoint {
+        println!("  Fallback Endpoint: {}", fallback_endpoint);
+    }
+    println!();
+
+    let config = envy::from_env::<Config>()?;
+    let accounts = match &args.pool.accounts {
+        Some(path) => load_accounts(path)?,
+        None => vec![args.account.resolve(&config.private_key)?],
+    };
+    let (accounts, shard) = match args.pool.shard {
+        Some(shard) => {
+            let (accounts, assignment) = shard_accounts(accounts, shard)?;
+            (accounts, Some(assignment))
+        }
+        None => (accounts, None),
+    };
+    let mut helpers = Vec::new();
+    if let (Some(address), Some(key)) = (&args.spender_address, &config.spender_private_key) {
+        helpers.push(Account::new(address, key)?);
+    }
+    // Fail early when the scenario needs more accounts than configured
+    let mut roles = vec![accounts[0].clone()];
+    roles.extend(helpers.iter().cloned());
+    let workload = match &args.scenario_file {
+        Some(path) => Workload::File(CustomScenario::load(path)?),
+        None => Workload::Builtin {
+            scenario: args.scenario,
+            chain_length: args.chain_length,
+        },
+    };
+    workload.legs(&roles, network.gas_token)?;
+    if args.ramp.unique_calldata {
+        workload.check_unique()?;
+    }
+
+    let provider = match &args.rpc_url {
+        Some(url) => Some(Arc::new(JsonRpcClient::new(HttpTransport::new(
+            Url::parse(url)?,
+        )))),
+        None => None,
+    };
+    match &provider {
+        Some(provider) => {
+            network.check_chain(provider).await?;
+            for account in accounts.iter().chain(&helpers) {
+                verify_account(provider, account).await?;
+            }
+        }
+        None => println!("No --rpc-url given, skipping account key verification"),
+    }
+    let receipts = match &provider {
+        Some(provider) => ReceiptTracker::new(
+            Arc::clone(provider),
+            args.lost_after_secs.map(Duration::from_secs),
+            &args.success_criteria,
+            Duration::from_secs(args.inclusion_timeout_secs),
+        ),
+        None if args
+            .success_criteria
+            .iter()
+            .all(|c| *c == SuccessCriterion::Execute) =>
+        {
+            None
+        }
+        None => return Err("--success criteria past execute need --rpc-url".into()),
+    };
+    if args.ramp.relayer_sample > 0 && provider.is_none() {
+        return Err("--relayer-sample needs --rpc-url".into());
+    }
+    let pool = AccountPool::new(accounts, &args.pool, provider);
+    pool.sync_nonces().await?;
+    println!("Sending from {} account(s)", pool.len());
+    if let Some(max_account_tps) = args.pool.max_account_tps {
+        let ceiling = max_account_tps * pool.len() as f64;
+        println!(
+            "At most {} TPS per account, {:.1} TPS for the pool",
+            max_account_tps, ceiling
+        );
+        if (args.ramp.peak_tps() as f64) > ceiling {
+            println!(
+                "Steps above {:.1} TPS will be throttled, add accounts to reach them",
+                ceiling
+            );
+        }
+    }
+
+    let context = RunContext {
+        failover: Failover::new(client, fallback, &args.failover),
+        pool,
+        helpers,
+        scenario: workload,
+        gas_token: network.gas_token,
+        tx_log: TxLog::open(&args.tx_log)?,
+        db: match &args.db {
+            Some(path) => Some(ResultsDb::open(path, args.label.as_deref(), &endpoint)?),
+            None => None,
+        },
+        statsd: Statsd::open(&args.statsd, &run_tag, args.label.as_deref())?,
+        label: args.label.clone(),
+        run_tag,
+        shard,
+        paymaster,
+        live,
+        request_timeout: Duration::from_secs(args.client.request_timeout_secs),
+        health_interval: args.health.interval(),
+        status_page: args.health.status_page(),
+        watchdog_multiple: args.watchdog.watchdog_multiple,
+        receipts,
+        success_criteria: args.success_criteria.clone(),
+        taxonomy: match &args.error_taxonomy {
+            Some(path) => Some(Taxonomy::load(path)?),
+            None => None,
+        },
+        injection: None,
+    };
+    let mut results = linear_ramp_test(context, &args.ramp).await?;
+    results.connection = Some(connection);
+    results.ci = ci::detect();
+    results.region = args.region.clone();
+    if let Some((environment, expectations)) = expectations {
+        results.acceptance = Some(check(environment, &expectations, &results));
+    }
+    if let Some(path) = &args.manifest.postmortem {
+        if results.acceptance.as_ref().is_some_and(|a| !a.passed) {
+            write_bundle(path, &results, args.redact)?;
+            let path = args.encrypt.encrypt_file(path)?;
+            println!("Post-mortem bundle saved to: {}", path.display());
+        }
+    }
+
+    // The transaction log is written as the run goes and only encrypted once complete
+    results.tx_log_segments = results
+        .tx_log_segments
+        .iter()
+        .map(|segment| args.encrypt.encrypt_file(segment))
+        .collect::<Result<_, _>>()?;
+    let mut json = serde_json::to_value(&results)?;
+    if args.redact {
+        redact(&mut json);
+    }
+    for sink in sinks {
+        sink.emit(&results, &json).await?;
+    }
+    Ok(results)
+}
@@ -0,0 +1,484 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use starknet::core::types::{Call, Felt};
+use starknet::core::utils::get_selector_from_name;
+use starknet::macros::{felt, selector};
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::abi::calldata_len;
+use crate::account::Account;
+use crate::TestError;
+
+// Receives the transfers of the built-in scenarios and the probes
+pub const RECIPIENT: Felt =
+    felt!("0x03f27a34e5e5483bf91257a3232ba753cc94e5b4ca19f8e200e8387e4a2ce555");
+
+// Computed at compile time, hashing the names again for every transaction adds up at high TPS
+pub const TRANSFER: Felt = selector!("transfer");
+const APPROVE: Felt = selector!("approve");
+const TRANSFER_FROM: Felt = selector!("transfer_from");
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum Scenario {
+    /// Single token transfer per transaction
+    Transfer,
+    /// Owner approves the spender, then the spender pulls the funds with transfer_from
+    ///
+    /// The second transaction is only built once the first one was executed
+    ApproveTransferFrom,
+    /// --chain-length transfers from the same account, each one built only after the
+    /// previous one was executed, to measure how fast a single account can go serially
+    NonceChain,
+}
+
+// Payload dimension grown across steps by --grow
+#[derive(ValueEnum, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Growth {
+    /// Repeat the calls of every leg `size` times within the same transaction
+    Calls,
+    /// Append `size` zero felts to the calldata of every leg's first call, the target
+    /// has to tolerate trailing calldata for the transaction to go through
+    Calldata,
+}
+
+impl Growth {
+    pub fn apply(&self, legs: &mut [Leg], size: u32) {
+        for leg in legs {
+            match self {
+                Growth::Calls => {
+                    let count = leg.calls.len() * size as usize;
+                    leg.calls = leg.calls.iter().cycle().take(count).cloned().collect();
+                }
+                Growth::Calldata => {
+                    if let Some(call) = leg.calls.first_mut() {
+                        call.calldata
+                            .extend(std::iter::repeat_n(Felt::ZERO, size as usize));
+                    }
+                }
+            }
+        }
+    }
+}
+
+// One paymaster transaction of a scenario, sent by `accounts[account]`
+#[derive(Clone)]
+pub struct Leg {
+    pub account: usize,
+    pub calls: Vec<Call>,
+}
+
+impl Scenario {
+    pub fn required_accounts(&self) -> usize {
+        match self {
+            Scenario::Transfer => 1,
+            Scenario::ApproveTransferFrom => 2,
+            Scenario::NonceChain => 1,
+        }
+    }
+
+    pub fn legs(
+        &self,
+        accounts: &[Account],
+        token: Felt,
+        chain_length: u32,
+    ) -> Result<Vec<Leg>, TestError> {
+        if accounts.len() < self.required_accounts() {
+            return Err(format!(
+                "scenario needs {} accounts, got {}",
+                self.required_accounts(),
+                accounts.len()
+            )
+            .into());
+        }
+
+        let transfer = Leg {
+            account: 0,
+            calls: vec![Call {
+                to: token,
+                selector: TRANSFER,
+                calldata: vec![
+                    RECIPIENT,  // to
+                    Felt::ONE,  // amount (low)
+                    Felt::ZERO, // amount (high)
+                ],
+            }],
+        };
+        let legs = match self {
+            Scenario::Transfer => vec![transfer],
+            Scenario::NonceChain => vec![transfer; chain_length as usize],
+            Scenario::ApproveTransferFrom => vec![
+                Leg {
+                    account: 0,
+                    calls: vec![Call {
+                        to: token,
+                        selector: APPROVE,
+                        calldata: vec![
+                            accounts[1].address, // spender
+                            Felt::ONE,           // amount (low)
+                            Felt::ZERO,          // amount (high)
+                        ],
+                    }],
+                },
+                Leg {
+                    account: 1,
+                    calls: vec![Call {
+                        to: token,
+                        selector: TRANSFER_FROM,
+                        calldata: vec![
+                            accounts[0].address, // sender
+                            RECIPIENT,           // recipient
+                            Felt::ONE,           // amount (low)
+                            Felt::ZERO,          // amount (high)
+                        ],
+                    }],
+                },
+            ],
+        };
+        Ok(legs)
+    }
+}
+
+// Legs of a scenario file:
+// `{"legs": [{"account": 0, "calls": [{"to": "0x...", "function": "transfer", "calldata": ["0x...", "1", "0"]}]}]}`
+// Calls name the function, or give a raw `selector` instead. A call may point to an `abi`
+// file, relative to the scenario file, to have its calldata length checked on load
+#[derive(Deserialize)]
+struct ScenarioFile {
+    legs: Vec<LegEntry>,
+}
+
+#[derive(Deserialize)]
+struct LegEntry {
+    #[serde(default)]
+    account: usize,
+    calls: Vec<CallEntry>,
+}
+
+#[derive(Deserialize)]
+struct CallEntry {
+    to: String,
+    function: Option<String>,
+    selector: Option<String>,
+    #[serde(default)]
+    calldata: Vec<String>,
+    abi: Option<String>,
+}
+
+// Calldata entry of a scenario file, a literal felt or a `{{variable}}` filled in per transaction:
+// `{{random_felt}}`, `{{account_address}}` (the leg's sender), `{{counter}}` (increments once
+// per transaction over the whole run) and `{{now}}` (unix seconds)
+#[derive(Clone)]
+enum CalldataArg {
+    Value(Felt),
+    RandomFelt,
+    AccountAddress,
+    Counter,
+    Now,
+}
+
+impl CalldataArg {
+    fn parse(raw: &str) -> Result<CalldataArg, TestError> {
+        let Some(name) = raw
+            .trim()
+            .strip_prefix("{{")
+            .and_then(|rest| rest.strip_suffix("}}"))
+        else {
+            return Ok(CalldataArg::Value(raw.parse::<Felt>()?));
+        };
+        match name.trim() {
+            "random_felt" => Ok(CalldataArg::RandomFelt),
+            "account_address" => Ok(CalldataArg::AccountAddress),
+            "counter" => Ok(CalldataArg::Counter),
+            "now" => Ok(CalldataArg::Now),
+            other => Err(format!("unknown template variable `{}`", other).into()),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct CallTemplate {
+    to: Felt,
+    selector: Felt,
+    calldata: Vec<CalldataArg>,
+}
+
+#[derive(Clone)]
+struct LegTemplate {
+    account: usize,
+    calls: Vec<CallTemplate>,
+}
+
+// Scenario loaded from a file, selectors are resolved once at load time and
+// template variables on every transaction
+#[derive(Clone)]
+pub struct CustomScenario {
+    legs: Vec<LegTemplate>,
+    counter: Arc<AtomicU64>,
+}
+
+impl CustomScenario {
+    pub fn load(path: &Path) -> Result<CustomScenario, TestError> {
+        let file: ScenarioFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if file.legs.is_empty() {
+            return Err(format!("no legs in {}", path.display()).into());
+        }
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut legs = Vec::new();
+        for leg in file.legs {
+            let mut calls = Vec::new();
+            for call in leg.calls {
+                calls.push(call.resolve(dir)?);
+            }
+            legs.push(LegTemplate {
+                account: leg.account,
+                calls,
+            });
+        }
+        Ok(CustomScenario {
+            legs,
+            counter: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    fn legs(&self, accounts: &[Account]) -> Result<Vec<Leg>, TestError> {
+        let counter = Felt::from(self.counter.fetch_add(1, Ordering::Relaxed));
+        let now = Felt::from(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs());
+        let render = |arg: &CalldataArg, sender: Felt| match arg {
+            CalldataArg::Value(value) => *value,
+            CalldataArg::RandomFelt => Felt::from_bytes_be(&rand::random::<[u8; 32]>()),
+            CalldataArg::AccountAddress => sender,
+            CalldataArg::Counter => counter,
+            CalldataArg::Now => now,
+        };
+        Ok(self
+            .legs
+            .iter()
+            .map(|leg| Leg {
+                account: leg.account,
+                calls: leg
+                    .calls
+                    .iter()
+                    .map(|call| Call {
+                        to: call.to,
+                        selector: call.selector,
+                        calldata: call
+                            .calldata
+                            .iter()
+                            .map(|arg| render(arg, accounts[leg.account].address))
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect())
+    }
+}
+
+impl CallEntry {
+    fn resolve(self, dir: &Path) -> Result<CallTemplate, TestError> {
+        let selector = match (&self.function, &self.selector) {
+            (Some(function), None) => get_selector_from_name(function)?,
+            (None, Some(selector)) => Felt::from_hex(selector)?,
+            _ => return Err("each call needs exactly one of `function` and `selector`".into()),
+        };
+        if let (Some(abi), Some(function)) = (&self.abi, &self.function) {
+            let abi: Value = serde_json::from_str(&fs::read_to_string(dir.join(abi))?)?;
+            if let Some(expected) = calldata_len(&abi, function)? {
+                if expected != self.calldata.len() {
+                    return Err(format!(
+                        "`{}` takes {} calldata felts, scenario passes {}",
+                        function,
+                        expected,
+                        self.calldata.len()
+                    )
+                    .into());
+                }
+            }
+        }
+        Ok(CallTemplate {
+            to: Felt::from_hex(&self.to)?,
+            selector,
+            calldata: self
+                .calldata
+                .iter()
+                .map(|raw| CalldataArg::parse(raw))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+// Scenario a run sends, one of the built-in ones or a scenario file
+#[derive(Clone)]
+pub enum Workload {
+    Builtin {
+        scenario: Scenario,
+        chain_length: u32,
+    },
+    File(CustomScenario),
+}
+
+impl Workload {
+    pub fn required_accounts(&self) -> usize {
+        match self {
+            Workload::Builtin { scenario, .. } => scenario.required_accounts(),
+            Workload::File(scenario) => scenario
+                .legs
+                .iter()
+                .map(|leg| leg.account + 1)
+                .max()
+                .unwrap_or(1),
+        }
+    }
+
+    pub fn legs(&self, accounts: &[Account], token: Felt) -> Result<Vec<Leg>, TestError> {
+        match self {
+            Workload::Builtin {
+                scenario,
+                chain_length,
+            } => scenario.legs(accounts, token, *chain_length),
+            Workload::File(scenario) => {
+                if accounts.len() < self.required_accounts() {
+                    return Err(format!(
+                        "scenario file needs {} accounts, got {}",
+                        self.required_accounts(),
+                        accounts.len()
+                    )
+                    .into());
+                }
+                scenario.legs(accounts)
+            }
+        }
+    }
+
+    // Fails for a scenario file whose transactions may all be the same, --unique-calldata
+    // can't tell where its amounts are
+    pub fn check_unique(&self) -> Result<(), TestError> {
+        let Workload::File(scenario) = self else {
+            return Ok(());
+        };
+        let varies = scenario
+            .legs
+            .iter()
+            .flat_map(|leg| &leg.calls)
+            .flat_map(|call| &call.calldata)
+            .any(|arg| matches!(arg, CalldataArg::Counter | CalldataArg::RandomFelt));
+        if !varies {
+            return Err(
+                "--unique-calldata needs a `{{counter}}` or `{{random_felt}}` in the scenario file"
+                    .into(),
+            );
+        }
+        Ok(())
+    }
+
+    // Raises the amount of every call of a built-in scenario by `n`, all of them end in a
+    // u256 amount. An approve and the transfer_from after it move the same amount
+    pub fn make_unique(&self, legs: &mut [Leg], n: u64) -> Result<(), TestError> {
+        if let Workload::File(_) = self {
+            return Ok(());
+        }
+        for call in legs.iter_mut().flat_map(|leg| &mut leg.calls) {
+            let low = call
+                .calldata
+                .len()
+                .checked_sub(2)
+                .ok_or("a call without a u256 amount can't be made unique")?;
+            call.calldata[low] += Felt::from(n);
+        }
+        Ok(())
+    }
+
+    // Dependent transactions sent back to back by one account, for the nonce-chain scenario
+    pub fn chain_length(&self) -> Option<u32> {
+        match self {
+            Workload::Builtin {
+                scenario: Scenario::NonceChain,
+                chain_length,
+            } => Some(*chain_length),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_unique_needs_an_amount() {
+        let workload = Workload::Builtin {
+            scenario: Scenario::Transfer,
+            chain_length: 1,
+        };
+        let call = |calldata: Vec<Felt>| Call {
+            to: Felt::ONE,
+            selector: Felt::TWO,
+            calldata,
+        };
+        let mut legs = vec![Leg {
+            account: 0,
+            calls: vec![call(vec![Felt::ONE, Felt::from(5u64), Felt::ZERO])],
+        }];
+        workload.make_unique(&mut legs, 3).unwrap();
+        assert_eq!(legs[0].calls[0].calldata[1], Felt::from(8u64));
+
+        let mut legs = vec![Leg {
+            account: 0,
+            calls: vec![call(vec![Felt::ONE])],
+        }];
+        assert!(workload.make_unique(&mut legs, 3).is_err());
+    }
+
+    fn scenario(calldata: &[&str]) -> CustomScenario {
+        let call = CallEntry {
+            to: "0x49d".to_string(),
+            function: Some("transfer".to_string()),
+            selector: None,
+            calldata: calldata.iter().map(|raw| raw.to_string()).collect(),
+            abi: None,
+        };
+        CustomScenario {
+            legs: vec![LegTemplate {
+                account: 0,
+                calls: vec![call.resolve(Path::new(".")).unwrap()],
+            }],
+            counter: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    #[test]
+    fn template_variables_are_filled_in_per_transaction() {
+        let scenario = scenario(&["0x5", "{{ account_address }}", "{{counter}}"]);
+        let accounts = [Account::new("0xabc", "0x1").unwrap()];
+        let first = scenario.legs(&accounts).unwrap();
+        let second = scenario.legs(&accounts).unwrap();
+        assert_eq!(
+            first[0].calls[0].calldata,
+            vec![Felt::from(5u8), Felt::from(0xabcu16), Felt::ZERO]
+        );
+        assert_eq!(second[0].calls[0].calldata[2], Felt::ONE);
+    }
+
+    #[test]
+    fn random_felts_differ_between_transactions() {
+        let scenario = scenario(&["{{random_felt}}"]);
+        let accounts = [Account::new("0xabc", "0x1").unwrap()];
+        let first = scenario.legs(&accounts).unwrap();
+        let second = scenario.legs(&accounts).unwrap();
+        assert_ne!(first[0].calls[0].calldata, second[0].calls[0].calldata);
+    }
+
+    #[test]
+    fn unknown_variables_are_rejected() {
+        assert!(CalldataArg::parse("{{block}}").is_err());
+        assert!(matches!(
+            CalldataArg::parse("{{now}}").unwrap(),
+            CalldataArg::Now
+        ));
+    }
+}
@@ -0,0 +1,247 @@
+use clap::Args;
+
+use rand::Rng;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+use crate::account::Account;
+use crate::client::default_http_client;
+use crate::failover::{Failover, FailoverArgs};
+use crate::pool::{AccountPool, PoolArgs};
+use crate::ramp::{linear_ramp_test, LiveStats, RampArgs, RunContext};
+use crate::receipts::SuccessCriterion;
+use crate::scenario::{Scenario, Workload};
+use crate::transaction::Sent;
+use crate::types::*;
+use crate::TestError;
+
+// Errors the simulated paymaster answers with, nonce errors are left out since they
+// would get the only account quarantined and skew the counts
+const INJECTED_ERRORS: [TransactionError; 3] = [
+    TransactionError::Timeout,
+    TransactionError::Relayer,
+    TransactionError::JsonRpc,
+];
+
+#[derive(Args, Clone)]
+pub struct SelfTestArgs {
+    #[command(flatten)]
+    pub ramp: RampArgs,
+
+    /// Share of transactions the simulated paymaster fails
+    #[arg(long, default_value = "0.1")]
+    pub error_rate: f64,
+
+    /// Mean latency of the simulated paymaster
+    #[arg(long, default_value = "50")]
+    pub latency_ms: u64,
+
+    /// Latencies are spread uniformly this far around the mean
+    #[arg(long, default_value = "20")]
+    pub jitter_ms: u64,
+
+    /// Allowed deviation of the reported metrics, absolute for rates and relative otherwise
+    #[arg(long, default_value = "0.05")]
+    pub tolerance: f64,
+}
+
+// Stands in for the paymaster calls of a transaction with a known latency and error rate
+#[derive(Clone, Copy)]
+pub struct Injection {
+    error_rate: f64,
+    latency_ms: u64,
+    jitter_ms: u64,
+}
+
+impl Injection {
+    pub async fn send(&self) -> Sent {
+        let (latency, error) = {
+            let mut rng = rand::thread_rng();
+            let low = self.latency_ms.saturating_sub(self.jitter_ms);
+            let latency = rng.gen_range(low..=self.latency_ms + self.jitter_ms);
+            let error = rng
+                .gen_bool(self.error_rate)
+                .then(|| INJECTED_ERRORS[rng.gen_range(0..INJECTED_ERRORS.len())]);
+            (Duration::from_millis(latency), error)
+        };
+        let start = Instant::now();
+        sleep(latency).await;
+        let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+        let phases = PhaseTimings {
+            execute_ms: elapsed_ms,
+            ..Default::default()
+        };
+        Sent {
+            phases,
+            transaction_hashes: Vec::new(),
+            tracking_ids: Vec::new(),
+            result: match error {
+                Some(error) => Err(error),
+                None => Ok(elapsed_ms),
+            },
+            error_detail: None,
+            trace: Vec::new(),
+        }
+    }
+}
+
+// Runs the ramp with the paymaster calls replaced by an `Injection` and checks that what
+// the tool reports matches what was injected. Everything from pacing to the summary runs
+// as in a real test, only the requests themselves are simulated
+pub async fn self_test(mut args: SelfTestArgs) -> Result<SelfTestReport, TestError> {
+    if !(0.0..=1.0).contains(&args.error_rate) {
+        return Err("--error-rate must be between 0 and 1".into());
+    }
+    args.ramp.seed_shuffle();
+    // Never called, the failover only needs a client to hand out
+    let client = default_http_client("http://127.0.0.1:9")?;
+    let pool_args = PoolArgs {
+        accounts: None,
+        quarantine_after: u32::MAX,
+        quarantine_secs: 0,
+        shard: None,
+        nonce_cache: None,
+        max_account_tps: None,
+    };
+    let failover_args = FailoverArgs {
+        fallback_endpoint: None,
+        failover_after_secs: None,
+        failover_after_failures: None,
+    };
+    let context = RunContext {
+        failover: Failover::new(client, None, &failover_args),
+        pool: AccountPool::new(vec![Account::new("0x1", "0x1")?], &pool_args, None),
+        helpers: Vec::new(),
+        scenario: Workload::Builtin {
+            scenario: Scenario::Transfer,
+            chain_length: 1,
+        },
+        gas_token: Default::default(),
+        tx_log: None,
+        db: None,
+        statsd: None,
+        label: Some("self-test".to_string()),
+        run_tag: "self-test".to_string(),
+        shard: None,
+        paymaster: PaymasterInfo {
+            version: None,
+            methods: None,
+            supported_tokens: Vec::new(),
+        },
+        live: Arc::new(LiveStats::default()),
+        request_timeout: Duration::from_secs(60),
+        health_interval: None,
+        status_page: None,
+        watchdog_multiple: None,
+        receipts: None,
+        success_criteria: vec![SuccessCriterion::Execute],
+        taxonomy: None,
+        injection: Some(Injection {
+            error_rate: args.error_rate,
+            latency_ms: args.latency_ms,
+            jitter_ms: args.jitter_ms,
+        }),
+    };
+    let results = linear_ramp_test(context, &args.ramp).await?;
+
+    let schedule = args.ramp.schedule()?;
+    let mut checks = Vec::new();
+    // Requests sent before the step, against --max-transactions
+    let mut sent = 0;
+    for (step, result) in results.results.iter().enumerate() {
+        let step = step as u32 + 1;
+        let metrics = &result.metrics;
+        let budget = args
+            .ramp
+            .max_transactions
+            .map(|max| max.saturating_sub(sent));
+        sent += (metrics.total_txs + result.quotes.as_ref().map_or(0, |q| q.total)) as u64;
+        let errors = &result.error_breakdown;
+        let mut check = |name: &str, expected: f64, measured: f64, allowed: f64| {
+            checks.push(SelfTestCheck {
+                step,
+                check: name.to_string(),
+                expected,
+                measured,
+                passed: (measured - expected).abs() <= allowed,
+            });
+        };
+
+        // Sampling noise of the step comes on top of the tolerance, small steps would
+        // fail on chance otherwise
+        let n = metrics.total_txs.max(1) as f64;
+        let success = 1.0 - args.error_rate;
+        let noise = 3.0 * (success * args.error_rate / n).sqrt();
+        check(
+            "success_rate",
+            success,
+            metrics.success_rate,
+            args.tolerance + noise,
+        );
+        let classified = errors.nonce_conflicts
+            + errors.timeouts
+            + errors.relayer_exhaustion
+            + errors.json_rpc_errors
+            + errors.tls_handshake
+            + errors.other
+            + errors.categories.values().sum::<u32>()
+            + errors.lost;
+        check(
+            "classified_errors",
+            metrics.failed_txs as f64,
+            classified as f64,
+            0.0,
+        );
+        // Closed-loop steps have no target rate to compare against
+        if metrics.target_tps > 0 {
+            // Goal seeking picks rates off the schedule, its steps all have the same length
+            let step_secs = schedule
+                .iter()
+                .find(|(tps, _)| *tps == metrics.target_tps)
+                .or(schedule.first())
+                .map(|(_, duration)| duration.as_secs_f64())
+                .unwrap_or_default();
+            let mut offered = metrics.target_tps as f64 * step_secs;
+            if let Some(budget) = budget {
+                offered = offered.min(budget as f64);
+            }
+            check(
+                "total_txs",
+                offered,
+                metrics.total_txs as f64,
+                offered * args.tolerance + result.burst_size as f64,
+            );
+        }
+        if metrics.successful_txs > 0 {
+            // Timer resolution adds up to a millisecond to every simulated request, the
+            // standard errors are those of the mean and the median of a uniform spread
+            let latency = args.latency_ms as f64;
+            let allowed = latency * args.tolerance + 1.0;
+            let n = metrics.successful_txs as f64;
+            let jitter = args.jitter_ms as f64;
+            let mean_noise = 3.0 * jitter / (3.0 * n).sqrt();
+            let median_noise = 3.0 * jitter / n.sqrt();
+            check(
+                "avg_latency_ms",
+                latency,
+                metrics.avg_latency_ms,
+                allowed + mean_noise,
+            );
+            check(
+                "p50_latency_ms",
+                latency,
+                metrics.p50_latency_ms,
+                allowed + median_noise,
+            );
+        }
+    }
+
+    Ok(SelfTestReport {
+        error_rate: args.error_rate,
+        latency_ms: args.latency_ms,
+        tolerance: args.tolerance,
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    })
+}
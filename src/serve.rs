@@ -0,0 +1,98 @@
+use axum::extract::{Path as UrlPath, State};
+use axum::http::StatusCode;
+use axum::response::Html;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::Serialize;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+
+use crate::TestError;
+
+const INDEX: &str = include_str!("serve/index.html");
+
+// Summary line of one result file, fields are optional so that files written by
+// older versions of the tool still show up
+#[derive(Serialize)]
+struct RunSummary {
+    file: String,
+    modified: u64,
+    total_duration_secs: Option<u64>,
+    max_sustainable_tps: Option<u64>,
+    total_transactions: Option<u64>,
+    overall_success_rate: Option<f64>,
+}
+
+// Hosts a small web UI listing every result file in `dir`
+pub async fn serve_results(dir: PathBuf, listen: &str) -> Result<(), TestError> {
+    let listener = tokio::net::TcpListener::bind(listen).await?;
+    println!(
+        "Serving results from {} on http://{}",
+        dir.display(),
+        listen
+    );
+
+    let app = Router::new()
+        .route("/", get(|| async { Html(INDEX) }))
+        .route("/api/runs", get(list_runs))
+        .route("/api/runs/:file", get(get_run))
+        .with_state(Arc::new(dir));
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn list_runs(State(dir): State<Arc<PathBuf>>) -> Result<Json<Vec<RunSummary>>, StatusCode> {
+    let entries = fs::read_dir(dir.as_path()).map_err(|_| StatusCode::NOT_FOUND)?;
+    let mut runs = Vec::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        // Skip anything in the directory that is not a result file
+        let Some(run) = read_json(&path) else {
+            continue;
+        };
+        let summary = &run["summary"];
+        runs.push(RunSummary {
+            file: entry.file_name().to_string_lossy().into_owned(),
+            modified: entry
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs())
+                .unwrap_or(0),
+            total_duration_secs: run["total_duration_secs"].as_u64(),
+            max_sustainable_tps: summary["max_sustainable_tps"].as_u64(),
+            total_transactions: summary["total_transactions"].as_u64(),
+            overall_success_rate: summary["overall_success_rate"].as_f64(),
+        });
+    }
+    Ok(Json(runs))
+}
+
+async fn get_run(
+    State(dir): State<Arc<PathBuf>>,
+    UrlPath(file): UrlPath<String>,
+) -> Result<Json<Value>, StatusCode> {
+    // Only serve plain file names from the results directory
+    if Path::new(&file)
+        .file_name()
+        .is_none_or(|name| name != file.as_str())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    read_json(&dir.join(&file))
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+fn read_json(path: &Path) -> Option<Value> {
+    let content = fs::read_to_string(path).ok()?;
+    let value: Value = serde_json::from_str(&content).ok()?;
+    value.get("results").is_some().then_some(value)
+}
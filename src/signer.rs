@@ -0,0 +1,125 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use starknet::core::types::Felt;
+use starknet::signers::SigningKey;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+pub type SignFuture<'a> = Pin<Box<dyn Future<Output = Result<Vec<Felt>, String>> + Send + 'a>>;
+
+// Produces the signature the account contract checks in `__validate__`. Signing is async
+// so that signers living outside the process, a hardware wallet or a signing service,
+// fit behind it
+pub trait Signer: Send + Sync {
+    // Key the account checks signatures against, when it is known locally
+    fn public_key(&self) -> Option<Felt>;
+
+    fn sign(&self, account: Felt, message_hash: Felt) -> SignFuture<'_>;
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub enum SignerKind {
+    /// PRIVATE_KEY, signed in process
+    Local,
+    /// A signing service, see --remote-signer-url
+    Remote,
+    /// PRIVATE_KEY and every --cosigner-key-env key sign, the signatures are concatenated
+    Multisig,
+}
+
+// Single STARK key, the signature is `[r, s]`
+pub struct LocalSigner(pub SigningKey);
+
+impl Signer for LocalSigner {
+    fn public_key(&self) -> Option<Felt> {
+        Some(self.0.verifying_key().scalar())
+    }
+
+    fn sign(&self, _account: Felt, message_hash: Felt) -> SignFuture<'_> {
+        let signature = self
+            .0
+            .sign(&message_hash)
+            .map(|signature| vec![signature.r, signature.s])
+            .map_err(|e| e.to_string());
+        Box::pin(async move { signature })
+    }
+}
+
+// Stand-in for multisig accounts: `[r1, s1, r2, s2, ...]`, one pair per key in order. The
+// layout of a given account implementation may differ, this covers the cost of several
+// signatures on the paymaster's validation
+pub struct MultiSigner(pub Vec<SigningKey>);
+
+impl Signer for MultiSigner {
+    fn public_key(&self) -> Option<Felt> {
+        self.0.first().map(|key| key.verifying_key().scalar())
+    }
+
+    fn sign(&self, _account: Felt, message_hash: Felt) -> SignFuture<'_> {
+        let mut signature = Vec::new();
+        for key in &self.0 {
+            match key.sign(&message_hash) {
+                Ok(pair) => signature.extend([pair.r, pair.s]),
+                Err(e) => return Box::pin(async move { Err(e.to_string()) }),
+            }
+        }
+        Box::pin(async move { Ok(signature) })
+    }
+}
+
+// POSTs `{"account", "message_hash"}` to a signing service and expects `{"signature": [...]}`
+// back, the way hardware-backed keys are usually exposed to automation
+pub struct RemoteSigner {
+    url: String,
+    client: reqwest::Client,
+}
+
+#[derive(Serialize)]
+struct SignRequest {
+    account: Felt,
+    message_hash: Felt,
+}
+
+#[derive(Deserialize)]
+struct SignResponse {
+    signature: Vec<Felt>,
+}
+
+impl RemoteSigner {
+    pub fn new(url: &str) -> Result<RemoteSigner, reqwest::Error> {
+        Ok(RemoteSigner {
+            url: url.to_string(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()?,
+        })
+    }
+}
+
+impl Signer for RemoteSigner {
+    fn public_key(&self) -> Option<Felt> {
+        None
+    }
+
+    fn sign(&self, account: Felt, message_hash: Felt) -> SignFuture<'_> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .post(&self.url)
+                .json(&SignRequest {
+                    account,
+                    message_hash,
+                })
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| format!("remote signer: {}", e))?;
+            let body: SignResponse = response
+                .json()
+                .await
+                .map_err(|e| format!("remote signer: {}", e))?;
+            Ok(body.signature)
+        })
+    }
+}
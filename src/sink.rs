@@ -0,0 +1,245 @@
+use async_trait::async_trait;
+use clap::Args;
+use serde_json::Value;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::encrypt::EncryptArgs;
+use crate::report::{render_run, ReportFormat};
+use crate::run::LinearArgs;
+use crate::types::StressTestResults;
+use crate::upload::upload;
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct SinkArgs {
+    /// InfluxDB write endpoint the step metrics are sent to as line protocol, e.g.
+    /// `http://influx:8086/api/v2/write?org=ops&bucket=paymaster`. The token is read
+    /// from INFLUX_TOKEN
+    #[arg(long)]
+    pub influx_url: Option<String>,
+
+    /// POST the results JSON here once the run is over, can be repeated
+    #[arg(long = "webhook")]
+    pub webhooks: Vec<String>,
+}
+
+// Somewhere the results of a run go once it is over. Every sink configured for a run
+// gets them, in the order they were built. `json` is the results as they are written
+// out, redacted with --redact
+#[async_trait(?Send)]
+pub trait Sink {
+    async fn emit(&self, results: &StressTestResults, json: &Value) -> Result<(), TestError>;
+}
+
+// Sinks of the output options of a run. Without --output the results go to stdout
+pub fn sinks(args: &LinearArgs) -> Vec<Box<dyn Sink>> {
+    let mut sinks = quiet_sinks(args);
+    if args.output.is_empty() {
+        sinks.insert(0, Box::new(StdoutSink));
+    }
+    sinks
+}
+
+// Same without printing the results when there is no --output
+pub fn quiet_sinks(args: &LinearArgs) -> Vec<Box<dyn Sink>> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    for path in &args.output {
+        let format = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Some(ReportFormat::Csv),
+            Some("html" | "htm") => Some(ReportFormat::Html),
+            Some("md") => Some(ReportFormat::Md),
+            _ => None,
+        };
+        match format {
+            Some(format) => sinks.push(Box::new(ReportSink {
+                path: path.clone(),
+                format,
+                encrypt: args.encrypt.clone(),
+            })),
+            None => sinks.push(Box::new(FileSink {
+                path: path.clone(),
+                encrypt: args.encrypt.clone(),
+            })),
+        }
+    }
+    if let Some(destination) = &args.upload {
+        sinks.push(Box::new(UploadSink {
+            destination: destination.clone(),
+            label: args.label.clone(),
+            db: args.db.clone(),
+            artifacts: args.upload_artifacts,
+            encrypt: args.encrypt.clone(),
+        }));
+    }
+    if let Some(url) = &args.sinks.influx_url {
+        sinks.push(Box::new(InfluxSink { url: url.clone() }));
+    }
+    for url in &args.sinks.webhooks {
+        sinks.push(Box::new(WebhookSink {
+            url: url.clone(),
+            encrypt: args.encrypt.clone(),
+        }));
+    }
+    sinks
+}
+
+struct StdoutSink;
+
+#[async_trait(?Send)]
+impl Sink for StdoutSink {
+    async fn emit(&self, _: &StressTestResults, json: &Value) -> Result<(), TestError> {
+        println!("{}", serde_json::to_string_pretty(json)?);
+        Ok(())
+    }
+}
+
+struct FileSink {
+    path: PathBuf,
+    encrypt: EncryptArgs,
+}
+
+#[async_trait(?Send)]
+impl Sink for FileSink {
+    async fn emit(&self, _: &StressTestResults, json: &Value) -> Result<(), TestError> {
+        let path = self.encrypt.path(&self.path);
+        let content = self.encrypt.encrypt(serde_json::to_vec_pretty(json)?)?;
+        fs::write(&path, content)?;
+        println!("Results saved to: {}", path.display());
+        Ok(())
+    }
+}
+
+// A report of the results as `report` renders it from the results file
+struct ReportSink {
+    path: PathBuf,
+    format: ReportFormat,
+    encrypt: EncryptArgs,
+}
+
+#[async_trait(?Send)]
+impl Sink for ReportSink {
+    async fn emit(&self, _: &StressTestResults, json: &Value) -> Result<(), TestError> {
+        let report = render_run(json, self.format)?;
+        let path = self.encrypt.path(&self.path);
+        fs::write(&path, self.encrypt.encrypt(report.into_bytes())?)?;
+        println!("Report saved to: {}", path.display());
+        Ok(())
+    }
+}
+
+// Object storage, see `upload`
+struct UploadSink {
+    destination: String,
+    label: Option<String>,
+    // Results database uploaded with --upload-artifacts
+    db: Option<PathBuf>,
+    artifacts: bool,
+    encrypt: EncryptArgs,
+}
+
+#[async_trait(?Send)]
+impl Sink for UploadSink {
+    async fn emit(&self, results: &StressTestResults, json: &Value) -> Result<(), TestError> {
+        let content = self.encrypt.encrypt(serde_json::to_vec_pretty(json)?)?;
+        let mut files = vec![(self.encrypt.path(Path::new("results.json")), content)];
+        if self.artifacts {
+            for segment in &results.tx_log_segments {
+                files.push((segment.clone(), fs::read(segment)?));
+            }
+            // Later runs append to the database, only the uploaded copy is encrypted
+            if let Some(db) = &self.db {
+                files.push((self.encrypt.path(db), self.encrypt.encrypt(fs::read(db)?)?));
+            }
+        }
+        upload(&self.destination, self.label.as_deref(), files).await
+    }
+}
+
+// One point per step, tagged with the run, timestamped at the start of the run
+struct InfluxSink {
+    url: String,
+}
+
+#[async_trait(?Send)]
+impl Sink for InfluxSink {
+    async fn emit(&self, results: &StressTestResults, _: &Value) -> Result<(), TestError> {
+        let mut tags = format!(",run_tag={}", escape_tag(&results.run_tag));
+        if let Some(label) = &results.label {
+            write!(tags, ",label={}", escape_tag(label))?;
+        }
+        if let Some(region) = &results.region {
+            write!(tags, ",region={}", escape_tag(region))?;
+        }
+        let timestamp_ns = results.started_at_unix_ms as u128 * 1_000_000;
+        let mut body = String::new();
+        for (i, result) in results.results.iter().enumerate() {
+            let metrics = &result.metrics;
+            writeln!(
+                body,
+                "paymaster_stress{},step={} target_tps={}i,total_txs={}i,successful_txs={}i,success_rate={},effective_tps={},avg_latency_ms={},p50_latency_ms={},p95_latency_ms={},p99_latency_ms={} {}",
+                tags,
+                i + 1,
+                metrics.target_tps,
+                metrics.total_txs,
+                metrics.successful_txs,
+                metrics.success_rate,
+                metrics.effective_tps,
+                metrics.avg_latency_ms,
+                metrics.p50_latency_ms,
+                metrics.p95_latency_ms,
+                metrics.p99_latency_ms,
+                timestamp_ns
+            )?;
+        }
+        let mut request = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?
+            .post(&self.url)
+            .body(body);
+        if let Ok(token) = std::env::var("INFLUX_TOKEN") {
+            request = request.header("Authorization", format!("Token {}", token));
+        }
+        request.send().await?.error_for_status()?;
+        println!("{} step(s) written to InfluxDB", results.results.len());
+        Ok(())
+    }
+}
+
+// Spaces, commas and equal signs end a tag in line protocol
+fn escape_tag(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+// The results as the output file holds them, encrypted with --encrypt-to
+struct WebhookSink {
+    url: String,
+    encrypt: EncryptArgs,
+}
+
+#[async_trait(?Send)]
+impl Sink for WebhookSink {
+    async fn emit(&self, _: &StressTestResults, json: &Value) -> Result<(), TestError> {
+        let content_type = match self.encrypt.extension() {
+            Some(_) => "application/octet-stream",
+            None => "application/json",
+        };
+        let body = self.encrypt.encrypt(serde_json::to_vec_pretty(json)?)?;
+        reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?
+            .post(&self.url)
+            .header("Content-Type", content_type)
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        println!("Results posted to {}", self.url);
+        Ok(())
+    }
+}
@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+// Values at or below this are counted as zero, the log mapping is undefined at 0
+const MIN_VALUE: f64 = 1e-9;
+
+// DDSketch: quantiles come out within `accuracy` relative error while memory only grows
+// with the log of the value range, not with the number of samples
+// Mean and standard deviation are tracked exactly alongside
+#[derive(Clone)]
+pub struct LatencySketch {
+    gamma: f64,
+    gamma_ln: f64,
+    bins: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+    // Welford's running mean and sum of squared deviations
+    mean: f64,
+    m2: f64,
+}
+
+impl LatencySketch {
+    pub fn new(accuracy: f64) -> LatencySketch {
+        let gamma = (1.0 + accuracy) / (1.0 - accuracy);
+        LatencySketch {
+            gamma,
+            gamma_ln: gamma.ln(),
+            bins: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, value: f64) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+
+        if value <= MIN_VALUE {
+            self.zeros += 1;
+        } else {
+            let index = (value.ln() / self.gamma_ln).ceil() as i32;
+            *self.bins.entry(index).or_default() += 1;
+        }
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    // Sample standard deviation
+    pub fn std_dev(&self) -> f64 {
+        if self.count < 2 {
+            return 0.0;
+        }
+        (self.m2 / (self.count - 1) as f64).sqrt()
+    }
+
+    // Standard deviation relative to the mean, comparable across steps with different latency levels
+    pub fn coefficient_of_variation(&self) -> f64 {
+        if self.mean == 0.0 {
+            return 0.0;
+        }
+        self.std_dev() / self.mean
+    }
+
+    // Nearest-rank quantile, `q` in 0..=1
+    pub fn quantile(&self, q: f64) -> f64 {
+        weighted_rank(self.entries(), self.count, q)
+    }
+
+    pub fn median(&self) -> f64 {
+        self.quantile(0.5)
+    }
+
+    // Median absolute deviation, a jitter measure that a few outliers can't inflate
+    pub fn median_absolute_deviation(&self) -> f64 {
+        let median = self.median();
+        let mut deviations: Vec<(f64, u64)> = self
+            .entries()
+            .map(|(value, count)| ((value - median).abs(), count))
+            .collect();
+        deviations.sort_by(|a, b| a.0.total_cmp(&b.0));
+        weighted_rank(deviations.into_iter(), self.count, 0.5)
+    }
+
+    // Representative value and count of every bin, in increasing order
+    fn entries(&self) -> impl Iterator<Item = (f64, u64)> + '_ {
+        let zeros = (self.zeros > 0).then_some((0.0, self.zeros));
+        zeros.into_iter().chain(
+            self.bins
+                .iter()
+                .map(|(&index, &count)| (2.0 * self.gamma.powi(index) / (self.gamma + 1.0), count)),
+        )
+    }
+}
+
+fn weighted_rank(entries: impl Iterator<Item = (f64, u64)>, total: u64, q: f64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    let rank = ((q * total as f64).ceil() as u64).clamp(1, total);
+    let mut seen = 0;
+    let mut last = 0.0;
+    for (value, count) in entries {
+        seen += count;
+        last = value;
+        if seen >= rank {
+            break;
+        }
+    }
+    last
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn within(value: f64, expected: f64, accuracy: f64) -> bool {
+        (value - expected).abs() <= expected * accuracy
+    }
+
+    #[test]
+    fn quantiles_stay_within_the_relative_accuracy() {
+        let mut sketch = LatencySketch::new(0.01);
+        for value in 1..=1000 {
+            sketch.add(value as f64);
+        }
+        for (q, expected) in [(0.5, 500.0), (0.95, 950.0), (0.99, 990.0)] {
+            let value = sketch.quantile(q);
+            assert!(within(value, expected, 0.01), "p{} is {}", q, value);
+        }
+    }
+
+    #[test]
+    fn mean_and_std_dev_are_exact() {
+        let mut sketch = LatencySketch::new(0.01);
+        for value in [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0] {
+            sketch.add(value);
+        }
+        assert!((sketch.mean() - 5.0).abs() < 1e-12);
+        assert!((sketch.std_dev() - (32.0f64 / 7.0).sqrt()).abs() < 1e-12);
+        assert!((sketch.coefficient_of_variation() - sketch.std_dev() / 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn empty_and_zero_samples() {
+        let mut sketch = LatencySketch::new(0.01);
+        assert_eq!(sketch.quantile(0.99), 0.0);
+        assert_eq!(sketch.std_dev(), 0.0);
+        sketch.add(0.0);
+        sketch.add(0.0);
+        sketch.add(100.0);
+        assert_eq!(sketch.median(), 0.0);
+        assert!(within(sketch.quantile(1.0), 100.0, 0.01));
+    }
+
+    #[test]
+    fn median_absolute_deviation_ignores_outliers() {
+        let mut sketch = LatencySketch::new(0.001);
+        for value in [10.0, 11.0, 12.0, 13.0, 14.0, 10_000.0] {
+            sketch.add(value);
+        }
+        assert!(sketch.median_absolute_deviation() < 3.0);
+    }
+}
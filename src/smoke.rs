@@ -0,0 +1,210 @@
+use clap::{Args, ValueEnum};
+use paymaster_rpc::PaymasterAPIClient;
+use starknet::core::types::Felt;
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Url};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::account::{load_accounts, parse_felt, verify_account, Account, AccountArgs};
+use crate::client::{build_client, ClientArgs};
+use crate::discover::discover;
+use crate::network::NetworkArgs;
+use crate::receipts::wait_for_receipt;
+use crate::scenario::{CustomScenario, Scenario, Workload};
+use crate::transaction::{run_legs, Fee, Pipeline};
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Args)]
+pub struct SmokeArgs {
+    #[command(flatten)]
+    pub client: ClientArgs,
+
+    #[command(flatten)]
+    pub network: NetworkArgs,
+
+    #[command(flatten)]
+    pub account: AccountArgs,
+
+    /// JSON file of `{address, private_key}` entries, every one of them sends
+    #[arg(long)]
+    pub accounts: Option<PathBuf>,
+
+    /// Repeat to check several built-in scenarios
+    #[arg(long, value_enum, default_value = "transfer")]
+    pub scenario: Vec<Scenario>,
+
+    /// Repeat to check several scenario files, on top of the built-in scenarios
+    #[arg(long)]
+    pub scenario_file: Vec<PathBuf>,
+
+    /// Transfers of the nonce-chain scenario
+    #[arg(long, default_value = "2", value_parser = clap::value_parser!(u32).range(1..))]
+    pub chain_length: u32,
+
+    /// Second account used by multi-account scenarios, its key is read from SPENDER_PRIVATE_KEY
+    #[arg(long)]
+    pub spender_address: Option<String>,
+
+    /// Also pay with these gas tokens, the network's one is always checked
+    #[arg(long, value_parser = parse_felt)]
+    pub extra_gas_token: Vec<Felt>,
+
+    /// Also send sponsored transactions
+    #[arg(long)]
+    pub sponsored: bool,
+
+    /// Wait for every executed transaction to be accepted on-chain
+    #[arg(long)]
+    pub rpc_url: Option<String>,
+
+    /// Longest wait for a transaction to be accepted on-chain, with --rpc-url
+    #[arg(long, default_value = "120")]
+    pub receipt_timeout_secs: u64,
+
+    /// Write the report to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Sends one transaction per scenario, fee mode and account, one after the other, and
+// checks that each of them goes through. Meant as a quick gate before a stress run or
+// right after a deploy, not as a measurement
+pub async fn smoke(mut args: SmokeArgs) -> Result<SmokeReport, TestError> {
+    let run_tag = args.client.tag_run();
+    let client = build_client(&args.client).await?;
+    let network = args.network.resolve()?;
+    if !client.is_available().await? {
+        return Err(format!(
+            "Paymaster service not available at {}",
+            args.client.endpoint
+        )
+        .into());
+    }
+    let paymaster = discover(&client).await?;
+    paymaster.check_required()?;
+    network.check_paymaster(&paymaster)?;
+
+    let config = envy::from_env::<Config>()?;
+    let accounts = match &args.accounts {
+        Some(path) => load_accounts(path)?,
+        None => vec![args.account.resolve(&config.private_key)?],
+    };
+    let mut helpers = Vec::new();
+    if let (Some(address), Some(key)) = (&args.spender_address, &config.spender_private_key) {
+        helpers.push(Account::new(address, key)?);
+    }
+    let mut workloads = Vec::new();
+    for scenario in &args.scenario {
+        let name = scenario
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default();
+        let workload = Workload::Builtin {
+            scenario: *scenario,
+            chain_length: args.chain_length,
+        };
+        workloads.push((name, workload));
+    }
+    for path in &args.scenario_file {
+        let workload = Workload::File(CustomScenario::load(path)?);
+        workloads.push((path.display().to_string(), workload));
+    }
+    let mut fees = vec![Fee::GasToken(network.gas_token)];
+    fees.extend(args.extra_gas_token.iter().copied().map(Fee::GasToken));
+    if args.sponsored {
+        fees.push(Fee::Sponsored);
+    }
+    // Every account sends the same legs of a scenario
+    let mut tx_accounts = vec![accounts[0].clone()];
+    tx_accounts.extend(helpers.iter().cloned());
+    let mut transactions = 0;
+    for (_, workload) in &workloads {
+        transactions += workload.legs(&tx_accounts, network.gas_token)?.len() as u64;
+    }
+    transactions *= (fees.len() * accounts.len()) as u64;
+    args.network
+        .guard_production_total(&args.client.endpoint, 1, transactions)?;
+
+    let provider = match &args.rpc_url {
+        Some(url) => Some(JsonRpcClient::new(HttpTransport::new(Url::parse(url)?))),
+        None => None,
+    };
+    if let Some(provider) = &provider {
+        network.check_chain(provider).await?;
+        for account in accounts.iter().chain(&helpers) {
+            verify_account(provider, account).await?;
+        }
+    }
+
+    println!(
+        "Smoke testing {} ({} scenario(s), {} fee mode(s), {} account(s))",
+        args.client.endpoint,
+        workloads.len(),
+        fees.len(),
+        accounts.len()
+    );
+    let pipeline = Pipeline::default();
+    let receipt_timeout = Duration::from_secs(args.receipt_timeout_secs);
+    let mut checks = Vec::new();
+    for (name, workload) in &workloads {
+        for fee in &fees {
+            for account in &accounts {
+                let mut tx_accounts = vec![account.clone()];
+                tx_accounts.extend(helpers.iter().cloned());
+                let legs = workload.legs(&tx_accounts, network.gas_token)?;
+                let sent = run_legs(
+                    &client,
+                    &pipeline,
+                    &tx_accounts,
+                    legs,
+                    *fee,
+                    false,
+                    false,
+                    false,
+                )
+                .await;
+                let mut receipts = Vec::new();
+                if let (Some(provider), Ok(_)) = (&provider, &sent.result) {
+                    for hash in &sent.transaction_hashes {
+                        receipts.push(wait_for_receipt(provider, *hash, receipt_timeout).await);
+                    }
+                }
+                let check = SmokeCheck {
+                    scenario: name.clone(),
+                    fee: *fee,
+                    account: account.address,
+                    latency_ms: sent.result.as_ref().ok().copied(),
+                    error: sent.result.as_ref().err().copied(),
+                    passed: sent.result.is_ok()
+                        && receipts.iter().all(|r| *r == ReceiptStatus::Succeeded),
+                    transaction_hashes: sent.transaction_hashes,
+                    receipts,
+                };
+                println!(
+                    "  {} {} {} {:#x}: {}",
+                    if check.passed { "PASS" } else { "FAIL" },
+                    check.scenario,
+                    match fee {
+                        Fee::GasToken(gas_token) => format!("{:#x}", gas_token),
+                        Fee::Sponsored => "sponsored".to_string(),
+                    },
+                    check.account,
+                    match (&check.error, check.latency_ms) {
+                        (Some(error), _) => error.name().to_string(),
+                        (None, Some(latency)) => format!("{:.0} ms", latency),
+                        (None, None) => String::new(),
+                    }
+                );
+                checks.push(check);
+            }
+        }
+    }
+
+    Ok(SmokeReport {
+        run_tag,
+        passed: checks.iter().all(|c| c.passed),
+        checks,
+    })
+}
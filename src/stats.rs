@@ -0,0 +1,588 @@
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use crate::sketch::LatencySketch;
+use crate::types::{
+    BudgetBucket, ConcurrencyBucket, ConcurrencyProfile, HeatmapRow, LatencyHeatmap,
+    LatencyRegression, RunSegment, SlowTransaction, ThroughputSecond, TransactionError,
+};
+
+const BUDGET_BUCKETS_PCT: [u32; 6] = [10, 25, 50, 75, 90, 100];
+
+pub fn median(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) / 2.0
+    } else {
+        sorted[mid]
+    }
+}
+
+// z of a two-sided 95% interval
+const Z_95: f64 = 1.96;
+
+// Wilson score interval of a binomial proportion at 95%. Unlike the normal approximation
+// it stays inside [0, 1] and holds up for small counts and rates close to 0 or 1
+pub fn wilson_interval(successes: u32, total: u32) -> (f64, f64) {
+    if total == 0 {
+        return (0.0, 1.0);
+    }
+    let n = total as f64;
+    let p = successes as f64 / n;
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z_95 / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+// Groups (in-flight count at send, latency) samples into power-of-two concurrency buckets
+// Latency growing with the bucket means requests are queueing inside the paymaster,
+// a flat profile means the cost is per-request processing
+// Samples are folded in as they arrive, only per-bucket sums are kept
+#[derive(Default)]
+pub struct ConcurrencyProfiler {
+    samples: Vec<u32>,
+    sums: Vec<f64>,
+    // Running sums for the Pearson correlation of in-flight count and latency
+    n: f64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_yy: f64,
+    sum_xy: f64,
+}
+
+impl ConcurrencyProfiler {
+    pub fn add(&mut self, in_flight: u32, latency: f64) {
+        let index = (u32::BITS - in_flight.max(1).leading_zeros() - 1) as usize;
+        if self.samples.len() <= index {
+            self.samples.resize(index + 1, 0);
+            self.sums.resize(index + 1, 0.0);
+        }
+        self.samples[index] += 1;
+        self.sums[index] += latency;
+
+        let x = in_flight as f64;
+        self.n += 1.0;
+        self.sum_x += x;
+        self.sum_y += latency;
+        self.sum_xx += x * x;
+        self.sum_yy += latency * latency;
+        self.sum_xy += x * latency;
+    }
+
+    pub fn finish(&self) -> ConcurrencyProfile {
+        let buckets = self
+            .samples
+            .iter()
+            .zip(&self.sums)
+            .enumerate()
+            .filter(|(_, (&samples, _))| samples > 0)
+            .map(|(index, (&samples, &sum))| {
+                let min = 1u32 << index;
+                ConcurrencyBucket {
+                    min_in_flight: min,
+                    max_in_flight: min.saturating_mul(2) - 1,
+                    samples,
+                    avg_latency_ms: sum / samples as f64,
+                }
+            })
+            .collect();
+        ConcurrencyProfile {
+            latency_correlation: self.correlation(),
+            buckets,
+        }
+    }
+
+    // Pearson correlation, 0 when either side has no variance
+    fn correlation(&self) -> f64 {
+        if self.n < 2.0 {
+            return 0.0;
+        }
+        let cov = self.sum_xy - self.sum_x * self.sum_y / self.n;
+        let var_x = self.sum_xx - self.sum_x * self.sum_x / self.n;
+        let var_y = self.sum_yy - self.sum_y * self.sum_y / self.n;
+        if var_x <= 0.0 || var_y <= 0.0 {
+            return 0.0;
+        }
+        cov / (var_x.sqrt() * var_y.sqrt())
+    }
+}
+
+// Least-squares line of latency over the size of a transaction, fitted within each step
+// (a pooled within-group regression) so latency rising with the rate of later steps
+// isn't taken for the cost of their larger payloads. Running sums are kept per step
+#[derive(Default)]
+pub struct LinearFit {
+    steps: BTreeMap<u32, FitSums>,
+}
+
+#[derive(Default)]
+struct FitSums {
+    n: f64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xx: f64,
+    sum_yy: f64,
+    sum_xy: f64,
+    min_x: f64,
+    max_x: f64,
+}
+
+impl FitSums {
+    // Sums of squares and products around the means of the step
+    fn centered(&self) -> (f64, f64, f64) {
+        (
+            self.sum_xx - self.sum_x * self.sum_x / self.n,
+            self.sum_xy - self.sum_x * self.sum_y / self.n,
+            self.sum_yy - self.sum_y * self.sum_y / self.n,
+        )
+    }
+}
+
+impl LinearFit {
+    pub fn add(&mut self, step: u32, x: f64, latency: f64) {
+        let sums = self.steps.entry(step).or_insert_with(|| FitSums {
+            min_x: x,
+            max_x: x,
+            ..Default::default()
+        });
+        sums.n += 1.0;
+        sums.sum_x += x;
+        sums.sum_y += latency;
+        sums.sum_xx += x * x;
+        sums.sum_yy += latency * latency;
+        sums.sum_xy += x * latency;
+        sums.min_x = sums.min_x.min(x);
+        sums.max_x = sums.max_x.max(x);
+    }
+
+    // None until the sizes differ within a step, sizes only changing from one step to
+    // the next can't be told apart from the change of rate
+    pub fn finish(&self) -> Option<LatencyRegression> {
+        let varied: Vec<&FitSums> = self
+            .steps
+            .values()
+            .filter(|sums| sums.min_x != sums.max_x)
+            .collect();
+        if varied.is_empty() {
+            return None;
+        }
+        let (mut var_x, mut cov, mut var_y) = (0.0, 0.0, 0.0);
+        for (sxx, sxy, syy) in varied.into_iter().map(FitSums::centered) {
+            var_x += sxx;
+            cov += sxy;
+            var_y += syy;
+        }
+        let slope = cov / var_x;
+        let sums = self.steps.values();
+        let n: f64 = sums.clone().map(|sums| sums.n).sum();
+        let sum_x: f64 = sums.clone().map(|sums| sums.sum_x).sum();
+        let sum_y: f64 = sums.clone().map(|sums| sums.sum_y).sum();
+        Some(LatencyRegression {
+            samples: n as u32,
+            min_size: sums.clone().map(|sums| sums.min_x).fold(f64::MAX, f64::min) as u32,
+            max_size: sums.map(|sums| sums.max_x).fold(0.0, f64::max) as u32,
+            ms_per_unit: slope,
+            intercept_ms: (sum_y - slope * sum_x) / n,
+            r_squared: if var_y > 0.0 {
+                cov * cov / (var_x * var_y)
+            } else {
+                0.0
+            },
+        })
+    }
+}
+
+// Histogram of latencies as a share of the request timeout, so a step creeping towards
+// the timeout shows up before requests actually start failing with it
+pub struct BudgetHistogram {
+    budget_ms: f64,
+    counts: [u32; BUDGET_BUCKETS_PCT.len() + 1],
+}
+
+impl BudgetHistogram {
+    pub fn new(budget_ms: f64) -> BudgetHistogram {
+        BudgetHistogram {
+            budget_ms,
+            counts: [0; BUDGET_BUCKETS_PCT.len() + 1],
+        }
+    }
+
+    pub fn add(&mut self, latency: f64) {
+        let pct = latency / self.budget_ms * 100.0;
+        let index = BUDGET_BUCKETS_PCT
+            .iter()
+            .position(|&bound| pct <= bound as f64)
+            .unwrap_or(BUDGET_BUCKETS_PCT.len());
+        self.counts[index] += 1;
+    }
+
+    pub fn finish(&self) -> Vec<BudgetBucket> {
+        BUDGET_BUCKETS_PCT
+            .iter()
+            .map(|&pct| Some(pct))
+            .chain([None])
+            .zip(self.counts)
+            .map(|(up_to_pct, count)| BudgetBucket { up_to_pct, count })
+            .collect()
+    }
+}
+
+// Keeps the `k` slowest transactions seen so far, slowest first
+pub fn keep_slowest(slowest: &mut Vec<SlowTransaction>, tx: SlowTransaction, k: usize) {
+    if slowest.len() == k
+        && slowest
+            .last()
+            .is_none_or(|last| last.latency_ms >= tx.latency_ms)
+    {
+        return;
+    }
+    let position = slowest.partition_point(|s| s.latency_ms >= tx.latency_ms);
+    slowest.insert(position, tx);
+    slowest.truncate(k);
+}
+
+// Fixed-length slices of a long run, each summarized on its own so a degradation late in
+// a soak stands out instead of being averaged into the whole run. Transactions belong to
+// the slice they were sent in
+pub struct SegmentTimeline {
+    length: Duration,
+    accuracy: f64,
+    segments: Vec<SegmentBuilder>,
+}
+
+struct SegmentBuilder {
+    latencies: LatencySketch,
+    succeeded: u32,
+    failed: u32,
+    errors: BTreeMap<&'static str, u32>,
+}
+
+impl SegmentTimeline {
+    pub fn new(length: Duration, accuracy: f64) -> SegmentTimeline {
+        SegmentTimeline {
+            length,
+            accuracy,
+            segments: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, sent_at: Duration, result: &Result<f64, TransactionError>) {
+        let index = (sent_at.as_secs_f64() / self.length.as_secs_f64()) as usize;
+        if self.segments.len() <= index {
+            let accuracy = self.accuracy;
+            self.segments.resize_with(index + 1, || SegmentBuilder {
+                latencies: LatencySketch::new(accuracy),
+                succeeded: 0,
+                failed: 0,
+                errors: BTreeMap::new(),
+            });
+        }
+        let segment = &mut self.segments[index];
+        match result {
+            Ok(latency) => {
+                segment.succeeded += 1;
+                segment.latencies.add(*latency);
+            }
+            Err(error) => {
+                segment.failed += 1;
+                *segment.errors.entry(error.name()).or_default() += 1;
+            }
+        }
+    }
+
+    // Nothing for runs that fit in a single segment, the run's own summary covers them
+    pub fn finish(self, run_length: Duration) -> Vec<RunSegment> {
+        if self.segments.len() < 2 {
+            return Vec::new();
+        }
+        let length = self.length.as_secs_f64();
+        self.segments
+            .into_iter()
+            .enumerate()
+            .map(|(index, segment)| {
+                let start = index as f64 * length;
+                let end = (start + length).min(run_length.as_secs_f64().max(start));
+                let total = segment.succeeded + segment.failed;
+                RunSegment {
+                    index: index as u32 + 1,
+                    start_secs: start as u64,
+                    end_secs: end as u64,
+                    total_txs: total,
+                    successful_txs: segment.succeeded,
+                    success_rate: if total > 0 {
+                        segment.succeeded as f64 / total as f64
+                    } else {
+                        0.0
+                    },
+                    effective_tps: if end > start {
+                        segment.succeeded as f64 / (end - start)
+                    } else {
+                        0.0
+                    },
+                    avg_latency_ms: segment.latencies.mean(),
+                    p50_latency_ms: segment.latencies.median(),
+                    p95_latency_ms: segment.latencies.quantile(0.95),
+                    p99_latency_ms: segment.latencies.quantile(0.99),
+                    top_error: segment
+                        .errors
+                        .into_iter()
+                        .max_by_key(|(_, count)| *count)
+                        .map(|(name, _)| name.to_string()),
+                }
+            })
+            .collect()
+    }
+}
+
+// Reservoir sampling: `sample` stays a uniform random pick of `k` out of the `seen` items
+// offered so far, whatever their number
+pub fn keep_sampled<T>(sample: &mut Vec<T>, seen: &mut u32, item: T, k: usize) {
+    *seen += 1;
+    if sample.len() < k {
+        sample.push(item);
+        return;
+    }
+    let slot = rand::thread_rng().gen_range(0..*seen as usize);
+    if slot < k {
+        sample[slot] = item;
+    }
+}
+
+// Latency buckets per doubling of the latency, ~19% wide each
+const HEATMAP_BUCKETS_PER_DOUBLING: f64 = 4.0;
+
+// Counts successful transactions by send time and latency, on log-spaced latency buckets
+// Percentiles average two latency modes into a line somewhere between them, the heatmap
+// shows both bands
+pub struct HeatmapBuilder {
+    bucket_secs: f64,
+    bounds: Vec<f64>,
+    rows: BTreeMap<u64, Vec<u32>>,
+}
+
+impl HeatmapBuilder {
+    // Buckets run from 1ms up to `max_latency_ms`, anything slower lands in the last one
+    pub fn new(bucket_secs: f64, max_latency_ms: f64) -> HeatmapBuilder {
+        let growth = 2f64.powf(1.0 / HEATMAP_BUCKETS_PER_DOUBLING);
+        let mut bounds = Vec::new();
+        let mut bound = 1.0;
+        while bound < max_latency_ms {
+            bounds.push((bound * 100.0).round() / 100.0);
+            bound *= growth;
+        }
+        HeatmapBuilder {
+            bucket_secs,
+            bounds,
+            rows: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, sent_at: Duration, latency_ms: f64) {
+        let row = (sent_at.as_secs_f64() / self.bucket_secs) as u64;
+        let column = self.bounds.partition_point(|&bound| bound < latency_ms);
+        let counts = self
+            .rows
+            .entry(row)
+            .or_insert_with(|| vec![0; self.bounds.len() + 1]);
+        counts[column] += 1;
+    }
+
+    pub fn finish(self) -> LatencyHeatmap {
+        LatencyHeatmap {
+            time_bucket_secs: self.bucket_secs,
+            latency_bounds_ms: self.bounds,
+            rows: self
+                .rows
+                .into_iter()
+                .map(|(row, counts)| HeatmapRow {
+                    start_secs: row as f64 * self.bucket_secs,
+                    counts,
+                })
+                .collect(),
+        }
+    }
+}
+
+// Transactions sent and completed in every second of the run, on the same clock. Under
+// saturation completions fall behind the sends and the two curves part
+#[derive(Default)]
+pub struct ThroughputTimeline {
+    seconds: Vec<ThroughputSecond>,
+}
+
+impl ThroughputTimeline {
+    pub fn add(&mut self, sent_at: Duration, completed_at: Duration, succeeded: bool) {
+        self.second(sent_at).offered += 1;
+        let second = self.second(completed_at);
+        second.completed += 1;
+        if succeeded {
+            second.succeeded += 1;
+        }
+    }
+
+    fn second(&mut self, at: Duration) -> &mut ThroughputSecond {
+        let index = at.as_secs() as usize;
+        if self.seconds.len() <= index {
+            self.seconds.resize_with(index + 1, Default::default);
+        }
+        &mut self.seconds[index]
+    }
+
+    pub fn finish(mut self) -> Vec<ThroughputSecond> {
+        for (index, second) in self.seconds.iter_mut().enumerate() {
+            second.second = index as u64;
+        }
+        self.seconds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn wilson_interval_matches_reference_values() {
+        let (low, high) = wilson_interval(8, 10);
+        assert_close(low, 0.4902);
+        assert_close(high, 0.9433);
+
+        let (low, high) = wilson_interval(0, 10);
+        assert_eq!(low, 0.0);
+        assert_close(high, 0.2775);
+
+        let (low, high) = wilson_interval(10, 10);
+        assert_close(low, 0.7225);
+        assert!(high <= 1.0);
+    }
+
+    #[test]
+    fn wilson_interval_narrows_with_more_transactions() {
+        let (low_small, high_small) = wilson_interval(48, 50);
+        let (low_large, high_large) = wilson_interval(4800, 5000);
+        assert!(high_large - low_large < (high_small - low_small) / 5.0);
+        assert!(low_large < 0.96 && 0.96 < high_large);
+        assert_eq!(wilson_interval(0, 0), (0.0, 1.0));
+    }
+
+    #[test]
+    fn heatmap_buckets_grow_by_a_quarter_doubling() {
+        let heatmap = HeatmapBuilder::new(1.0, 7.0).finish();
+        assert_eq!(heatmap.latency_bounds_ms.len(), 12);
+        assert_eq!(heatmap.latency_bounds_ms[0], 1.0);
+        assert_eq!(heatmap.latency_bounds_ms[4], 2.0);
+        assert_eq!(heatmap.latency_bounds_ms[8], 4.0);
+        assert!(heatmap.rows.is_empty());
+    }
+
+    #[test]
+    fn heatmap_counts_by_send_time_and_latency() {
+        let mut builder = HeatmapBuilder::new(2.0, 7.0);
+        builder.add(Duration::from_millis(500), 0.5);
+        builder.add(Duration::from_millis(1500), 2.0);
+        builder.add(Duration::from_secs(5), 100.0);
+        let heatmap = builder.finish();
+
+        assert_eq!(heatmap.rows.len(), 2);
+        assert_eq!(heatmap.rows[0].start_secs, 0.0);
+        assert_eq!(heatmap.rows[0].counts[0], 1);
+        assert_eq!(heatmap.rows[0].counts[4], 1);
+        assert_eq!(heatmap.rows[1].start_secs, 4.0);
+        // Slower than the last bound
+        assert_eq!(heatmap.rows[1].counts[12], 1);
+        assert_eq!(heatmap.rows[1].counts.iter().sum::<u32>(), 1);
+    }
+
+    #[test]
+    fn linear_fit_recovers_an_exact_line() {
+        let mut fit = LinearFit::default();
+        for size in 1..=5 {
+            fit.add(0, size as f64, 20.0 + 3.0 * size as f64);
+        }
+        let line = fit.finish().unwrap();
+        assert_eq!(line.samples, 5);
+        assert_eq!((line.min_size, line.max_size), (1, 5));
+        assert_close(line.ms_per_unit, 3.0);
+        assert_close(line.intercept_ms, 20.0);
+        assert_close(line.r_squared, 1.0);
+    }
+
+    #[test]
+    fn linear_fit_ignores_latency_shifts_between_steps() {
+        // Every step is 100ms slower than the last on top of 2ms per unit of size, a fit
+        // over the whole run would take the shift for a much steeper slope
+        let mut fit = LinearFit::default();
+        for step in 0..3 {
+            for size in [step * 10 + 1, step * 10 + 3] {
+                fit.add(step, size as f64, 100.0 * step as f64 + 2.0 * size as f64);
+            }
+        }
+        let line = fit.finish().unwrap();
+        assert_close(line.ms_per_unit, 2.0);
+        assert_close(line.r_squared, 1.0);
+        assert_eq!((line.min_size, line.max_size), (1, 23));
+    }
+
+    #[test]
+    fn linear_fit_needs_sizes_varying_within_a_step() {
+        let mut fit = LinearFit::default();
+        assert!(fit.finish().is_none());
+        for step in 0..4 {
+            fit.add(step, step as f64, 50.0 * step as f64);
+            fit.add(step, step as f64, 50.0 * step as f64 + 1.0);
+        }
+        assert!(fit.finish().is_none());
+        // Identical latencies leave no variance to explain
+        fit.add(4, 1.0, 10.0);
+        fit.add(4, 2.0, 10.0);
+        let line = fit.finish().unwrap();
+        assert_close(line.ms_per_unit, 0.0);
+        assert_eq!(line.r_squared, 0.0);
+    }
+
+    #[test]
+    fn reservoir_keeps_everything_until_full() {
+        let mut sample = Vec::new();
+        let mut seen = 0;
+        for item in 0..3 {
+            keep_sampled(&mut sample, &mut seen, item, 5);
+        }
+        assert_eq!(sample, vec![0, 1, 2]);
+        for item in 3..100 {
+            keep_sampled(&mut sample, &mut seen, item, 5);
+        }
+        assert_eq!(sample.len(), 5);
+        assert_eq!(seen, 100);
+    }
+
+    #[test]
+    fn reservoir_picks_items_uniformly() {
+        let mut picks = [0u32; 10];
+        for _ in 0..10_000 {
+            let mut sample = Vec::new();
+            let mut seen = 0;
+            for item in 0..10 {
+                keep_sampled(&mut sample, &mut seen, item, 1);
+            }
+            picks[sample[0]] += 1;
+        }
+        // 1000 expected, with a standard deviation of 30
+        assert!(picks.iter().all(|&count| (800..1200).contains(&count)));
+    }
+}
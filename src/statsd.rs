@@ -0,0 +1,99 @@
+use clap::Args;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::types::TransactionError;
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct StatsdArgs {
+    /// DogStatsD agent (`host:port`) every transaction is reported to as it completes
+    #[arg(long)]
+    pub statsd_addr: Option<String>,
+
+    /// Prefix of every metric name
+    #[arg(long, default_value = "paymaster_stress")]
+    pub statsd_prefix: String,
+}
+
+// Fire-and-forget DogStatsD client, a counter and a timing per transaction tagged with
+// the run, step, kind (quote or execute) and outcome. Datagrams that can't be sent are
+// dropped, metrics are never worth slowing the test down for
+pub struct Statsd {
+    socket: UdpSocket,
+    prefix: String,
+    // Tags of the run, sent with every metric
+    tags: String,
+}
+
+impl Statsd {
+    pub fn open(
+        args: &StatsdArgs,
+        run_tag: &str,
+        label: Option<&str>,
+    ) -> Result<Option<Statsd>, TestError> {
+        let Some(addr) = &args.statsd_addr else {
+            return Ok(None);
+        };
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| format!("cannot resolve {}", addr))?;
+        let local = if addr.is_ipv4() {
+            "0.0.0.0:0"
+        } else {
+            "[::]:0"
+        };
+        let socket = UdpSocket::bind(local)?;
+        socket.connect(addr)?;
+        socket.set_nonblocking(true)?;
+        let mut tags = format!("run:{}", run_tag);
+        if let Some(label) = label {
+            tags.push_str(&format!(",label:{}", sanitize(label)));
+        }
+        Ok(Some(Statsd {
+            socket,
+            prefix: args.statsd_prefix.clone(),
+            tags,
+        }))
+    }
+
+    pub fn record(
+        &self,
+        step: u32,
+        target_tps: u32,
+        latency_ms: f64,
+        build_only: bool,
+        result: &Result<f64, TransactionError>,
+    ) {
+        let kind = if build_only { "quote" } else { "execute" };
+        let outcome = match result {
+            Ok(_) => "result:success".to_string(),
+            Err(error) => format!("result:failure,error:{}", error.name()),
+        };
+        let tags = format!(
+            "{},step:{},target_tps:{},kind:{},{}",
+            self.tags, step, target_tps, kind, outcome
+        );
+        let packet = format!(
+            "{prefix}.tx:1|c|#{tags}\n{prefix}.latency:{latency:.3}|ms|#{tags}",
+            prefix = self.prefix,
+            latency = latency_ms,
+            tags = tags,
+        );
+        let _ = self.socket.send(packet.as_bytes());
+    }
+}
+
+// Tag values can't hold the separators of the format
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if matches!(c, ',' | '|' | '#' | '\n') {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
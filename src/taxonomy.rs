@@ -0,0 +1,92 @@
+use std::fs;
+use std::path::Path;
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::transaction::ErrorDetail;
+use crate::TestError;
+
+// Named categories for paymaster errors, read from a TOML file of `[[category]]` tables:
+//
+//   [[category]]
+//   name = "insufficient_balance"
+//   codes = [163]
+//   messages = ["insufficient", "balance too low"]
+//
+// An error goes to the first category listing its JSON-RPC code or containing one of its
+// messages, case insensitive. Errors no category matches keep the built-in classification
+pub struct Taxonomy {
+    categories: Vec<Category>,
+}
+
+struct Category {
+    name: String,
+    codes: Vec<i64>,
+    messages: Vec<String>,
+}
+
+impl Taxonomy {
+    pub fn load(path: &Path) -> Result<Taxonomy, TestError> {
+        let invalid = |e: String| format!("invalid error taxonomy {}: {}", path.display(), e);
+        let document: DocumentMut = fs::read_to_string(path)?
+            .parse()
+            .map_err(|e: toml_edit::TomlError| invalid(e.to_string()))?;
+        let tables = document
+            .get("category")
+            .and_then(Item::as_array_of_tables)
+            .ok_or_else(|| invalid("expected [[category]] tables".to_string()))?;
+        let categories = tables
+            .iter()
+            .map(Category::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid)?;
+        Ok(Taxonomy { categories })
+    }
+
+    pub fn categorize(&self, error: &ErrorDetail) -> Option<&str> {
+        let message = error.message.to_lowercase();
+        self.categories
+            .iter()
+            .find(|category| {
+                error
+                    .code
+                    .is_some_and(|code| category.codes.contains(&(code as i64)))
+                    || category.messages.iter().any(|m| message.contains(m))
+            })
+            .map(|category| category.name.as_str())
+    }
+}
+
+impl Category {
+    fn parse(table: &Table) -> Result<Category, String> {
+        let name = table
+            .get("name")
+            .and_then(Item::as_str)
+            .ok_or("a category has no name")?
+            .to_string();
+        let list = |key: &str| match table.get(key) {
+            None => Ok(Vec::new()),
+            Some(item) => item
+                .as_array()
+                .map(|array| array.iter().collect::<Vec<_>>())
+                .ok_or_else(|| format!("`{}` of category {} is not a list", key, name)),
+        };
+        let codes = list("codes")?
+            .into_iter()
+            .map(|code| code.as_integer())
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("codes of category {} must be integers", name))?;
+        let messages = list("messages")?
+            .into_iter()
+            .map(|message| message.as_str().map(str::to_lowercase))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| format!("messages of category {} must be strings", name))?;
+        if codes.is_empty() && messages.is_empty() {
+            return Err(format!("category {} matches no code and no message", name));
+        }
+        Ok(Category {
+            name,
+            codes,
+            messages,
+        })
+    }
+}
@@ -0,0 +1,524 @@
+use jsonrpsee::core::ClientError;
+use paymaster_rpc::{
+    BuildTransactionRequest, BuildTransactionResponse, ExecutableInvokeParameters,
+    ExecutableTransactionParameters, ExecuteRequest, ExecuteResponse, ExecutionParameters,
+    FeeEstimate, FeeMode, InvokeParameters, PaymasterAPIClient, TransactionParameters,
+};
+use rand::Rng;
+use serde::Serialize;
+use starknet::core::types::{Call, Felt};
+use std::error::Error;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep, Instant};
+
+use crate::account::Account;
+use crate::client::{server_timed, PaymasterClient};
+use crate::scenario::Leg;
+use crate::types::{PhaseTimings, TracedLeg, TransactionError};
+
+// Concurrency limits of the build and execute stages. A transaction holds a build slot
+// while it is built and signed, then queues for an execute slot with its signature ready,
+// so each stage can be saturated and observed on its own
+#[derive(Clone, Default)]
+pub struct Pipeline {
+    build: Option<Arc<Semaphore>>,
+    execute: Option<Arc<Semaphore>>,
+    // Range the pause between signing and executing is drawn from, the time a wallet
+    // user takes to confirm
+    confirm_delay: Option<(Duration, Duration)>,
+}
+
+impl Pipeline {
+    pub fn new(
+        build: Option<usize>,
+        execute: Option<usize>,
+        confirm_delay: Option<(Duration, Duration)>,
+    ) -> Pipeline {
+        Pipeline {
+            build: build.map(|n| Arc::new(Semaphore::new(n))),
+            execute: execute.map(|n| Arc::new(Semaphore::new(n))),
+            confirm_delay,
+        }
+    }
+}
+
+// How the transactions are paid for, in a gas token the paymaster converts or sponsored
+// by the paymaster's operator
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Fee {
+    GasToken(Felt),
+    Sponsored,
+}
+
+impl Fee {
+    fn mode(&self) -> FeeMode {
+        match self {
+            Fee::GasToken(gas_token) => FeeMode::Default {
+                gas_token: *gas_token,
+            },
+            Fee::Sponsored => FeeMode::Sponsored,
+        }
+    }
+}
+
+// Waits for a slot of the stage, stages without a limit don't queue
+async fn enter(stage: &Option<Arc<Semaphore>>, queue_ms: &mut f64) -> Option<OwnedSemaphorePermit> {
+    let stage = stage.as_ref()?;
+    let start = Instant::now();
+    let permit = Arc::clone(stage).acquire_owned().await.ok();
+    *queue_ms += start.elapsed().as_secs_f64() * 1000.0;
+    permit
+}
+
+// What sending the legs of a scenario came back with
+pub struct Sent {
+    // Phase timings are returned for failed attempts too, up to the failing phase
+    pub phases: PhaseTimings,
+    // Hashes the paymaster returned for the executed legs
+    pub transaction_hashes: Vec<Felt>,
+    // Ids the paymaster tracks the executed legs under, to find them in its logs
+    pub tracking_ids: Vec<Felt>,
+    pub result: Result<f64, TransactionError>,
+    // Error the paymaster answered with, when it answered
+    pub error_detail: Option<ErrorDetail>,
+    // Requests and responses of every attempt, only when traced
+    pub trace: Vec<TracedLeg>,
+}
+
+// Code and message of a paymaster error, matched against --error-taxonomy
+#[derive(Clone)]
+pub struct ErrorDetail {
+    // JSON-RPC error code, absent for transport errors
+    pub code: Option<i32>,
+    pub message: String,
+    // The TLS handshake with the endpoint failed, the request never reached the paymaster
+    pub tls: bool,
+}
+
+impl From<&ClientError> for ErrorDetail {
+    fn from(error: &ClientError) -> ErrorDetail {
+        ErrorDetail {
+            code: match error {
+                ClientError::Call(call) => Some(call.code()),
+                _ => None,
+            },
+            message: error.to_string(),
+            tls: is_tls_failure(error),
+        }
+    }
+}
+
+// Whether rustls turned the connection down, somewhere in the chain of a transport error.
+// Handshake failures come up wrapped in an io::Error, whose source skips the error it wraps
+fn is_tls_failure(error: &ClientError) -> bool {
+    let ClientError::Transport(error) = error else {
+        return false;
+    };
+    let mut current: Option<&(dyn Error + 'static)> = Some(error.as_ref());
+    while let Some(error) = current {
+        if error.is::<rustls::Error>() {
+            return true;
+        }
+        current = match error
+            .downcast_ref::<io::Error>()
+            .and_then(io::Error::get_ref)
+        {
+            Some(inner) => Some(inner),
+            None => error.source(),
+        };
+    }
+    false
+}
+
+// Sends the legs of a scenario one after the other, each leg is built only after
+// the previous one was executed. Latency is the sum over all legs, time spent queueing
+// for a pipeline stage is reported in the phases but not counted as latency
+// Latencies are kept as durations and only turned into fractional milliseconds for the
+// result, truncating them would blur sub-millisecond builds
+// With `retry_stale`, a leg whose execution was turned down for a stale nonce or expired
+// time bounds is built, signed and executed once more, the way a wallet would retry it.
+// The failed attempt counts towards the latency
+// With `build_only` each leg is only built, the quote a wallet fetches to show the fee
+// With `trace` the requests and responses of every attempt are kept, they are costly to
+// serialize and only wanted for a sample of the transactions
+#[allow(clippy::too_many_arguments)]
+pub async fn run_legs(
+    client: &PaymasterClient,
+    pipeline: &Pipeline,
+    accounts: &[Account],
+    legs: Vec<Leg>,
+    fee: Fee,
+    retry_stale: bool,
+    build_only: bool,
+    trace: bool,
+) -> Sent {
+    let mut phases = PhaseTimings::default();
+    let mut transaction_hashes = Vec::new();
+    let mut tracking_ids = Vec::new();
+    let mut latency = Duration::ZERO;
+    let mut traced = Vec::new();
+    for leg in legs {
+        let account = &accounts[leg.account];
+        let mut leg_calls = leg.calls;
+        loop {
+            // Only the first stale rejection is retried, the last attempt can take the
+            // calls instead of copying them
+            let calls = if retry_stale && phases.retries == 0 {
+                leg_calls.clone()
+            } else {
+                std::mem::take(&mut leg_calls)
+            };
+            let mut attempt_trace = trace.then(TracedLeg::default);
+            let sent = send_single_transaction(
+                client,
+                pipeline,
+                account,
+                calls,
+                fee,
+                &mut phases,
+                build_only,
+                &mut attempt_trace,
+            )
+            .await;
+            traced.extend(attempt_trace);
+            if let Err(Failure::Execute(e, _)) = &sent {
+                if is_stale(&e.message) {
+                    phases.stale_rejections += 1;
+                }
+            }
+            match sent {
+                Ok((leg_latency, fee, response)) => {
+                    latency += leg_latency;
+                    phases.add_fee(
+                        fee_amount(fee.estimated_fee_in_gas_token),
+                        fee_amount(fee.estimated_fee_in_strk),
+                    );
+                    if let Some(response) = response {
+                        transaction_hashes.push(response.transaction_hash);
+                        tracking_ids.push(response.tracking_id);
+                    }
+                }
+                Err(Failure::Execute(e, attempt))
+                    if retry_stale && phases.retries == 0 && is_stale(&e.message) =>
+                {
+                    phases.retries += 1;
+                    latency += attempt;
+                    continue;
+                }
+                Err(failure) => {
+                    return Sent {
+                        phases,
+                        transaction_hashes,
+                        tracking_ids,
+                        result: Err(failure.error()),
+                        error_detail: failure.detail(),
+                        trace: traced,
+                    }
+                }
+            }
+            break;
+        }
+    }
+    Sent {
+        phases,
+        transaction_hashes,
+        tracking_ids,
+        result: Ok(latency.as_secs_f64() * 1000.0),
+        error_detail: None,
+        trace: traced,
+    }
+}
+
+// Where a transaction failed, the raw error of a rejected execution decides whether it is retried
+enum Failure {
+    Build(TransactionError, Option<ErrorDetail>),
+    Execute(ErrorDetail, Duration),
+}
+
+impl Failure {
+    fn error(&self) -> TransactionError {
+        match self {
+            Failure::Build(error, _) => *error,
+            Failure::Execute(error, _) => classify_error(error),
+        }
+    }
+
+    fn detail(self) -> Option<ErrorDetail> {
+        match self {
+            Failure::Build(_, detail) => detail,
+            Failure::Execute(detail, _) => Some(detail),
+        }
+    }
+}
+
+// Fees are far below 2^128, in the smallest unit of their token
+fn fee_amount(fee: Felt) -> f64 {
+    u128::try_from(fee).unwrap_or(u128::MAX) as f64
+}
+
+// Rejections a rebuilt transaction gets past: a nonce someone else used in the meantime,
+// or typed data that sat in a queue past its time bounds
+fn is_stale(error_str: &str) -> bool {
+    let error_str = error_str.to_lowercase();
+    error_str.contains("nonce")
+        || error_str.contains("expired")
+        || error_str.contains("time bounds")
+}
+
+// `trace` is filled in as far as the attempt got when it is set. The fee is the estimate
+// the paymaster built the transaction with
+#[allow(clippy::too_many_arguments)]
+async fn send_single_transaction(
+    client: &PaymasterClient,
+    pipeline: &Pipeline,
+    account: &Account,
+    calls: Vec<Call>,
+    fee: Fee,
+    phases: &mut PhaseTimings,
+    build_only: bool,
+    trace: &mut Option<TracedLeg>,
+) -> Result<(Duration, FeeEstimate, Option<ExecuteResponse>), Failure> {
+    let user_address = account.address;
+    let build_permit = enter(&pipeline.build, &mut phases.build_queue_ms).await;
+    let tx_start = Instant::now();
+
+    // Build transaction
+    let build_request = BuildTransactionRequest {
+        transaction: TransactionParameters::Invoke {
+            invoke: InvokeParameters {
+                user_address,
+                calls,
+            },
+        },
+        parameters: ExecutionParameters::V1 {
+            fee_mode: fee.mode(),
+            time_bounds: None,
+        },
+    };
+
+    if let Some(trace) = trace {
+        trace.build_request = serde_json::to_value(&build_request).ok();
+    }
+    let (build_result, server_ms) = server_timed(client.build_transaction(build_request)).await;
+    let build_time = tx_start.elapsed();
+    phases.build_ms += build_time.as_secs_f64() * 1000.0;
+    phases.add_server_time(build_time, server_ms);
+    if let Some(trace) = trace {
+        trace.build_ms = build_time.as_secs_f64() * 1000.0;
+        match &build_result {
+            Ok(response) => trace.build_response = serde_json::to_value(response).ok(),
+            Err(e) => trace.error = Some(e.to_string()),
+        }
+    }
+    let invoke_tx = match build_result {
+        Ok(BuildTransactionResponse::Invoke(tx)) => tx,
+        Err(e) => {
+            let detail = ErrorDetail::from(&e);
+            return Err(Failure::Build(build_error(&detail), Some(detail)));
+        }
+        Ok(_) => {
+            let detail = ErrorDetail {
+                code: None,
+                message: "paymaster built a transaction of another type than invoke".to_string(),
+                tls: false,
+            };
+            return Err(Failure::Build(TransactionError::Other, Some(detail)));
+        }
+    };
+    if build_only {
+        return Ok((build_time, invoke_tx.fee, None));
+    }
+
+    // Sign the transaction
+    let sign_start = Instant::now();
+    let message_hash = invoke_tx
+        .typed_data
+        .message_hash(user_address)
+        .map_err(|_| Failure::Build(TransactionError::Other, None))?;
+
+    let signature = account
+        .signer
+        .sign(user_address, message_hash)
+        .await
+        .map_err(|_| Failure::Build(TransactionError::Other, None))?;
+    let sign_time = sign_start.elapsed();
+    phases.sign_ms += sign_time.as_secs_f64() * 1000.0;
+    if let Some(trace) = trace {
+        trace.sign_ms = sign_time.as_secs_f64() * 1000.0;
+    }
+    drop(build_permit);
+
+    // The user looking at the fee, not counted as latency
+    if let Some((min, max)) = pipeline.confirm_delay {
+        let delay = if min == max {
+            min
+        } else {
+            rand::thread_rng().gen_range(min..=max)
+        };
+        sleep(delay).await;
+        phases.confirm_ms += delay.as_secs_f64() * 1000.0;
+    }
+
+    // Execute transaction
+    let _execute_permit = enter(&pipeline.execute, &mut phases.execute_queue_ms).await;
+    let execute_start = Instant::now();
+    let execute_request = ExecuteRequest {
+        transaction: ExecutableTransactionParameters::Invoke {
+            invoke: ExecutableInvokeParameters {
+                user_address,
+                typed_data: invoke_tx.typed_data,
+                signature,
+            },
+        },
+        parameters: ExecutionParameters::V1 {
+            fee_mode: fee.mode(),
+            time_bounds: None,
+        },
+    };
+
+    if let Some(trace) = trace {
+        trace.execute_request = serde_json::to_value(&execute_request).ok();
+    }
+    let (execute_result, server_ms) =
+        server_timed(client.execute_transaction(execute_request)).await;
+    let execute_time = execute_start.elapsed();
+    phases.execute_ms += execute_time.as_secs_f64() * 1000.0;
+    phases.add_server_time(execute_time, server_ms);
+    if let Some(trace) = trace {
+        trace.execute_ms = execute_time.as_secs_f64() * 1000.0;
+        match &execute_result {
+            Ok(response) => trace.execute_response = serde_json::to_value(response).ok(),
+            Err(e) => trace.error = Some(e.to_string()),
+        }
+    }
+    let latency = build_time + sign_time + execute_time;
+    match execute_result {
+        Ok(response) => Ok((latency, invoke_tx.fee, Some(response))),
+        Err(e) => Err(Failure::Execute(ErrorDetail::from(&e), latency)),
+    }
+}
+
+// Builds and signs a transaction but leaves its execution to the caller, e.g. to send
+// several of them in one JSON-RPC batch
+pub async fn prepare_execution(
+    client: &PaymasterClient,
+    account: &Account,
+    calls: Vec<Call>,
+    fee: Fee,
+) -> Result<ExecuteRequest, TransactionError> {
+    let user_address = account.address;
+    let build_request = BuildTransactionRequest {
+        transaction: TransactionParameters::Invoke {
+            invoke: InvokeParameters {
+                user_address,
+                calls,
+            },
+        },
+        parameters: ExecutionParameters::V1 {
+            fee_mode: fee.mode(),
+            time_bounds: None,
+        },
+    };
+    let invoke_tx = match client.build_transaction(build_request).await {
+        Ok(BuildTransactionResponse::Invoke(tx)) => tx,
+        Ok(_) => return Err(TransactionError::Other),
+        Err(e) => return Err(build_error(&ErrorDetail::from(&e))),
+    };
+    let message_hash = invoke_tx
+        .typed_data
+        .message_hash(user_address)
+        .map_err(|_| TransactionError::Other)?;
+    let signature = account
+        .signer
+        .sign(user_address, message_hash)
+        .await
+        .map_err(|_| TransactionError::Other)?;
+    Ok(ExecuteRequest {
+        transaction: ExecutableTransactionParameters::Invoke {
+            invoke: ExecutableInvokeParameters {
+                user_address,
+                typed_data: invoke_tx.typed_data,
+                signature,
+            },
+        },
+        parameters: ExecutionParameters::V1 {
+            fee_mode: fee.mode(),
+            time_bounds: None,
+        },
+    })
+}
+
+// A failed build is only told apart when the handshake failed
+fn build_error(error: &ErrorDetail) -> TransactionError {
+    if error.tls {
+        TransactionError::Tls
+    } else {
+        TransactionError::Other
+    }
+}
+
+fn classify_error(error: &ErrorDetail) -> TransactionError {
+    let error_str = error.message.as_str();
+    if error.tls {
+        TransactionError::Tls
+    } else if error_str.contains("nonce") {
+        TransactionError::Nonce
+    } else if error_str.contains("timeout") {
+        TransactionError::Timeout
+    } else if error_str.contains("relayer") || error_str.contains("unavailable") {
+        TransactionError::Relayer
+    } else if error_str.contains("JSON-RPC error") {
+        TransactionError::JsonRpc
+    } else {
+        TransactionError::Other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detail(error: &ClientError) -> ErrorDetail {
+        ErrorDetail::from(error)
+    }
+
+    #[test]
+    fn handshake_failure_is_tls() {
+        let handshake = io::Error::new(
+            io::ErrorKind::InvalidData,
+            rustls::Error::InvalidCertificate(rustls::CertificateError::UnknownIssuer),
+        );
+        let error = ClientError::Transport(Box::new(handshake));
+        assert!(matches!(
+            classify_error(&detail(&error)),
+            TransactionError::Tls
+        ));
+        assert!(matches!(
+            build_error(&detail(&error)),
+            TransactionError::Tls
+        ));
+    }
+
+    #[test]
+    fn tls_in_a_message_is_not_tls() {
+        let error = ClientError::Custom("certificate of the tls handshake".to_string());
+        assert!(!detail(&error).tls);
+        assert!(matches!(
+            build_error(&detail(&error)),
+            TransactionError::Other
+        ));
+    }
+
+    #[test]
+    fn execution_errors_are_classified_by_message() {
+        let error = ClientError::Custom("invalid nonce".to_string());
+        assert!(matches!(
+            classify_error(&detail(&error)),
+            TransactionError::Nonce
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+use clap::builder::RangedU64ValueParser;
+use clap::Args;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::stats::median;
+use crate::types::*;
+use crate::TestError;
+
+// Defaults of `trend`, also used by the daemon on its own trend.json
+pub const WINDOW: usize = 5;
+pub const MAX_DROP: f64 = 0.1;
+
+const BAR_WIDTH: usize = 40;
+
+#[derive(Args)]
+pub struct TrendArgs {
+    /// Directory of result files, e.g. the daemon's --results-dir
+    pub dir: PathBuf,
+
+    /// Every run is compared to the median of this many runs before it
+    #[arg(long, default_value_t = WINDOW, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub window: usize,
+
+    /// Change against that median flagged as a regression, as a share of the median
+    #[arg(long, default_value_t = MAX_DROP)]
+    pub max_drop: f64,
+
+    /// Write the trend to this JSON file instead of stdout
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+// Lists the runs stored in a directory, oldest first, and flags the ones that got worse
+// than the runs before them. Files are read as plain JSON like `report` does, so results
+// of older versions of the tool are part of the trend too
+pub fn trend(args: &TrendArgs) -> Result<Vec<TrendEntry>, TestError> {
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(&args.dir)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        // Anything that is not a result file, the daemon's trend.json among them
+        let Ok(run) = serde_json::from_str::<Value>(&fs::read_to_string(&path)?) else {
+            continue;
+        };
+        if let Some(entry) = read_entry(&path, &run) {
+            runs.push(entry);
+        }
+    }
+    if runs.is_empty() {
+        return Err(format!("no result files in {}", args.dir.display()).into());
+    }
+    runs.sort_by_key(|r| r.started_at_unix_ms);
+    for i in 0..runs.len() {
+        let previous = &runs[i.saturating_sub(args.window)..i];
+        runs[i].regressions = regressions(previous, &runs[i], args.max_drop);
+    }
+
+    let peak = runs
+        .iter()
+        .map(|r| r.max_sustainable_tps)
+        .max()
+        .unwrap_or(0);
+    println!(
+        "{:<24} {:>8} {:>10} {:>8}",
+        "run", "max tps", "p95 ms", "success"
+    );
+    for run in &runs {
+        let name = run
+            .results_file
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let bar = (run.max_sustainable_tps as usize * BAR_WIDTH)
+            .checked_div(peak as usize)
+            .unwrap_or(0);
+        println!(
+            "{:<24} {:>8} {:>10} {:>7.1}% {:<width$}{}",
+            name,
+            run.max_sustainable_tps,
+            run.p95_latency_ms
+                .map(|p95| format!("{:.1}", p95))
+                .unwrap_or_else(|| "-".to_string()),
+            run.overall_success_rate * 100.0,
+            "#".repeat(bar),
+            if run.regressions.is_empty() {
+                String::new()
+            } else {
+                format!(" regressed: {}", describe(&run.regressions))
+            },
+            width = BAR_WIDTH
+        );
+    }
+
+    if let Some(path) = &args.output {
+        fs::write(path, serde_json::to_string_pretty(&runs)?)?;
+        println!("Trend saved to: {}", path.display());
+    }
+    Ok(runs)
+}
+
+fn read_entry(path: &Path, run: &Value) -> Option<TrendEntry> {
+    let summary = run.get("summary")?;
+    let max_tps = summary["max_sustainable_tps"].as_u64()? as u32;
+    // Older result files have no start time, the file's is close enough for ordering
+    let started_at_unix_ms = match run["started_at_unix_ms"].as_u64() {
+        Some(started) => started,
+        None => fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_millis() as u64,
+    };
+    let p95_latency_ms = run["results"].as_array()?.iter().find_map(|step| {
+        let metrics = &step["metrics"];
+        (max_tps > 0 && metrics["target_tps"].as_u64() == Some(max_tps as u64))
+            .then(|| metrics["p95_latency_ms"].as_f64())
+            .flatten()
+    });
+    Some(TrendEntry {
+        started_at_unix_ms,
+        label: run["label"].as_str().map(str::to_string),
+        results_file: path.to_path_buf(),
+        max_sustainable_tps: max_tps,
+        p95_latency_ms,
+        overall_success_rate: summary["overall_success_rate"].as_f64().unwrap_or(0.0),
+        regressions: Vec::new(),
+    })
+}
+
+// Metrics of `run` worse than the median of `previous` by more than `max_drop` of it
+pub fn regressions(previous: &[TrendEntry], run: &TrendEntry, max_drop: f64) -> Vec<TrendMetric> {
+    if previous.is_empty() {
+        return Vec::new();
+    }
+    let mut flagged = Vec::new();
+    let tps: Vec<f64> = previous
+        .iter()
+        .map(|r| r.max_sustainable_tps as f64)
+        .collect();
+    if (run.max_sustainable_tps as f64) < median(&tps) * (1.0 - max_drop) {
+        flagged.push(TrendMetric::MaxSustainableTps);
+    }
+    let p95: Vec<f64> = previous.iter().filter_map(|r| r.p95_latency_ms).collect();
+    if let Some(latency) = run.p95_latency_ms {
+        if !p95.is_empty() && latency > median(&p95) * (1.0 + max_drop) {
+            flagged.push(TrendMetric::P95Latency);
+        }
+    }
+    let success: Vec<f64> = previous.iter().map(|r| r.overall_success_rate).collect();
+    if run.overall_success_rate < median(&success) * (1.0 - max_drop) {
+        flagged.push(TrendMetric::SuccessRate);
+    }
+    flagged
+}
+
+pub fn describe(metrics: &[TrendMetric]) -> String {
+    metrics
+        .iter()
+        .map(|metric| match metric {
+            TrendMetric::MaxSustainableTps => "max sustainable TPS",
+            TrendMetric::P95Latency => "p95 latency",
+            TrendMetric::SuccessRate => "success rate",
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
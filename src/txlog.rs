@@ -0,0 +1,166 @@
+use clap::{Args, ValueEnum};
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::types::TxRecord;
+use crate::TestError;
+
+#[derive(Args, Clone)]
+pub struct TxLogArgs {
+    /// Stream one NDJSON record per transaction to this path
+    #[arg(long)]
+    pub tx_log: Option<PathBuf>,
+
+    /// Compression of --tx-log, its extension is added to the path
+    #[arg(long, value_enum, default_value = "none")]
+    pub tx_log_compression: Compression,
+
+    /// Start a new segment once this many uncompressed bytes were written
+    #[arg(long)]
+    pub tx_log_max_bytes: Option<u64>,
+
+    /// Start a new segment after this many seconds
+    #[arg(long)]
+    pub tx_log_max_secs: Option<u64>,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+enum Segment {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Zstd(zstd::Encoder<'static, BufWriter<File>>),
+}
+
+impl Segment {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Segment::Plain(w) => w,
+            Segment::Gzip(w) => w,
+            Segment::Zstd(w) => w,
+        }
+    }
+
+    // Compressed segments are only readable once the encoder wrote its trailer
+    fn finish(self) -> io::Result<()> {
+        match self {
+            Segment::Plain(mut w) => w.flush(),
+            Segment::Gzip(w) => w.finish()?.flush(),
+            Segment::Zstd(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+// Per-transaction NDJSON log, optionally compressed and split into segments
+// Segments are named `<stem>.<n>.<ext>[.gz|.zst]` when rotation is enabled
+pub struct TxLog {
+    path: PathBuf,
+    compression: Compression,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    segment: Option<Segment>,
+    // Every segment file written so far
+    written_segments: Vec<PathBuf>,
+    // Serialization buffer, reused for every record
+    line: Vec<u8>,
+    index: u32,
+    written: u64,
+    opened: Instant,
+}
+
+impl TxLog {
+    pub fn open(args: &TxLogArgs) -> Result<Option<TxLog>, TestError> {
+        let Some(path) = &args.tx_log else {
+            return Ok(None);
+        };
+        let mut log = TxLog {
+            path: path.clone(),
+            compression: args.tx_log_compression,
+            max_bytes: args.tx_log_max_bytes,
+            max_age: args.tx_log_max_secs.map(Duration::from_secs),
+            segment: None,
+            written_segments: Vec::new(),
+            line: Vec::with_capacity(512),
+            index: 0,
+            written: 0,
+            opened: Instant::now(),
+        };
+        log.rotate()?;
+        Ok(Some(log))
+    }
+
+    pub fn write(&mut self, record: &TxRecord) -> Result<(), TestError> {
+        let full = self.max_bytes.is_some_and(|max| self.written >= max);
+        let expired = self.max_age.is_some_and(|max| self.opened.elapsed() >= max);
+        if full || expired {
+            self.rotate()?;
+        }
+
+        self.line.clear();
+        serde_json::to_writer(&mut self.line, record)?;
+        self.line.push(b'\n');
+        if let Some(segment) = &mut self.segment {
+            segment.writer().write_all(&self.line)?;
+        }
+        self.written += self.line.len() as u64;
+        Ok(())
+    }
+
+    // Returns the paths of all segments
+    pub fn finish(mut self) -> Result<Vec<PathBuf>, TestError> {
+        if let Some(segment) = self.segment.take() {
+            segment.finish()?;
+        }
+        Ok(self.written_segments)
+    }
+
+    fn rotate(&mut self) -> Result<(), TestError> {
+        if let Some(segment) = self.segment.take() {
+            segment.finish()?;
+        }
+        self.index += 1;
+
+        let path = self.segment_path();
+        let file = BufWriter::new(File::create(&path)?);
+        self.written_segments.push(path);
+        self.segment = Some(match self.compression {
+            Compression::None => Segment::Plain(file),
+            Compression::Gzip => {
+                Segment::Gzip(GzEncoder::new(file, flate2::Compression::default()))
+            }
+            Compression::Zstd => Segment::Zstd(zstd::Encoder::new(file, 0)?),
+        });
+        self.written = 0;
+        self.opened = Instant::now();
+        Ok(())
+    }
+
+    fn segment_path(&self) -> PathBuf {
+        let mut name = self
+            .path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        if self.max_bytes.is_some() || self.max_age.is_some() {
+            name = format!("{}.{:04}", name, self.index);
+        }
+        if let Some(ext) = self.path.extension() {
+            name = format!("{}.{}", name, ext.to_string_lossy());
+        }
+        match self.compression {
+            Compression::None => {}
+            Compression::Gzip => name.push_str(".gz"),
+            Compression::Zstd => name.push_str(".zst"),
+        }
+        self.path.with_file_name(name)
+    }
+}
@@ -1,8 +1,23 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use starknet::core::types::Felt;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::client::IpFamily;
+use crate::receipts::SuccessCriterion;
+use crate::scenario::Growth;
+use crate::stats::wilson_interval;
+use crate::transaction::Fee;
 
 #[derive(Deserialize, Debug)]
 pub struct Config {
+    // Empty when not set, a remote signer doesn't need it
+    #[serde(default)]
     pub private_key: String,
+    pub spender_private_key: Option<String>,
 }
 
 #[derive(Serialize, Default)]
@@ -12,28 +27,915 @@ pub struct Metrics {
     pub total_txs: u32,
     pub target_tps: u32,
     pub success_rate: f64,
+    // 95% confidence interval of the success rate given the number of transactions, a
+    // 96% rate out of 50 transactions spans far more than the same rate out of 5000
+    pub success_rate_ci_low: f64,
+    pub success_rate_ci_high: f64,
     pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    pub latency_stddev_ms: f64,
+    pub latency_cov: f64,
+    pub latency_mad_ms: f64,
+    // Latency of successful transactions from the time the schedule meant to send them
+    // rather than the time they went out, corrected for coordinated omission like wrk2
+    // does. The two only differ when the sender fell behind, then these are what a user
+    // arriving at the target rate would have seen
+    pub corrected_avg_latency_ms: f64,
+    pub corrected_p50_latency_ms: f64,
+    pub corrected_p95_latency_ms: f64,
+    pub corrected_p99_latency_ms: f64,
+    // Successful transactions split into the time the paymaster reported spending on them
+    // in its Server-Timing header and the rest, the network. Only over http and for the
+    // `server_timed_txs` transactions answered with the header, left at 0 without any
+    pub server_timed_txs: u32,
+    pub server_p50_ms: f64,
+    pub server_p95_ms: f64,
+    pub server_p99_ms: f64,
+    pub network_p50_ms: f64,
+    pub network_p95_ms: f64,
+    pub network_p99_ms: f64,
+    // Ticks where no account was available because the whole pool was quarantined or
+    // at its --max-account-tps
+    pub skipped_txs: u32,
+    // Skipped ticks where accounts were only held back by --max-account-tps, the rate
+    // above what the pool may send within the fair-use limit
+    pub throttled_txs: u32,
+    // Transactions not sent because the generator was close to its memory or file
+    // descriptor limit
+    pub shed_txs: u32,
+    // Average wait for a pipeline stage slot, a growing wait marks the saturated stage
+    pub avg_build_queue_ms: f64,
+    pub avg_execute_queue_ms: f64,
+    // Successful transactions per second of the send window, what the step actually achieved
+    pub effective_tps: f64,
+    // Transactions sent per second of the send window, what the step actually offered
+    pub offered_tps: f64,
+    // Successful transactions that only went through on a rebuilt retry, included in
+    // successful_txs. Wallet-side retries would hide these failures from users
+    pub recovered_txs: u32,
+    // Transactions with an execution turned down for a stale nonce or expired time bounds,
+    // recovered ones included. What waiting on the user with --confirm-delay costs
+    pub stale_txs: u32,
+    // How late the sends of the step went out against their precomputed schedule, a lag
+    // above a few ms means the generator couldn't keep the rate
+    pub schedule_avg_lag_ms: f64,
+    pub schedule_max_lag_ms: f64,
+    // Transactions the paymaster executed that never showed up on-chain, only with
+    // --lost-after-secs. They are moved from successful_txs to failed_txs once the run ends
+    pub lost_txs: u32,
+    // Fee the paymaster estimated for the successful transactions on average, in the
+    // smallest unit of the gas token and in fri, 0 without any
+    pub avg_fee_in_gas_token: f64,
+    pub avg_fee_in_strk: f64,
+}
+impl Metrics {
+    // From the transaction counts, along with its confidence interval
+    pub fn update_success_rate(&mut self) {
+        self.success_rate = if self.total_txs > 0 {
+            self.successful_txs as f64 / self.total_txs as f64
+        } else {
+            0.0
+        };
+        (self.success_rate_ci_low, self.success_rate_ci_high) =
+            wilson_interval(self.successful_txs, self.total_txs);
+    }
 }
+
 #[derive(Serialize)]
 pub struct TestResult {
     pub metrics: Metrics,
+    // Transactions sent back to back on each tick, 1 for evenly paced delivery
+    pub burst_size: u32,
+    // Virtual users of a closed-loop step
+    pub users: Option<u32>,
+    // Calls or calldata felts added to each transaction when the payload is grown
+    pub payload_size: Option<u32>,
+    pub fee: Fee,
+    // Build-only requests of the step, only with --quote-ratio
+    pub quotes: Option<QuoteMetrics>,
+    // Transactions per second a single account gets through back to back, from the
+    // average latency of the successful chains of the nonce-chain scenario
+    pub serial_tps: Option<f64>,
     pub error_breakdown: ErrorBreakdown,
+    pub concurrency: ConcurrencyProfile,
+    // Little's Law estimate of the requests waiting at the paymaster, filled in once the
+    // run is over
+    pub queue: Option<QueueEstimate>,
+    // Time between the end of the send window and the last in-flight response
+    pub drain_time_ms: f64,
+    // Share of the request timeout each transaction used up
+    pub timeout_budget: Vec<BudgetBucket>,
+    pub slowest: Vec<SlowTransaction>,
+    // Random sample of the step's transactions with their requests and responses, see
+    // --trace-sample
+    pub traces: Vec<TransactionTrace>,
+    // Generator side usage, sampled at the end of the send window
+    pub resources: ResourceUsage,
+    // The step under every --success criterion, filled in once the run is over. Empty
+    // when execute is the only one
+    pub criteria: Vec<CriterionMetrics>,
+    // Relayers of the --relayer-sample, filled in once the run is over
+    pub relayers: Option<RelayerUsage>,
+    // Hashes of the --relayer-sample, waiting for the lookup
+    #[serde(skip)]
+    pub relayer_sample: Vec<Felt>,
 }
 
-#[derive(Serialize, Default)]
+// Which relayers submitted the sampled transactions of a step, most used first. One
+// relayer taking most of the load while others idle shows an unbalanced pool
+#[derive(Serialize)]
+pub struct RelayerUsage {
+    pub relayers: Vec<RelayerShare>,
+    // Sampled transactions the node couldn't return
+    pub unresolved: u32,
+}
+
+#[derive(Serialize)]
+pub struct RelayerShare {
+    pub relayer: Felt,
+    pub transactions: u32,
+    pub share: f64,
+}
+
+// Latencies run from the send to the point the transaction met the criterion
+#[derive(Serialize)]
+pub struct CriterionMetrics {
+    pub criterion: SuccessCriterion,
+    pub successful_txs: u32,
+    pub success_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+}
+
+// Quotes are built but never signed nor executed, the metrics of the step only cover
+// the transactions that went all the way
+#[derive(Serialize)]
+pub struct QuoteMetrics {
+    pub total: u32,
+    pub successful: u32,
+    pub success_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+}
+
+// Memory and file descriptors of the generator against their limits over the run, see
+// --shed-at and --abort-at. Shares are of the limit, 0 when it is unknown
+#[derive(Serialize)]
+pub struct GuardReport {
+    pub memory_limit_bytes: Option<u64>,
+    pub fd_limit: Option<u64>,
+    pub peak_memory_share: f64,
+    pub peak_fd_share: f64,
+    // Some ticks sent nothing to keep the generator under its limits
+    pub shed: bool,
+    // The run was stopped before reaching a limit
+    pub aborted: bool,
+}
+
+#[derive(Serialize)]
+pub struct ResourceUsage {
+    // Average CPU use of the process over the send window, 100 per fully used core
+    pub cpu_pct: Option<f64>,
+    pub rss_bytes: Option<u64>,
+    pub peak_rss_bytes: Option<u64>,
+    pub open_sockets: Option<u32>,
+    pub tokio_tasks: usize,
+}
+
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct PhaseTimings {
+    pub build_ms: f64,
+    pub sign_ms: f64,
+    pub execute_ms: f64,
+    // Waits for a slot of the build and execute stages, only with stage limits set
+    pub build_queue_ms: f64,
+    pub execute_queue_ms: f64,
+    // Executions retried with a rebuilt transaction, only with --retry-stale
+    pub retries: u32,
+    // Simulated user confirmation between signing and executing, see --confirm-delay. Not
+    // part of the latency
+    pub confirm_ms: f64,
+    // Executions turned down for a stale nonce or expired time bounds
+    pub stale_rejections: u32,
+    // Time the paymaster reported spending on the build and execute requests in its
+    // Server-Timing header, and the rest of their round trips: the network and whatever
+    // sits in front of the server. Only over the requests answered with the header, None
+    // when none was
+    pub server_ms: Option<f64>,
+    pub network_ms: Option<f64>,
+    // Fee the paymaster estimated for the built legs, in the smallest unit of the gas token
+    // and in fri. None when no leg was built
+    pub fee_in_gas_token: Option<f64>,
+    pub fee_in_strk: Option<f64>,
+}
+
+impl PhaseTimings {
+    pub fn total_ms(&self) -> f64 {
+        self.build_ms + self.sign_ms + self.execute_ms
+    }
+
+    // Splits the round trip of a request by the server time it came back with
+    pub fn add_server_time(&mut self, round_trip: Duration, server_ms: Option<f64>) {
+        let Some(server_ms) = server_ms else {
+            return;
+        };
+        let round_trip_ms = round_trip.as_secs_f64() * 1000.0;
+        // Clocks of both ends are only compared by duration, a server time above the
+        // round trip is taken as all of it
+        let server_ms = server_ms.min(round_trip_ms);
+        *self.server_ms.get_or_insert(0.0) += server_ms;
+        *self.network_ms.get_or_insert(0.0) += round_trip_ms - server_ms;
+    }
+
+    pub fn add_fee(&mut self, in_gas_token: f64, in_strk: f64) {
+        *self.fee_in_gas_token.get_or_insert(0.0) += in_gas_token;
+        *self.fee_in_strk.get_or_insert(0.0) += in_strk;
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct SlowTransaction {
+    pub account: Felt,
+    pub sent_at_ms: u64,
+    pub latency_ms: f64,
+    pub phases: PhaseTimings,
+    pub error: Option<TransactionError>,
+}
+
+// Transactions whose latency was at most `up_to_pct` percent of the request timeout
+// (and above the previous bucket), the last bucket has no bound and counts overruns
+#[derive(Serialize)]
+pub struct BudgetBucket {
+    pub up_to_pct: Option<u32>,
+    pub count: u32,
+}
+
+// Latency of successful txs grouped by how many requests were in flight when they were sent
+#[derive(Serialize)]
+pub struct ConcurrencyProfile {
+    pub latency_correlation: f64,
+    pub buckets: Vec<ConcurrencyBucket>,
+}
+
+// Latency of successful transactions against their size, only when the sizes varied
+// within a step, e.g. a scenario file mixing payloads. Sizes growing from one step to the
+// next along with the rate, like --grow, don't give a fit on their own
+#[derive(Serialize)]
+pub struct PayloadRegression {
+    pub calls: Option<LatencyRegression>,
+    pub calldata_felts: Option<LatencyRegression>,
+}
+
+// Least-squares line `latency = intercept_ms + ms_per_unit * size`, the slope taken within
+// each step so the load of the step doesn't count as the cost of size. An r² close to 1
+// means size explains the latency within a step, close to 0 that the slope is mostly noise
+#[derive(Serialize)]
+pub struct LatencyRegression {
+    pub samples: u32,
+    pub min_size: u32,
+    pub max_size: u32,
+    pub ms_per_unit: f64,
+    pub intercept_ms: f64,
+    pub r_squared: f64,
+}
+
+// By Little's Law the requests inside the paymaster average the arrival rate times the
+// time they spend there. Of that time, the latency of the least loaded step of the run is
+// taken as service and anything above it as queueing, so a depth growing with the rate
+// marks saturation before the percentiles blow up
+#[derive(Serialize)]
+pub struct QueueEstimate {
+    pub in_system: f64,
+    pub queue_depth: f64,
+    pub baseline_latency_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct ConcurrencyBucket {
+    pub min_in_flight: u32,
+    pub max_in_flight: u32,
+    pub samples: u32,
+    pub avg_latency_ms: f64,
+}
+
+#[derive(Serialize, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionError {
+    Nonce,
+    Timeout,
+    Relayer,
+    JsonRpc,
+    Tls,
+    Other,
+}
+
+impl TransactionError {
+    // Same name as the serialized form
+    pub fn name(&self) -> &'static str {
+        match self {
+            TransactionError::Nonce => "nonce",
+            TransactionError::Timeout => "timeout",
+            TransactionError::Relayer => "relayer",
+            TransactionError::JsonRpc => "json_rpc",
+            TransactionError::Tls => "tls",
+            TransactionError::Other => "other",
+        }
+    }
+}
+
+#[derive(Serialize, Default, Clone)]
 pub struct ErrorBreakdown {
     pub nonce_conflicts: u32,
     pub timeouts: u32,
     pub relayer_exhaustion: u32,
     pub json_rpc_errors: u32,
+    pub tls_handshake: u32,
     pub other: u32,
+    pub lost: u32,
+    // Errors matched by --error-taxonomy, counted here instead of the fields above
+    pub categories: BTreeMap<String, u32>,
+}
+
+impl ErrorBreakdown {
+    // Most frequent error kind, none when nothing failed
+    pub fn top(&self) -> Option<(&str, u32)> {
+        [
+            ("nonce", self.nonce_conflicts),
+            ("timeout", self.timeouts),
+            ("relayer", self.relayer_exhaustion),
+            ("json_rpc", self.json_rpc_errors),
+            ("tls", self.tls_handshake),
+            ("other", self.other),
+            ("lost", self.lost),
+        ]
+        .into_iter()
+        .chain(self.categories.iter().map(|(name, n)| (name.as_str(), *n)))
+        .filter(|(_, n)| *n > 0)
+        .max_by_key(|(_, n)| *n)
+    }
 }
 
 #[derive(Serialize)]
 pub struct StressTestResults {
+    pub label: Option<String>,
+    // Value of the tag header sent with every request of the run
+    pub run_tag: String,
+    pub shard: Option<ShardAssignment>,
+    pub paymaster: PaymasterInfo,
+    // Wall clock at the start of the run, the `*_ms` offsets of the run count from it
+    pub started_at_unix_ms: u64,
     pub total_duration_secs: u64,
     pub results: Vec<TestResult>,
     pub summary: TestSummary,
+    pub stop_reason: StopReason,
+    pub failover: Option<FailoverReport>,
+    pub quarantine_events: Vec<QuarantineEvent>,
+    pub burst_comparison: Vec<BurstComparison>,
+    pub fee_comparison: Vec<FeeModeComparison>,
+    pub health: Option<HealthReport>,
+    // Only with --status-url
+    pub status_page: Option<StatusPageReport>,
+    // Requests the watchdog flagged while they were still in flight
+    pub long_tail: Vec<LongTailEvent>,
+    pub payload_limits: Option<PayloadLimits>,
+    // Confirmation run of the highest passing TPS after the ramp stopped on a failing step
+    pub peak_verification: Option<PeakVerification>,
+    // Files of the per-transaction log
+    pub tx_log_segments: Vec<PathBuf>,
+    pub hooks: Vec<HookRun>,
+    pub heatmap: Option<LatencyHeatmap>,
+    pub throughput: Vec<ThroughputSecond>,
+    // --segment-mins slices of the run, only when it spans more than one. The run's
+    // summary is their roll-up
+    pub segments: Vec<RunSegment>,
+    pub lost_transactions: Vec<LostTransaction>,
+    pub criteria: Vec<CriterionSummary>,
+    pub goal_seek: Option<GoalSeek>,
+    // Seed the steps were shuffled with, only with --shuffle-steps
+    pub shuffle_seed: Option<u64>,
+    // Transaction hashes the paymaster returned for more than one transaction, only with
+    // --unique-calldata. Every transaction differs, any duplicate is a response reused
+    pub duplicate_hashes: Option<u32>,
+    pub connection: Option<ConnectionReport>,
+    // Only with --transport ws
+    pub websocket: Option<WebSocketReport>,
+    // Pipeline the run was started from, when it ran in CI
+    pub ci: Option<CiMetadata>,
+    pub region: Option<String>,
+    // Outcome of --manifest, filled in once the run is over
+    pub acceptance: Option<Acceptance>,
+    // Without --no-guardrails
+    pub resource_guard: Option<GuardReport>,
+    // Per-account rate the run kept to, when limited
+    pub max_account_tps: Option<f64>,
+    pub payload_regression: Option<PayloadRegression>,
+}
+
+#[derive(Serialize)]
+pub struct SmokeReport {
+    pub run_tag: String,
+    pub passed: bool,
+    pub checks: Vec<SmokeCheck>,
+}
+
+// A traced transaction sent again by `replay-tx`
+#[derive(Serialize)]
+pub struct ReplayReport {
+    pub capture_id: String,
+    pub results_file: PathBuf,
+    // Error of the transaction in the run it was captured in
+    pub original_error: Option<String>,
+    pub latency_ms: Option<f64>,
+    pub error: Option<TransactionError>,
+    pub error_message: Option<String>,
+    // Failed again, not necessarily with the same error
+    pub reproduced: bool,
+    pub transaction_hashes: Vec<Felt>,
+    pub legs: Vec<TracedLeg>,
+}
+
+// One transaction of the smoke test
+#[derive(Serialize)]
+pub struct SmokeCheck {
+    pub scenario: String,
+    pub fee: Fee,
+    pub account: Felt,
+    pub latency_ms: Option<f64>,
+    pub error: Option<TransactionError>,
+    pub transaction_hashes: Vec<Felt>,
+    // On-chain outcome of every leg, only with --rpc-url
+    pub receipts: Vec<ReceiptStatus>,
+    pub passed: bool,
+}
+
+// Written by the panic hook, what the run got through before it panicked
+#[derive(Serialize)]
+pub struct CrashReport {
+    pub version: &'static str,
+    pub run_tag: String,
+    pub message: String,
+    pub location: Option<String>,
+    pub backtrace: String,
+    // Results of the steps that were complete, as in the results file
+    pub completed_steps: Vec<Value>,
+}
+
+// Outcome of `bench-signing`
+#[derive(Serialize)]
+pub struct SigningBenchmark {
+    pub threads: u32,
+    pub keys: u32,
+    pub duration_secs: u64,
+    // Transactions hashed and signed over all threads
+    pub signed: u64,
+    pub signed_per_sec: f64,
+    pub per_thread_per_sec: f64,
+    // Typed data hash and signature of one transaction
+    pub avg_hash_us: f64,
+    pub avg_sign_us: f64,
+}
+
+// Outcome of `accounts status`
+#[derive(Serialize)]
+pub struct PoolStatusReport {
+    pub pool_file: PathBuf,
+    // Accounts with at least one problem
+    pub degraded: u32,
+    pub accounts: Vec<AccountStatus>,
+}
+
+#[derive(Serialize)]
+pub struct AccountStatus {
+    pub address: Felt,
+    pub deployed: bool,
+    // None when it couldn't be asked, e.g. not deployed
+    pub signer_matches: Option<bool>,
+    pub nonce: Option<u64>,
+    // Gas token first
+    pub balances: Vec<TokenBalance>,
+    // Why the account is degraded, empty when it is healthy
+    pub problems: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct TokenBalance {
+    pub token: Felt,
+    // Base units, None when the token couldn't be queried
+    pub balance: Option<u128>,
+}
+
+// Outcome of `validate-config`
+#[derive(Serialize)]
+pub struct ValidationReport {
+    pub config: PathBuf,
+    pub passed: bool,
+    pub checks: Vec<ValidationCheck>,
+}
+
+#[derive(Serialize)]
+pub struct ValidationCheck {
+    pub check: String,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiptStatus {
+    Succeeded,
+    Reverted,
+    Rejected,
+    TimedOut,
+}
+
+#[derive(Serialize)]
+pub struct Acceptance {
+    pub environment: String,
+    pub passed: bool,
+    pub expectations: Vec<ExpectationResult>,
+}
+
+#[derive(Serialize)]
+pub struct ExpectationResult {
+    pub tps: u32,
+    pub min_success_rate: f64,
+    pub max_p95_latency_ms: Option<f64>,
+    // Highest target TPS of a step meeting the expectation
+    pub measured_tps: u32,
+    pub tps_delta: i64,
+    // Against the bounds, at the first step reaching the expected TPS
+    pub success_rate_delta: Option<f64>,
+    pub p95_latency_delta_ms: Option<f64>,
+    pub passed: bool,
+}
+
+#[derive(Serialize)]
+pub struct LostTransaction {
+    pub step: u32,
+    pub account: Felt,
+    // Hashes of the legs that were never seen
+    pub transaction_hashes: Vec<Felt>,
+    pub executed_at_secs: f64,
+}
+
+impl TestResult {
+    // Turns one of the step's successes into a failure, for a transaction found lost
+    // after the step was already summed up
+    pub fn mark_lost(&mut self) {
+        let metrics = &mut self.metrics;
+        if metrics.successful_txs == 0 {
+            return;
+        }
+        if metrics.effective_tps > 0.0 {
+            metrics.effective_tps *=
+                (metrics.successful_txs - 1) as f64 / metrics.successful_txs as f64;
+        }
+        metrics.successful_txs -= 1;
+        metrics.failed_txs += 1;
+        metrics.lost_txs += 1;
+        metrics.update_success_rate();
+        self.error_breakdown.lost += 1;
+    }
+}
+
+// Successful transactions counted by send time (rows) and latency (columns)
+// `counts` has one entry more than `latency_bounds_ms`: entry i counts latencies up to
+// bound i and above the previous one, the last entry everything above the last bound.
+// Time buckets without any transaction are left out
+#[derive(Serialize)]
+pub struct LatencyHeatmap {
+    pub time_bucket_secs: f64,
+    pub latency_bounds_ms: Vec<f64>,
+    pub rows: Vec<HeatmapRow>,
+}
+
+#[derive(Serialize)]
+pub struct HeatmapRow {
+    pub start_secs: f64,
+    pub counts: Vec<u32>,
+}
+
+// Summary of one --segment-mins slice of the run
+#[derive(Serialize)]
+pub struct RunSegment {
+    pub index: u32,
+    pub start_secs: u64,
+    pub end_secs: u64,
+    pub total_txs: u32,
+    pub successful_txs: u32,
+    pub success_rate: f64,
+    pub effective_tps: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    pub top_error: Option<String>,
+}
+
+// Executed transactions by the second of the run they were sent in (offered) and the
+// second they completed in, quotes are left out
+#[derive(Serialize, Default)]
+pub struct ThroughputSecond {
+    pub second: u64,
+    pub offered: u32,
+    pub completed: u32,
+    pub succeeded: u32,
+}
+
+// One execution of --pre-step-hook or --post-step-hook
+#[derive(Serialize)]
+pub struct HookRun {
+    pub stage: HookStage,
+    pub step: u32,
+    // None when the hook was killed by a signal
+    pub exit_code: Option<i32>,
+    pub duration_ms: f64,
+    // Tail of the output, long outputs are cut at the front
+    pub stdout: String,
+    pub stderr: String,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    Pre,
+    Post,
+}
+
+// Part of a shared accounts file a worker sent from
+#[derive(Serialize, Clone)]
+pub struct ShardAssignment {
+    pub index: u32,
+    pub count: u32,
+    pub accounts: Vec<Felt>,
+}
+
+#[derive(Serialize)]
+pub struct PeakVerification {
+    pub target_tps: u32,
+    pub passed: bool,
+    pub result: TestResult,
+}
+
+// Outcome of growing the payload across steps, a step fails the way a TPS step does
+// when its success rate drops to 95% or below
+#[derive(Serialize)]
+pub struct PayloadLimits {
+    pub growth: Growth,
+    pub max_passing_size: Option<u32>,
+    pub first_failing_size: Option<u32>,
+    // Errors of the first failing step
+    pub failing_errors: Option<ErrorBreakdown>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct LongTailEvent {
+    pub step: u32,
+    pub account: Felt,
+    pub sent_at_ms: u64,
+    // How long the request had been pending when it was flagged
+    pub flagged_after_ms: f64,
+    // Running median latency at that moment
+    pub median_ms: f64,
+}
+
+#[derive(Serialize, Default, Clone)]
+pub struct HealthReport {
+    pub polls: u32,
+    pub unavailable_polls: u32,
+    // Every flip between available and unavailable, in order
+    pub changes: Vec<AvailabilityChange>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct AvailabilityChange {
+    pub at_secs: f64,
+    pub available: bool,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct StatusPageReport {
+    pub url: String,
+    pub fetches: u32,
+    // Fetches that failed or got an HTTP error back
+    pub failed_fetches: u32,
+    // The first fetch and every one whose status, --status-field or failure differs from
+    // the one before, in order
+    pub samples: Vec<StatusSample>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct StatusSample {
+    pub at_secs: f64,
+    // HTTP status, None when the fetch failed
+    pub status: Option<u16>,
+    pub body: Option<Value>,
+    pub error: Option<String>,
+}
+
+// The same average TPS delivered evenly and in bursts of `burst_size`
+#[derive(Serialize)]
+pub struct BurstComparison {
+    pub target_tps: u32,
+    pub burst_size: u32,
+    pub smooth: LatencyDistribution,
+    pub burst: LatencyDistribution,
+}
+
+// The same TPS paid in each gas token and sponsored. Deltas are taken against the
+// sponsored run, which skips the token conversion, so they show what the conversion costs.
+// Fees of different gas tokens only compare in STRK
+#[derive(Serialize)]
+pub struct FeeModeComparison {
+    pub target_tps: u32,
+    pub modes: Vec<FeeModeRun>,
+}
+
+#[derive(Serialize)]
+pub struct FeeModeRun {
+    pub fee: Fee,
+    pub latency: LatencyDistribution,
+    pub avg_fee_in_gas_token: f64,
+    pub avg_fee_in_strk: f64,
+    pub avg_fee_delta_in_strk: f64,
+    pub avg_latency_delta_ms: f64,
+    pub p95_latency_delta_ms: f64,
+    pub success_rate_delta: f64,
+}
+
+impl FeeModeComparison {
+    // The sponsored run comes last
+    pub fn new(target_tps: u32, runs: &[TestResult]) -> FeeModeComparison {
+        let baseline = runs.last().map(|r| &r.metrics);
+        let modes = runs
+            .iter()
+            .map(|run| FeeModeRun::new(run.fee, &run.metrics, baseline))
+            .collect();
+        FeeModeComparison { target_tps, modes }
+    }
+}
+
+impl FeeModeRun {
+    pub fn new(fee: Fee, metrics: &Metrics, baseline: Option<&Metrics>) -> FeeModeRun {
+        let (cost, avg, p95, success) = baseline.map_or((0.0, 0.0, 0.0, 0.0), |b| {
+            (
+                b.avg_fee_in_strk,
+                b.avg_latency_ms,
+                b.p95_latency_ms,
+                b.success_rate,
+            )
+        });
+        FeeModeRun {
+            fee,
+            latency: LatencyDistribution::from(metrics),
+            avg_fee_in_gas_token: metrics.avg_fee_in_gas_token,
+            avg_fee_in_strk: metrics.avg_fee_in_strk,
+            avg_fee_delta_in_strk: metrics.avg_fee_in_strk - cost,
+            avg_latency_delta_ms: metrics.avg_latency_ms - avg,
+            p95_latency_delta_ms: metrics.p95_latency_ms - p95,
+            success_rate_delta: metrics.success_rate - success,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct LatencyDistribution {
+    pub success_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+    pub latency_stddev_ms: f64,
+}
+
+impl From<&Metrics> for LatencyDistribution {
+    fn from(metrics: &Metrics) -> Self {
+        LatencyDistribution {
+            success_rate: metrics.success_rate,
+            avg_latency_ms: metrics.avg_latency_ms,
+            p50_latency_ms: metrics.p50_latency_ms,
+            p95_latency_ms: metrics.p95_latency_ms,
+            p99_latency_ms: metrics.p99_latency_ms,
+            latency_stddev_ms: metrics.latency_stddev_ms,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct QuarantineEvent {
+    pub address: Felt,
+    pub quarantined_at_secs: f64,
+    pub recovered_at_secs: Option<f64>,
+    // Settled on-chain nonce observed before the account was put back in rotation
+    pub chain_nonce: Option<Felt>,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    Completed,
+    StopRequested,
+    // A step fell below the sustainable success rate with --verify-peak set
+    StepFailed,
+    // --stop-after-failures steps in a row fell below the sustainable success rate
+    Saturated,
+    // --max-transactions requests were sent before the last step ended
+    MaxTransactions,
+    // The generator came close to its memory or file descriptor limit, see --abort-at
+    ResourceLimit,
+    // --p99-target-ms narrowed the equilibrium rate down to a single TPS
+    Converged,
+}
+
+// Progress of a run in flight, as reported by the control API
+#[derive(Serialize)]
+pub struct LiveSnapshot {
+    pub step: u32,
+    pub target_tps: u32,
+    pub sent: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub long_tail: u64,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum FailoverTrigger {
+    Scheduled,
+    ConsecutiveFailures,
+}
+
+#[derive(Serialize, Clone)]
+pub struct FailoverReport {
+    pub trigger: FailoverTrigger,
+    pub switched_at_secs: f64,
+    // Time from the first failure of the burst to the switch
+    pub detection_time_ms: Option<f64>,
+    // Consecutive failures around the switch, until the first success on the fallback
+    pub error_burst_size: u32,
+}
+
+// What the paymaster under test reported about itself at startup
+#[derive(Serialize, Clone)]
+pub struct PaymasterInfo {
+    pub version: Option<String>,
+    // JSON-RPC methods listed by `rpc.discover`, None when the server doesn't publish them
+    pub methods: Option<Vec<String>>,
+    pub supported_tokens: Vec<Felt>,
+}
+
+// Result files of several workers put together, see `merge`
+#[derive(Serialize)]
+pub struct MergedResults {
+    pub files: Vec<PathBuf>,
+    pub regions: Vec<RegionMetrics>,
+    // Step by step, the steps of all workers with the same number are summed up
+    pub steps: Vec<MergedStep>,
+}
+
+#[derive(Serialize)]
+pub struct MergedStep {
+    pub step: u32,
+    // Sum over the workers
+    pub target_tps: u32,
+    pub regions: Vec<RegionMetrics>,
+}
+
+// Quantiles are the means of the workers' quantiles weighted by their successful
+// transactions, an approximation as quantiles don't add up
+#[derive(Serialize)]
+pub struct RegionMetrics {
+    pub region: String,
+    pub workers: u32,
+    pub total_txs: u64,
+    pub successful_txs: u64,
+    pub success_rate: f64,
+    pub avg_latency_ms: f64,
+    pub p95_latency_ms: f64,
+    pub p99_latency_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct WebSocketReport {
+    // Times the connection dropped and was opened again
+    pub reconnects: u32,
+    // Most requests waiting for their response on the socket at once
+    pub max_pending_requests: u32,
+}
+
+#[derive(Serialize)]
+pub struct CiMetadata {
+    pub provider: String,
+    pub commit: Option<String>,
+    pub branch: Option<String>,
+    pub job_url: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -42,3 +944,304 @@ pub struct TestSummary {
     pub total_transactions: u32,
     pub overall_success_rate: f64,
 }
+
+// Summary of the run under one --success criterion
+#[derive(Serialize)]
+pub struct CriterionSummary {
+    pub criterion: SuccessCriterion,
+    pub max_sustainable_tps: u32,
+    pub overall_success_rate: f64,
+}
+
+// A transaction of the --trace-sample, complete enough to replay it against the paymaster
+#[derive(Serialize)]
+pub struct TransactionTrace {
+    // `<run tag>-<step>-<n>`, what `replay-tx` is given to send it again
+    pub capture_id: String,
+    pub account: Felt,
+    pub sent_at_ms: u64,
+    pub latency_ms: Option<f64>,
+    pub error: Option<TransactionError>,
+    pub phases: PhaseTimings,
+    // One per attempt of every leg, retries included
+    pub legs: Vec<TracedLeg>,
+    pub transaction_hashes: Vec<Felt>,
+    // On-chain outcome of every hash, looked up once the run is over and only with --rpc-url
+    pub receipts: Vec<ReceiptStatus>,
+}
+
+// Requests and responses as they went over the wire, up to where the attempt failed
+#[derive(Serialize, Default)]
+pub struct TracedLeg {
+    pub build_request: Option<Value>,
+    pub build_response: Option<Value>,
+    pub execute_request: Option<Value>,
+    pub execute_response: Option<Value>,
+    pub error: Option<String>,
+    pub build_ms: f64,
+    pub sign_ms: f64,
+    pub execute_ms: f64,
+}
+
+// How the endpoint resolved and how long a TCP connect to each of its addresses took,
+// measured once before the run
+#[derive(Serialize)]
+pub struct ConnectionReport {
+    pub host: String,
+    pub ip_family: IpFamily,
+    // None when the endpoint is an IP address
+    pub dns_ms: Option<f64>,
+    pub addresses: Vec<AddressTiming>,
+}
+
+#[derive(Serialize)]
+pub struct AddressTiming {
+    pub address: SocketAddr,
+    pub ipv6: bool,
+    // Of the --ip-family the run sends over
+    pub used: bool,
+    pub connect_ms: Option<f64>,
+    pub error: Option<String>,
+}
+
+// Outcome of --p99-target-ms
+#[derive(Serialize)]
+pub struct GoalSeek {
+    pub p99_target_ms: f64,
+    // Highest rate whose p99 stayed under the target, 0 if none did
+    pub equilibrium_tps: u32,
+    // Lowest rate that went over it, none if every rate up to --max-tps met the target
+    pub lowest_missed_tps: Option<u32>,
+    // False when --steps ran out before the range was down to a single TPS
+    pub converged: bool,
+}
+
+// One run of the daemon in its rolling trend.json
+#[derive(Serialize, Deserialize)]
+pub struct TrendEntry {
+    pub started_at_unix_ms: u64,
+    pub label: Option<String>,
+    pub results_file: PathBuf,
+    pub max_sustainable_tps: u32,
+    // Of the step at the max sustainable TPS
+    pub p95_latency_ms: Option<f64>,
+    pub overall_success_rate: f64,
+    // Metrics worse than in the runs before, see `trend`
+    #[serde(default)]
+    pub regressions: Vec<TrendMetric>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendMetric {
+    MaxSustainableTps,
+    P95Latency,
+    SuccessRate,
+}
+
+impl TrendEntry {
+    pub fn new(results: &StressTestResults, results_file: &Path) -> TrendEntry {
+        let max_tps = results.summary.max_sustainable_tps;
+        TrendEntry {
+            started_at_unix_ms: results.started_at_unix_ms,
+            label: results.label.clone(),
+            results_file: results_file.to_path_buf(),
+            max_sustainable_tps: max_tps,
+            p95_latency_ms: results
+                .results
+                .iter()
+                .find(|r| max_tps > 0 && r.metrics.target_tps == max_tps)
+                .map(|r| r.metrics.p95_latency_ms),
+            overall_success_rate: results.summary.overall_success_rate,
+            regressions: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ProbeResults {
+    pub endpoint: String,
+    pub run_tag: String,
+    // Highest offered rate that went through without any pushback
+    pub last_clean_rpm: Option<u32>,
+    // Accepted rate at the step that hit the limit
+    pub quota_rpm: Option<f64>,
+    pub stopped_by: ProbeStop,
+    pub steps: Vec<ProbeStep>,
+}
+
+#[derive(Serialize, Default)]
+pub struct ProbeStep {
+    pub offered_rpm: u32,
+    pub sent: u32,
+    pub accepted: u32,
+    pub rate_limited: u32,
+    pub rejected: u32,
+    pub failed: u32,
+    pub accepted_rpm: f64,
+}
+
+#[derive(Serialize)]
+pub struct FundReport {
+    pub faucet: String,
+    pub funded: u32,
+    pub requests: Vec<FaucetRequest>,
+}
+
+#[derive(Serialize)]
+pub struct FaucetRequest {
+    pub account: Felt,
+    // None when the faucet couldn't be reached
+    pub status: Option<u16>,
+    pub funded: bool,
+    // Body of a refusal or the transport error
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct CacheProbeResults {
+    pub endpoint: String,
+    pub run_tag: String,
+    // Repeats of one build request, the way the built-in scenarios send them
+    pub identical: CacheProbeSample,
+    // Requests differing from it only in the amount
+    pub variant: CacheProbeSample,
+    // How much lower the median of the repeats is, as a share of the variants' median
+    pub p50_speedup: f64,
+    pub caching_suspected: bool,
+}
+
+#[derive(Serialize)]
+pub struct CacheProbeSample {
+    pub sent: u32,
+    pub failed: u32,
+    pub avg_latency_ms: f64,
+    pub p50_latency_ms: f64,
+    pub p95_latency_ms: f64,
+}
+
+#[derive(Serialize)]
+pub struct BatchingResults {
+    pub endpoint: String,
+    pub run_tag: String,
+    // Execute requests sent per batch size
+    pub requests: u32,
+    pub sizes: Vec<BatchSizeResult>,
+}
+
+// Latencies are of the whole execute request, every transaction of a batch waits for all
+// of them
+#[derive(Serialize)]
+pub struct BatchSizeResult {
+    pub batch_size: u32,
+    pub transactions: u32,
+    // Failed to build, sign or execute
+    pub failed: u32,
+    pub avg_request_ms: f64,
+    pub p50_request_ms: f64,
+    pub p95_request_ms: f64,
+    // Executed transactions per second of time spent in execute requests
+    pub throughput_tps: f64,
+    // Throughput against single executes, when batch size 1 was measured
+    pub throughput_gain: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct ColdStartResults {
+    pub endpoint: String,
+    pub run_tag: String,
+    // Transactions per burst and bursts per idle gap
+    pub burst: u32,
+    pub rounds: u32,
+    pub gaps: Vec<ColdStartGap>,
+}
+
+#[derive(Serialize)]
+pub struct ColdStartGap {
+    pub idle_secs: u64,
+    // First transaction of every burst after the gap
+    pub first: CacheProbeSample,
+    // The rest of the bursts
+    pub warm: CacheProbeSample,
+    // Latency the idle time adds, difference of the two medians
+    pub p50_penalty_ms: f64,
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ProbeStop {
+    RateLimited,
+    Rejected,
+    // Reached --max-rpm without hitting a limit
+    MaxRate,
+}
+
+// One line of the per-transaction log
+// `*_ms` are monotonic offsets from the start of the run and give the latency, `*_unix_ms`
+// are the same instants on the wall clock, so logs of several workers can be merged by
+// sorting on `sent_at_unix_ms` and told apart by `run_tag`
+#[derive(Serialize)]
+pub struct TxRecord {
+    pub run_tag: String,
+    pub step: u32,
+    pub target_tps: u32,
+    pub sent_at_ms: u64,
+    pub sent_at_unix_ms: u64,
+    pub completed_at_ms: u64,
+    pub completed_at_unix_ms: u64,
+    // Quote request, built without being executed
+    pub build_only: bool,
+    pub in_flight: u32,
+    pub latency_ms: Option<f64>,
+    pub error: Option<TransactionError>,
+    pub transaction_hashes: Vec<Felt>,
+    // Ids the paymaster's execute responses carried, to find the transaction in its logs
+    pub tracking_ids: Vec<Felt>,
+}
+
+// Outcome of the self-test, metrics the tool reported against the injected ones
+#[derive(Serialize)]
+pub struct SelfTestReport {
+    pub error_rate: f64,
+    pub latency_ms: u64,
+    pub tolerance: f64,
+    pub passed: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+#[derive(Serialize)]
+pub struct SelfTestCheck {
+    pub step: u32,
+    pub check: String,
+    pub expected: f64,
+    pub measured: f64,
+    pub passed: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fee_modes_are_costed_against_the_sponsored_run() {
+        let sponsored = Metrics {
+            avg_fee_in_strk: 1000.0,
+            avg_latency_ms: 200.0,
+            ..Default::default()
+        };
+        let paid = Metrics {
+            avg_fee_in_gas_token: 30.0,
+            avg_fee_in_strk: 1250.0,
+            avg_latency_ms: 260.0,
+            ..Default::default()
+        };
+        let run = FeeModeRun::new(Fee::GasToken(Felt::ONE), &paid, Some(&sponsored));
+        assert_eq!(run.avg_fee_in_gas_token, 30.0);
+        assert_eq!(run.avg_fee_in_strk, 1250.0);
+        assert_eq!(run.avg_fee_delta_in_strk, 250.0);
+        assert_eq!(run.avg_latency_delta_ms, 60.0);
+
+        let baseline = FeeModeRun::new(Fee::Sponsored, &sponsored, Some(&sponsored));
+        assert_eq!(baseline.avg_fee_delta_in_strk, 0.0);
+    }
+}
@@ -0,0 +1,48 @@
+use object_store::path::Path as StorePath;
+use object_store::{parse_url_opts, ObjectStore, PutPayload};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+use crate::TestError;
+
+// Copies the results, and optionally the run's artifacts, to `s3://bucket/prefix` or
+// `gs://bucket/prefix` once the run completed. Each run goes to its own
+// `<prefix>/<unix time>[-<label>]/` folder, every file under the file name of its path
+// Credentials are taken from the usual AWS_* / GOOGLE_* environment variables
+pub async fn upload(
+    destination: &str,
+    label: Option<&str>,
+    files: Vec<(PathBuf, Vec<u8>)>,
+) -> Result<(), TestError> {
+    let url = Url::parse(destination)?;
+    let options = std::env::vars()
+        .filter(|(key, _)| key.starts_with("AWS_") || key.starts_with("GOOGLE_"))
+        .map(|(key, value)| (key.to_lowercase(), value));
+    let (store, prefix) = parse_url_opts(&url, options)?;
+
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let folder = match label {
+        Some(label) => format!("{}-{}", started, label.replace('/', "_")),
+        None => started.to_string(),
+    };
+    let base = prefix.child(folder);
+
+    for (path, content) in files {
+        let Some(name) = path.file_name() else {
+            continue;
+        };
+        put(&store, base.child(name.to_string_lossy().as_ref()), content).await?;
+    }
+    println!(
+        "Results uploaded to {}/{}",
+        destination.trim_end_matches('/'),
+        base
+    );
+    Ok(())
+}
+
+async fn put(store: &dyn ObjectStore, path: StorePath, content: Vec<u8>) -> Result<(), TestError> {
+    store.put(&path, PutPayload::from(content)).await?;
+    Ok(())
+}
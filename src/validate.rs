@@ -0,0 +1,216 @@
+use paymaster_rpc::PaymasterAPIClient;
+use serde_json::Value;
+use starknet::core::types::{BlockId, BlockTag, ContractClass, Felt};
+use starknet::providers::jsonrpc::HttpTransport;
+use starknet::providers::{JsonRpcClient, Provider, Url};
+use std::path::Path;
+
+use crate::abi::{calldata_len, function_name};
+use crate::account::{load_accounts, verify_account, Account};
+use crate::client::build_client;
+use crate::daemon::campaign;
+use crate::discover::discover;
+use crate::run::LinearArgs;
+use crate::scenario::{CustomScenario, Workload};
+use crate::types::*;
+use crate::TestError;
+
+#[derive(Default)]
+struct Checks(Vec<ValidationCheck>);
+
+impl Checks {
+    fn record(&mut self, check: impl Into<String>, outcome: Result<(), TestError>) {
+        let check = ValidationCheck {
+            check: check.into(),
+            passed: outcome.is_ok(),
+            error: outcome.err().map(|e| e.to_string()),
+        };
+        println!(
+            "  [{}] {}{}",
+            if check.passed { "PASS" } else { "FAIL" },
+            check.check,
+            check
+                .error
+                .as_ref()
+                .map(|e| format!(": {}", e))
+                .unwrap_or_default()
+        );
+        self.0.push(check);
+    }
+}
+
+// Everything a `linear --config` run checks while it starts up and more, without sending a
+// transaction. On-chain checks need the config's --rpc-url, they are skipped without it
+pub async fn validate_config(path: &Path) -> Result<ValidationReport, TestError> {
+    let args = campaign(path)?;
+    println!("Validating {}", path.display());
+    let mut checks = Checks::default();
+
+    let network = args.network.resolve();
+    let gas_token = network.as_ref().ok().map(|n| n.gas_token);
+    match network {
+        Ok(network) => {
+            checks.record("network", network.check_max_tps(args.ramp.peak_tps()));
+            let provider = match &args.rpc_url {
+                Some(url) => Some(JsonRpcClient::new(HttpTransport::new(Url::parse(url)?))),
+                None => None,
+            };
+            if let Some(provider) = &provider {
+                checks.record("rpc chain", network.check_chain(provider).await);
+            }
+            let endpoint = async {
+                let client = build_client(&args.client).await?;
+                if !client.is_available().await? {
+                    return Err(format!("{} is not available", args.client.endpoint).into());
+                }
+                discover(&client).await
+            }
+            .await;
+            let paymaster = match endpoint {
+                Ok(paymaster) => {
+                    checks.record("endpoint", paymaster.check_required());
+                    Some(paymaster)
+                }
+                Err(e) => {
+                    checks.record("endpoint", Err(e));
+                    None
+                }
+            };
+            let mut tokens = vec![network.gas_token];
+            tokens.extend(&args.ramp.compare_gas_token);
+            for token in tokens {
+                let name = format!("gas token {:#x}", token);
+                if let Some(paymaster) = &paymaster {
+                    let accepted = if paymaster.supported_tokens.contains(&token) {
+                        Ok(())
+                    } else {
+                        Err("not accepted by the paymaster".into())
+                    };
+                    checks.record(name.clone(), accepted);
+                }
+                if let Some(provider) = &provider {
+                    let deployed = provider
+                        .get_class_hash_at(BlockId::Tag(BlockTag::Pending), token)
+                        .await
+                        .map(|_| ())
+                        .map_err(|e| format!("no contract on this network: {}", e).into());
+                    checks.record(format!("{} deployed", name), deployed);
+                }
+            }
+            validate_accounts(&args, provider.as_ref(), gas_token, &mut checks).await?;
+        }
+        Err(e) => {
+            checks.record("network", Err(e));
+        }
+    }
+
+    let passed = checks.0.iter().all(|c| c.passed);
+    println!(
+        "{}",
+        if passed {
+            "Config is valid"
+        } else {
+            "Config has errors"
+        }
+    );
+    Ok(ValidationReport {
+        config: path.to_path_buf(),
+        passed,
+        checks: checks.0,
+    })
+}
+
+async fn validate_accounts(
+    args: &LinearArgs,
+    provider: Option<&JsonRpcClient<HttpTransport>>,
+    gas_token: Option<Felt>,
+    checks: &mut Checks,
+) -> Result<(), TestError> {
+    let config = envy::from_env::<Config>()?;
+    let accounts = match &args.pool.accounts {
+        Some(path) => load_accounts(path),
+        None => args.account.resolve(&config.private_key).map(|a| vec![a]),
+    };
+    let accounts = match accounts {
+        Ok(accounts) => {
+            checks.record("account keys", Ok(()));
+            accounts
+        }
+        Err(e) => {
+            checks.record("account keys", Err(e));
+            return Ok(());
+        }
+    };
+    let mut roles = vec![accounts[0].clone()];
+    if let (Some(address), Some(key)) = (&args.spender_address, &config.spender_private_key) {
+        match Account::new(address, key) {
+            Ok(spender) => roles.push(spender),
+            Err(e) => checks.record("spender key", Err(e)),
+        }
+    }
+    if let Some(provider) = provider {
+        for account in accounts.iter().chain(&roles[1..]) {
+            checks.record(
+                format!("account {:#x}", account.address),
+                verify_account(provider, account).await,
+            );
+        }
+    }
+
+    let workload = match &args.scenario_file {
+        Some(path) => CustomScenario::load(path).map(Workload::File),
+        None => Ok(Workload::Builtin {
+            scenario: args.scenario,
+            chain_length: args.chain_length,
+        }),
+    };
+    let legs = workload.and_then(|w| w.legs(&roles, gas_token.unwrap_or_default()));
+    let legs = match legs {
+        Ok(legs) => {
+            checks.record("scenario", Ok(()));
+            legs
+        }
+        Err(e) => {
+            checks.record("scenario", Err(e));
+            return Ok(());
+        }
+    };
+    let Some(provider) = provider else {
+        return Ok(());
+    };
+    // Calldata against the ABI of the contract actually deployed at each target
+    for call in legs.iter().flat_map(|leg| &leg.calls) {
+        let abi = match provider
+            .get_class_at(BlockId::Tag(BlockTag::Pending), call.to)
+            .await
+        {
+            Ok(ContractClass::Sierra(class)) => serde_json::from_str::<Value>(&class.abi)?,
+            Ok(ContractClass::Legacy(class)) => serde_json::to_value(&class.abi)?,
+            Err(e) => {
+                checks.record(
+                    format!("call to {:#x}", call.to),
+                    Err(format!("no contract on this network: {}", e).into()),
+                );
+                continue;
+            }
+        };
+        let Some(function) = function_name(&abi, call.selector) else {
+            checks.record(
+                format!("call to {:#x}", call.to),
+                Err(format!("no function with selector {:#x}", call.selector).into()),
+            );
+            continue;
+        };
+        let arity = match calldata_len(&abi, &function)? {
+            Some(expected) if expected != call.calldata.len() => Err(format!(
+                "takes {} calldata felts, the scenario passes {}",
+                expected,
+                call.calldata.len()
+            )
+            .into()),
+            _ => Ok(()),
+        };
+        checks.record(format!("{} on {:#x}", function, call.to), arity);
+    }
+    Ok(())
+}
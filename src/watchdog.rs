@@ -0,0 +1,139 @@
+use clap::Args;
+use starknet::core::types::Felt;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Instant};
+
+use crate::ramp::LiveStats;
+use crate::stats::median;
+use crate::types::LongTailEvent;
+
+// Latencies the running median is computed over
+const WINDOW: usize = 256;
+// Below this many samples the median is too noisy to judge anything against
+const MIN_SAMPLES: usize = 10;
+const CHECK_PERIOD: Duration = Duration::from_millis(250);
+
+#[derive(Args, Clone)]
+pub struct WatchdogArgs {
+    /// Flag in-flight requests taking longer than this multiple of the running median latency
+    #[arg(long)]
+    pub watchdog_multiple: Option<f64>,
+}
+
+struct Pending {
+    step: u32,
+    account: Felt,
+    sent: Instant,
+    sent_at_ms: u64,
+    flagged: bool,
+}
+
+#[derive(Default)]
+struct State {
+    next_id: u64,
+    pending: HashMap<u64, Pending>,
+    recent: VecDeque<f64>,
+    events: Vec<LongTailEvent>,
+}
+
+// Watches requests while they are still in flight so a hanging paymaster is noticed
+// right away instead of when the step is aggregated
+pub struct Watchdog {
+    multiple: f64,
+    state: Mutex<State>,
+    live: Arc<LiveStats>,
+}
+
+impl Watchdog {
+    pub fn new(multiple: f64, live: Arc<LiveStats>) -> Arc<Watchdog> {
+        Arc::new(Watchdog {
+            multiple,
+            state: Mutex::new(State::default()),
+            live,
+        })
+    }
+
+    pub fn start(self: &Arc<Self>) -> JoinHandle<()> {
+        let watchdog = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = interval(CHECK_PERIOD);
+            loop {
+                ticker.tick().await;
+                watchdog.check();
+            }
+        })
+    }
+
+    pub fn register(&self, step: u32, account: Felt, sent_at_ms: u64) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pending.insert(
+            id,
+            Pending {
+                step,
+                account,
+                sent: Instant::now(),
+                sent_at_ms,
+                flagged: false,
+            },
+        );
+        id
+    }
+
+    // Only successful latencies feed the median, failures are often fast rejections
+    pub fn complete(&self, id: u64, latency_ms: Option<f64>) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.remove(&id);
+        if let Some(latency) = latency_ms {
+            if state.recent.len() == WINDOW {
+                state.recent.pop_front();
+            }
+            state.recent.push_back(latency);
+        }
+    }
+
+    pub fn events(&self) -> Vec<LongTailEvent> {
+        self.state.lock().unwrap().events.clone()
+    }
+
+    fn check(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.recent.len() < MIN_SAMPLES {
+            return;
+        }
+        let median_ms = median(state.recent.make_contiguous());
+        let threshold_ms = median_ms * self.multiple;
+        let mut flagged = Vec::new();
+        for pending in state.pending.values_mut() {
+            let elapsed_ms = pending.sent.elapsed().as_secs_f64() * 1000.0;
+            if pending.flagged || elapsed_ms <= threshold_ms {
+                continue;
+            }
+            pending.flagged = true;
+            eprintln!(
+                "Watchdog: request from {:#x} sent at {} ms still pending after {:.0} ms ({:.0}x median of {:.0} ms)",
+                pending.account,
+                pending.sent_at_ms,
+                elapsed_ms,
+                elapsed_ms / median_ms,
+                median_ms
+            );
+            flagged.push(LongTailEvent {
+                step: pending.step,
+                account: pending.account,
+                sent_at_ms: pending.sent_at_ms,
+                flagged_after_ms: elapsed_ms,
+                median_ms,
+            });
+        }
+        self.live
+            .long_tail
+            .fetch_add(flagged.len() as u64, Ordering::Relaxed);
+        state.events.extend(flagged);
+    }
+}